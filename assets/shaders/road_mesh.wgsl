@@ -5,12 +5,22 @@ var sprite_texture: texture_2d<f32>;
 @group(1) @binding(1)
 var sprite_sampler: sampler;
 
+struct RoadMaterial {
+    color: vec4<f32>,
+    center_of_mass: vec2<f32>,
+    show_com: f32,
+};
+
+@group(1) @binding(2)
+var<uniform> material: RoadMaterial;
+
 @fragment
 fn fragment(
     @builtin(position) position: vec4<f32>,
     #import bevy_sprite::mesh2d_vertex_output
 ) -> @location(0) vec4<f32> {
-    var color = textureSample(sprite_texture, sprite_sampler, uv); 
+    // untextured bands sample the default white image
+    var color = textureSample(sprite_texture, sprite_sampler, uv) * material.color;
 
     return color;
 }
\ No newline at end of file
@@ -2,17 +2,22 @@ use super::buttons::{ButtonInteraction, ButtonState, UiButton};
 // use crate::cam::Cam;
 use crate::materials::ButtonMat;
 
-use crate::mesh::{FillMesh2dMaterial, MeshId, PenMesh, RoadMesh2dMaterial, StartMovingMesh};
+use crate::mesh::{
+    FillMesh2dMaterial, MeshId, PenMesh, RoadBandMesh, RoadMesh2dMaterial, StartMovingMesh,
+};
 use crate::model::util::Maps;
 use crate::model::{
     get_close_anchor, get_close_mesh, get_close_still_anchor, AchorEdgeQuad, Anchor, AnchorEdge,
     Bezier, BezierId, BezierParent, ColorButton, CurrentlySelecting, Globals, GroupId,
-    HistoryAction, MainUi, MoveAnchorEvent, MovingAnchor, OfficialLatch, SelectingBoxQuad,
-    SpawningCurve, UiAction, UiBoard,
+    HistoryAction, MainUi, MoveAnchorEvent, MovingAnchor, OfficialLatch, Ruler, SelectedBoxQuad,
+    SelectingBoxQuad, Selection, SelectionChoice, SpawningCurve, UiAction, UiBoard,
 };
 
 use bevy::render::camera::OrthographicProjection;
-use bevy::{input::mouse::MouseWheel, prelude::*, window::CursorMoved};
+use bevy::{input::mouse::MouseWheel, prelude::*, sprite::Mesh2dHandle, window::CursorMoved};
+use bevy_egui::EguiContext;
+
+use std::collections::HashSet;
 
 pub struct Cursor {
     pub position: Vec2,
@@ -91,6 +96,67 @@ pub enum Action {
     MakeMesh,
     SpawnRoad,
     StartMoveAnchor,
+    AddPathMarker,
+    RemovePathMarker,
+    ToggleLutSampling,
+    AddWidthKeyframe,
+    RemoveWidthKeyframe,
+    MakeStroke,
+    BakeMesh,
+    Duplicate,
+    RecolorMesh,
+    ToggleSnapping,
+    ToggleRuler,
+    CycleShapeTool,
+    CycleSymmetry,
+    LoadReferenceImage,
+    SpawnShape,
+    Copy,
+    Cut,
+    Paste,
+    SelectAll,
+    InvertSelection,
+    SelectConnected,
+    Nudge(Vec2),
+    Align(Alignment),
+    Transform(Transformation),
+}
+
+/// Alignment of the selected curves, groups, meshes and anchors, using their bounding boxes
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Alignment {
+    Left,
+    Right,
+    Top,
+    Bottom,
+    // same x for all the centers
+    HorizontalCenter,
+    // same y for all the centers
+    VerticalCenter,
+    // evenly spaced centers between the two outermost ones
+    DistributeHorizontally,
+    DistributeVertically,
+}
+
+/// Flip or rotation of the selected curves around the center of their bounding box
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Transformation {
+    FlipHorizontally,
+    FlipVertically,
+    // counterclockwise, in degrees
+    Rotate(f32),
+}
+
+impl Transformation {
+    pub fn apply(&self, center: Vec2, position: Vec2) -> Vec2 {
+        let relative = position - center;
+        let transformed = match self {
+            Transformation::FlipHorizontally => Vec2::new(-relative.x, relative.y),
+            Transformation::FlipVertically => Vec2::new(relative.x, -relative.y),
+            Transformation::Rotate(angle) => Mat2::from_angle(angle.to_radians()) * relative,
+        };
+        center + transformed
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -106,6 +172,7 @@ pub fn send_action(
     mouse_button_input: Res<Input<MouseButton>>,
     mut mouse_wheel_events: EventReader<MouseWheel>,
     mut button_query: Query<(&ButtonState, &UiButton)>,
+    mut egui_context: ResMut<EguiContext>,
 ) {
     // send Action event upon UI button press
     for ui_button in ui_event_reader.iter() {
@@ -137,6 +204,11 @@ pub fn send_action(
         }
     }
 
+    // the keys go to the text field being edited in an egui panel, such as the properties panel
+    if egui_context.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+
     let mouse_pressed = mouse_button_input.pressed(MouseButton::Left);
 
     let mut mouse_wheel_up = false;
@@ -158,6 +230,33 @@ pub fn send_action(
     let _pressed_z = keyboard_input.just_pressed(KeyCode::Z);
     let _pressed_t = keyboard_input.just_pressed(KeyCode::T);
     let _pressed_delete = keyboard_input.just_pressed(KeyCode::Delete);
+    let _pressed_m = keyboard_input.just_pressed(KeyCode::M);
+    let _pressed_k = keyboard_input.just_pressed(KeyCode::K);
+    let _pressed_o = keyboard_input.just_pressed(KeyCode::O);
+    let _pressed_b = keyboard_input.just_pressed(KeyCode::B);
+    let _pressed_d = keyboard_input.just_pressed(KeyCode::D);
+    let _pressed_c = keyboard_input.just_pressed(KeyCode::C);
+    let _pressed_r = keyboard_input.just_pressed(KeyCode::R);
+    let _pressed_p = keyboard_input.just_pressed(KeyCode::P);
+    let _pressed_x = keyboard_input.just_pressed(KeyCode::X);
+    let _pressed_v = keyboard_input.just_pressed(KeyCode::V);
+    let _pressed_a = keyboard_input.just_pressed(KeyCode::A);
+    let _pressed_i = keyboard_input.just_pressed(KeyCode::I);
+    let _pressed_e = keyboard_input.just_pressed(KeyCode::E);
+    let _pressed_f = keyboard_input.just_pressed(KeyCode::F);
+
+    // the arrow keys nudge the selection by one unit
+    let mut _nudge = Vec2::ZERO;
+    for (key_code, direction) in [
+        (KeyCode::Left, -Vec2::X),
+        (KeyCode::Right, Vec2::X),
+        (KeyCode::Up, Vec2::Y),
+        (KeyCode::Down, -Vec2::Y),
+    ] {
+        if keyboard_input.just_pressed(key_code) {
+            _nudge += direction;
+        }
+    }
 
     // match keys / mouse buttons / mouse wheel combination and send event to corresponding action
     match (
@@ -180,6 +279,68 @@ pub fn send_action(
         (false, true, false) if mouse_wheel_down => action_event_writer.send(Action::ScaleDown),
         (false, false, false) if _pressed_delete => action_event_writer.send(Action::Delete(false)),
         (true, false, false) if _pressed_t => action_event_writer.send(Action::ComputeLut),
+        (false, true, false) if _pressed_t => action_event_writer.send(Action::ToggleLutSampling),
+        (true, false, false) if _pressed_m => action_event_writer.send(Action::AddPathMarker),
+        (false, true, false) if _pressed_m => action_event_writer.send(Action::RemovePathMarker),
+        (true, false, false) if _pressed_k => action_event_writer.send(Action::AddWidthKeyframe),
+        (false, true, false) if _pressed_k => action_event_writer.send(Action::RemoveWidthKeyframe),
+        (true, false, false) if _pressed_o => action_event_writer.send(Action::MakeStroke),
+        (true, false, false) if _pressed_b => action_event_writer.send(Action::BakeMesh),
+        (false, true, false) if _pressed_d => action_event_writer.send(Action::Duplicate),
+        (true, false, false) if _pressed_c => action_event_writer.send(Action::RecolorMesh),
+        (true, false, false) if _pressed_g => action_event_writer.send(Action::ToggleSnapping),
+        (true, false, false) if _pressed_r => action_event_writer.send(Action::ToggleRuler),
+        (true, false, false) if _pressed_p => action_event_writer.send(Action::CycleShapeTool),
+        (true, false, false) if _pressed_s => action_event_writer.send(Action::CycleSymmetry),
+        (false, true, false) if _pressed_c => action_event_writer.send(Action::Copy),
+        (false, true, false) if _pressed_x => action_event_writer.send(Action::Cut),
+        (false, true, false) if _pressed_v => action_event_writer.send(Action::Paste),
+        (false, true, false) if _pressed_a => action_event_writer.send(Action::SelectAll),
+        (false, true, false) if _pressed_i => action_event_writer.send(Action::InvertSelection),
+        (false, true, false) if _pressed_e => action_event_writer.send(Action::SelectConnected),
+        (false, true, false) if _pressed_b => action_event_writer.send(Action::LoadReferenceImage),
+        (false, true, false) if _pressed_f => {
+            action_event_writer.send(Action::Transform(Transformation::FlipHorizontally))
+        }
+        (true, true, false) if _pressed_f => {
+            action_event_writer.send(Action::Transform(Transformation::FlipVertically))
+        }
+        (false, true, false) if _pressed_r => {
+            action_event_writer.send(Action::Transform(Transformation::Rotate(90.0)))
+        }
+        (true, true, false) if _pressed_r => {
+            action_event_writer.send(Action::Transform(Transformation::Rotate(-90.0)))
+        }
+        (false, false, false) if _nudge != Vec2::ZERO => {
+            action_event_writer.send(Action::Nudge(_nudge))
+        }
+        (true, false, false) if _nudge != Vec2::ZERO => {
+            action_event_writer.send(Action::Nudge(_nudge * 10.0))
+        }
+        (false, true, false) if _nudge.x < 0.0 => {
+            action_event_writer.send(Action::Align(Alignment::Left))
+        }
+        (false, true, false) if _nudge.x > 0.0 => {
+            action_event_writer.send(Action::Align(Alignment::Right))
+        }
+        (false, true, false) if _nudge.y > 0.0 => {
+            action_event_writer.send(Action::Align(Alignment::Top))
+        }
+        (false, true, false) if _nudge.y < 0.0 => {
+            action_event_writer.send(Action::Align(Alignment::Bottom))
+        }
+        (true, true, false) if _nudge.x != 0.0 => {
+            action_event_writer.send(Action::Align(Alignment::HorizontalCenter))
+        }
+        (true, true, false) if _nudge.y != 0.0 => {
+            action_event_writer.send(Action::Align(Alignment::VerticalCenter))
+        }
+        (false, false, true) if _nudge.x != 0.0 => {
+            action_event_writer.send(Action::Align(Alignment::DistributeHorizontally))
+        }
+        (false, false, true) if _nudge.y != 0.0 => {
+            action_event_writer.send(Action::Align(Alignment::DistributeVertically))
+        }
 
         _ => {}
     }
@@ -239,6 +400,8 @@ pub enum MouseClickEvent {
     SpawnOnBezier((AnchorEdge, BezierId, IsLatched)),
     OnMesh(MeshId),
     SpawnOnCanvas,
+    ToggleSelectedCurve(BezierId),
+    ToggleSelectedAnchor((Anchor, BezierId)),
 }
 
 pub fn check_mouseclick_on_objects(
@@ -261,6 +424,7 @@ pub fn check_mouseclick_on_objects(
     bezier_curves: ResMut<Assets<Bezier>>,
     mut mouse_event_writer: EventWriter<MouseClickEvent>,
     mut action_event_writer: EventWriter<Action>,
+    mut ruler: ResMut<Ruler>,
 ) {
     if mouse_button_input.just_pressed(MouseButton::Left) {
         let scale = globals.scale;
@@ -341,6 +505,31 @@ pub fn check_mouseclick_on_objects(
             }
         }
 
+        //
+        // in measurement mode, clicks on the canvas only place the points of the ruler
+        if ruler.active {
+            let mut position = cursor.position;
+            if globals.snapping.is_active(&keyboard_input) {
+                let targets = globals
+                    .snapping
+                    .curve_targets(&bezier_curves, &HashSet::new());
+                position = globals.snapping.snap_point(position, &targets);
+            }
+            ruler.click(position);
+            return ();
+        }
+
+        //
+        // with a shape tool, dragging on the canvas sizes the shape like a selection box
+        if globals.shape_tool.kind.is_some()
+            && !keyboard_input.pressed(KeyCode::LShift)
+            && !keyboard_input.pressed(KeyCode::LControl)
+            && !keyboard_input.pressed(KeyCode::Space)
+        {
+            action_event_writer.send(Action::SelectionBox);
+            return ();
+        }
+
         //
         // check for mouseclick on anchors (including control points)
         let mut anchor_event: Option<MouseClickEvent> = None;
@@ -371,6 +560,49 @@ pub fn check_mouseclick_on_objects(
             )));
         }
 
+        //
+        // Left Alt + click adds an anchor, a control point or a curve to the selection, or
+        // removes it. Left Shift + click is left to spawning curves
+        if keyboard_input.pressed(KeyCode::LAlt) {
+            let clicked_anchor = match (&anchor_edge_event, &anchor_event) {
+                (Some(MouseClickEvent::OnAnchorEdge((anchor_edge, bezier_id, _))), _)
+                    if !globals.do_hide_anchors =>
+                {
+                    Some((anchor_edge.to_anchor(), *bezier_id))
+                }
+                (_, Some(MouseClickEvent::OnAnchor((anchor, bezier_id, _))))
+                    if !globals.do_hide_anchors && !globals.hide_control_points =>
+                {
+                    Some((*anchor, *bezier_id))
+                }
+                _ => None,
+            };
+
+            if let Some(clicked_anchor) = clicked_anchor {
+                mouse_event_writer.send(MouseClickEvent::ToggleSelectedAnchor(clicked_anchor));
+                return ();
+            }
+
+            // the body of a curve, away from its anchors. Left Control + Left Alt drags a
+            // selection box instead
+            if anchor_event.is_none()
+                && anchor_edge_event.is_none()
+                && !keyboard_input.pressed(KeyCode::LControl)
+            {
+                let curve_under_cursor = bezier_curves
+                    .iter()
+                    .map(|(_, bezier)| (bezier.id, bezier.closest_t(cursor.position, 50).1))
+                    .filter(|(_, distance)| *distance < globals.anchor_clicking_dist)
+                    .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                    .map(|(bezier_id, _)| bezier_id);
+
+                if let Some(bezier_id) = curve_under_cursor {
+                    mouse_event_writer.send(MouseClickEvent::ToggleSelectedCurve(bezier_id));
+                    return ();
+                }
+            }
+        }
+
         match (
             anchor_event,
             anchor_edge_event,
@@ -520,8 +752,14 @@ pub fn check_mouse_on_meshes(
     mut fill_mesh_materials: ResMut<Assets<FillMesh2dMaterial>>,
     mut road_mesh_materials: ResMut<Assets<RoadMesh2dMaterial>>,
     globals: Res<Globals>,
+    mut selection: ResMut<Selection>,
+    mut selected_box_query: Query<&mut Visibility, With<SelectedBoxQuad>>,
+    ruler: Res<Ruler>,
     // mut start_moving_mesh_event: EventWriter<StartMovingMesh>,
 ) {
+    if ruler.active {
+        return;
+    }
     //
     // let max_dist = 20.;
     let maybe_mesh_id_translation = get_close_mesh(
@@ -538,6 +776,16 @@ pub fn check_mouse_on_meshes(
             commands.entity(entity).insert(StartMovingMesh {
                 start_position: translation,
             });
+
+            // a clicked mesh becomes the selection, ready to be deleted, duplicated or recolored
+            let pen_mesh = match (fill_query.get(entity), road_query.get(entity)) {
+                (Ok((_, _, _, pen_mesh)), _) | (_, Ok((_, _, _, pen_mesh))) => pen_mesh.clone(),
+                _ => return,
+            };
+            selection.selected = vec![SelectionChoice::Mesh(pen_mesh, translation)];
+            for mut visible in selected_box_query.iter_mut() {
+                visible.is_visible = true;
+            }
         }
     }
 }
@@ -549,6 +797,12 @@ pub fn events_on_spawn_mouseclick(
     mut mouse_event_reader: EventReader<MouseClickEvent>,
     mut spawn_curve_event_writer: EventWriter<SpawningCurve>,
     maps: Res<Maps>,
+    mut cursor: ResMut<Cursor>,
+    keyboard_input: Res<Input<KeyCode>>,
+    globals: Res<Globals>,
+    meshes: Res<Assets<Mesh>>,
+    mesh_query: Query<(&Transform, Option<&Mesh2dHandle>, Option<&Children>), With<PenMesh>>,
+    band_query: Query<&Mesh2dHandle, With<RoadBandMesh>>,
 ) {
     let click_event = mouse_event_reader.iter().next();
 
@@ -571,6 +825,16 @@ pub fn events_on_spawn_mouseclick(
             }
         }
         Some(MouseClickEvent::SpawnOnCanvas) => {
+            // the new curve starts at the click position
+            let snapping = &globals.snapping;
+            if snapping.is_active(&keyboard_input) {
+                let mut snap_targets = snapping.curve_targets(&bezier_curves, &HashSet::new());
+                snap_targets.extend(snapping.mesh_targets(&meshes, &mesh_query, &band_query));
+                cursor.last_click_position =
+                    snapping.snap_point(cursor.last_click_position, &snap_targets);
+                cursor.pos_relative_to_click = cursor.position - cursor.last_click_position;
+            }
+
             spawn_curve_event_writer.send(SpawningCurve {
                 bezier_hist: None,
                 maybe_bezier_id: None,
@@ -583,6 +847,7 @@ pub fn events_on_spawn_mouseclick(
 
 pub fn events_on_canvas_mouseclick(
     mut move_anchor_event_writer: EventWriter<MoveAnchorEvent>,
+    selection: Res<Selection>,
 
     // mut user_state: ResMut<UserState>,
     mut mouse_event_reader: EventReader<MouseClickEvent>,
@@ -601,6 +866,21 @@ pub fn events_on_canvas_mouseclick(
             // passing anchor data to a MoveAnchor event
             move_anchor_event_writer.send(moving_anchor.clone());
 
+            // dragging one of the selected anchors moves all of them
+            let selected_anchors = selection.anchors();
+            if !*unlatch && selected_anchors.contains(&(*handle, *anchor)) {
+                for (bezier_id, selected_anchor) in selected_anchors.iter() {
+                    if (bezier_id, selected_anchor) != (handle, anchor) {
+                        move_anchor_event_writer.send(MoveAnchorEvent {
+                            bezier_id: *bezier_id,
+                            anchor: *selected_anchor,
+                            unlatch: false,
+                            once: false,
+                        });
+                    }
+                }
+            }
+
             // // This state needs to be cleaned up.
             // // This is used in the selection attribute update
             // let user_state = user_state.as_mut();
@@ -623,7 +903,8 @@ pub fn events_on_mouse_release(
     mut latch_event_writer: EventWriter<OfficialLatch>,
     mut add_to_history_event_writer: EventWriter<HistoryAction>,
     selecting_query: Query<Entity, (With<SelectingBoxQuad>, With<CurrentlySelecting>)>,
-    fill_query: Query<Entity, With<StartMovingMesh>>,
+    moving_mesh_query: Query<(Entity, &Transform, &PenMesh, &StartMovingMesh), Without<MainUi>>,
+    globals: Res<Globals>,
 ) {
     if mouse_button_input.just_released(MouseButton::Left) {
         //
@@ -683,11 +964,75 @@ pub fn events_on_mouse_release(
         // the CurrentlySelecting component
         for entity in selecting_query.iter() {
             commands.entity(entity).remove::<CurrentlySelecting>();
-            action_event_writer.send(Action::Selected)
+            if globals.shape_tool.kind.is_some() {
+                action_event_writer.send(Action::SpawnShape)
+            } else {
+                action_event_writer.send(Action::Selected)
+            }
         }
 
-        for entity in fill_query.iter() {
+        for (entity, transform, pen_mesh, start_moving_mesh) in moving_mesh_query.iter() {
+            let new_position = transform.translation.truncate();
+            if new_position != start_moving_mesh.start_position {
+                add_to_history_event_writer.send(HistoryAction::MovedMesh {
+                    mesh_id: pen_mesh.id,
+                    previous_position: start_moving_mesh.start_position,
+                    new_position,
+                });
+            }
             commands.entity(entity).remove::<StartMovingMesh>();
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!(a.distance(b) < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn flips_mirror_around_the_center() {
+        let center = Vec2::new(10.0, 20.0);
+        let position = Vec2::new(13.0, 25.0);
+
+        let flipped = Transformation::FlipHorizontally.apply(center, position);
+        assert_close(flipped, Vec2::new(7.0, 25.0));
+        assert_close(
+            Transformation::FlipHorizontally.apply(center, flipped),
+            position,
+        );
+
+        let flipped = Transformation::FlipVertically.apply(center, position);
+        assert_close(flipped, Vec2::new(13.0, 15.0));
+        assert_close(
+            Transformation::FlipVertically.apply(center, flipped),
+            position,
+        );
+    }
+
+    #[test]
+    fn rotations_turn_counterclockwise_around_the_center() {
+        let center = Vec2::new(10.0, 20.0);
+        let position = Vec2::new(13.0, 20.0);
+
+        assert_close(
+            Transformation::Rotate(90.0).apply(center, position),
+            Vec2::new(10.0, 23.0),
+        );
+        assert_close(
+            Transformation::Rotate(-90.0).apply(center, position),
+            Vec2::new(10.0, 17.0),
+        );
+        assert_close(
+            Transformation::Rotate(180.0).apply(center, position),
+            Vec2::new(7.0, 20.0),
+        );
+        assert_close(
+            Transformation::Rotate(0.0).apply(center, position),
+            position,
+        );
+    }
+}
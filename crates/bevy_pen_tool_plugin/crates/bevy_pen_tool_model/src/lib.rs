@@ -10,6 +10,8 @@ pub use mesh::*;
 pub use model::*;
 pub use spawner::*;
 
+pub use bevy_pen_tool_runtime::*;
+
 use bevy::{prelude::*, sprite::Material2dPlugin};
 
 pub struct SpawnerPlugin;
@@ -28,10 +30,14 @@ impl Plugin for SpawnerPlugin {
             .add_event::<UiButton>()
             .add_event::<Handle<Group>>()
             .add_event::<SpawnMids>()
+            .add_event::<SpawningGroups>()
+            .add_event::<SpawnedSymmetricCopies>()
             .add_event::<HistoryAction>()
             .add_event::<ComputeLut>()
             .add_event::<RedoDelete>()
             .add_event::<ComputeGroupLut>()
+            .add_event::<FollowerEvent>()
+            .add_event::<PathMarkerReached>()
             // .add_plugin(ColoredMesh2dPlugin) // mesh making
             .add_plugin(RoadMesh2dPlugin)
             .add_plugin(FillMesh2dPlugin)
@@ -49,6 +55,11 @@ impl Plugin for SpawnerPlugin {
             .insert_resource(Globals::default())
             .insert_resource(Selection::default())
             .insert_resource(Maps::default())
+            .insert_resource(Ruler::default())
+            .insert_resource(Clipboard::default())
+            .insert_resource(SymmetryLinks::default())
+            .insert_resource(ReferenceImage::default())
+            .init_resource::<MeshRegeneration>()
             .add_startup_system(setup.exclusive_system().at_start())
             .add_startup_system(spawn_selection_bounding_box)
             .add_startup_system(spawn_ui)
@@ -61,10 +72,19 @@ impl Plugin for SpawnerPlugin {
                     .label("spawner")
                     // .with_system(spawn_middle_quads)
                     .with_system(spawn_bezier_system)
+                    .with_system(spawn_groups_system)
                     .with_system(spawn_group_entities)
                     .with_system(spawn_heli)
+                    .with_system(spawn_path_marker_quads)
+                    .with_system(spawn_width_keyframe_quads)
+                    .with_system(spawn_snapping_grid)
+                    .with_system(measure_with_ruler)
+                    .with_system(spawn_symmetric_copies)
+                    .with_system(show_symmetry_axis)
+                    .with_system(show_reference_image)
                     .with_system(make_fill_mesh)
-                    .with_system(make_road),
+                    .with_system(make_road)
+                    .with_system(make_stroke_mesh),
             )
             //
             // Update controller
@@ -172,6 +192,12 @@ fn setup(asset_server: Res<AssetServer>, mut meshes: ResMut<Assets<Mesh>>, mut m
 
     maps.mesh_handles.insert("icon", mesh_handle_icon.clone());
 
+    let path_marker_mesh_handle =
+        bevy::sprite::Mesh2dHandle(meshes.add(Mesh::from(shape::Quad::new(Vec2::new(12.0, 12.0)))));
+
+    maps.mesh_handles
+        .insert("path_marker", path_marker_mesh_handle);
+
     let road_texture_handle: Handle<Image> = asset_server.load("textures/single_lane_road.png");
 
     maps.textures
@@ -0,0 +1,182 @@
+use crate::mesh::MinsMaxes;
+
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+};
+
+use lyon::tessellation::math::{point, Point};
+use lyon::tessellation::path::Path;
+use lyon::tessellation::{BuffersBuilder, FillOptions, FillTessellator, FillVertex, VertexBuffers};
+
+use serde::{Deserialize, Serialize};
+
+/// Decides which parts of overlapping contours are filled. Contained within [`Globals`]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FillRule {
+    /// A point is filled when it is inside an odd number of contours
+    EvenOdd,
+    /// A point is filled when the contours around it do not wind around it as many times
+    /// clockwise as counterclockwise. Contours are oriented so that nested contours are holes
+    NonZero,
+}
+
+impl Default for FillRule {
+    fn default() -> Self {
+        FillRule::EvenOdd
+    }
+}
+
+impl From<FillRule> for lyon::tessellation::FillRule {
+    fn from(fill_rule: FillRule) -> Self {
+        match fill_rule {
+            FillRule::EvenOdd => lyon::tessellation::FillRule::EvenOdd,
+            FillRule::NonZero => lyon::tessellation::FillRule::NonZero,
+        }
+    }
+}
+
+// twice the signed area of a polygon, positive for counterclockwise polygons
+fn signed_area(polygon: &Vec<Vec2>) -> f32 {
+    let mut area = 0.0;
+    for (idx, p0) in polygon.iter().enumerate() {
+        let p1 = polygon[(idx + 1) % polygon.len()];
+        area += p0.perp_dot(p1);
+    }
+    return area;
+}
+
+pub fn polygon_contains(polygon: &Vec<Vec2>, position: Vec2) -> bool {
+    let mut inside = false;
+    for (idx, p0) in polygon.iter().enumerate() {
+        let p1 = polygon[(idx + 1) % polygon.len()];
+        if (p0.y > position.y) != (p1.y > position.y) {
+            let x = p0.x + (position.y - p0.y) / (p1.y - p0.y) * (p1.x - p0.x);
+            if position.x < x {
+                inside = !inside;
+            }
+        }
+    }
+    return inside;
+}
+
+// number of other contours containing each contour
+pub fn nesting_depths(contours: &Vec<Vec<Vec2>>) -> Vec<usize> {
+    contours
+        .iter()
+        .enumerate()
+        .map(|(idx, contour)| match contour.first() {
+            Some(first) => contours
+                .iter()
+                .enumerate()
+                .filter(|(other_idx, other)| *other_idx != idx && polygon_contains(other, *first))
+                .count(),
+            None => 0,
+        })
+        .collect()
+}
+
+// closed path made of cubic Bezier segments, each given as (start, start control, end control, end)
+pub type CubicContour = Vec<[Vec2; 4]>;
+
+// coarse polygon of a contour, used to find its orientation and the contours around it
+fn contour_polygon(contour: &CubicContour) -> Vec<Vec2> {
+    let num_steps = 8;
+    let mut polygon = Vec::new();
+    for [p0, p1, p2, p3] in contour.iter() {
+        for step in 0..num_steps {
+            let t = step as f32 / num_steps as f32;
+            let s = 1.0 - t;
+            polygon.push(
+                *p0 * s * s * s + *p1 * 3.0 * s * s * t + *p2 * 3.0 * s * t * t + *p3 * t * t * t,
+            );
+        }
+    }
+    return polygon;
+}
+
+// tessellates closed contours into a single mesh, flattening the curves within the given
+// tolerance. Contours nested inside an odd number of other contours are holes
+pub fn fill_mesh(
+    contours: &Vec<CubicContour>,
+    fill_rule: FillRule,
+    tolerance: f32,
+    color: Color,
+) -> Option<(Mesh, MinsMaxes)> {
+    let polygons: Vec<Vec<Vec2>> = contours.iter().map(contour_polygon).collect();
+    let depths = nesting_depths(&polygons);
+
+    let mut path_builder = Path::builder();
+    let mut is_empty = true;
+    for ((contour, polygon), depth) in contours.iter().zip(polygons.iter()).zip(depths) {
+        if contour.is_empty() {
+            continue;
+        }
+        // with the non-zero rule, holes must wind the other way around
+        let counterclockwise = depth % 2 == 0;
+        let mut contour = contour.clone();
+        if (signed_area(polygon) > 0.0) != counterclockwise {
+            contour.reverse();
+            for segment in contour.iter_mut() {
+                segment.reverse();
+            }
+        }
+
+        let first = contour[0][0];
+        path_builder.begin(point(first.x, first.y));
+        for [_p0, p1, p2, p3] in contour.iter() {
+            path_builder.cubic_bezier_to(point(p1.x, p1.y), point(p2.x, p2.y), point(p3.x, p3.y));
+        }
+        path_builder.end(true);
+        is_empty = false;
+    }
+
+    if is_empty {
+        return None;
+    }
+    let path = path_builder.build();
+
+    let mut buffers: VertexBuffers<Point, u32> = VertexBuffers::new();
+    {
+        // simple_builder only writes u16 indices
+        let mut vertex_builder =
+            BuffersBuilder::new(&mut buffers, |vertex: FillVertex| vertex.position());
+        let mut tessellator = FillTessellator::new();
+        let options = FillOptions::default()
+            .with_fill_rule(fill_rule.into())
+            .with_tolerance(tolerance.max(0.001));
+        let result = tessellator.tessellate_path(&path, &options, &mut vertex_builder);
+        if result.is_err() {
+            info!("could not tessellate fill: {:?}", result);
+            return None;
+        }
+    }
+
+    let mut mins_maxes = MinsMaxes::default();
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    for position in buffers.vertices.iter() {
+        positions.push([position.x, position.y, 0.0]);
+        mins_maxes.update(Vec2::new(position.x, position.y));
+    }
+
+    // uvs span the bounding box of all the contours
+    let (min, max) = mins_maxes.to_vec2_pair();
+    let size = (max - min).max(Vec2::splat(f32::EPSILON));
+    let uvs: Vec<[f32; 2]> = positions
+        .iter()
+        .map(|p| [(p[0] - min.x) / size.x, (p[1] - min.y) / size.y])
+        .collect();
+
+    let colors = vec![[color.r(), color.g(), color.b(), color.a()]; positions.len()];
+    let normals = vec![[0.0, 0.0, 1.0]; positions.len()];
+    let indices: Vec<u32> = buffers.indices.iter().rev().cloned().collect();
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+
+    return Some((mesh, mins_maxes));
+}
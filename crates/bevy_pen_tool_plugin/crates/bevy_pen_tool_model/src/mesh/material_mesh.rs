@@ -12,6 +12,8 @@ pub struct RoadMesh2dMaterial {
     #[sampler(1)]
     pub road_texture: Handle<Image>,
     #[uniform(2)]
+    pub color: Vec4,
+    #[uniform(2)]
     pub center_of_mass: Vec2,
     #[uniform(2)]
     pub show_com: f32,
@@ -1,18 +1,17 @@
 use crate::inputs::Action;
+use crate::mesh::{
+    build_fill_mesh, build_road_meshes, build_stroke_mesh, MeshSource, MeshSourceOrigin,
+    RoadBandColor, RoadBandMesh, RoadProfile, RoadTint,
+};
 use crate::model::*;
 use crate::{FillMesh2dMaterial, RoadMesh2dMaterial};
 
 use bevy::{
     prelude::*,
-    render::{mesh::Indices, render_resource::PrimitiveTopology},
+    render::texture::DEFAULT_IMAGE_HANDLE,
     sprite::{MaterialMesh2dBundle, Mesh2dHandle},
 };
 
-use lyon::tessellation::geometry_builder::simple_builder;
-use lyon::tessellation::math::{point, Point};
-use lyon::tessellation::path::Path;
-use lyon::tessellation::{FillOptions, FillTessellator, VertexBuffers};
-
 use rand::{thread_rng, Rng};
 
 use std::collections::HashMap;
@@ -75,6 +74,101 @@ pub struct StartMovingMesh {
     pub start_position: Vec2,
 }
 
+// hides a mesh and keeps it in Maps::deleted_meshes, so that its deletion can be undone
+pub fn stash_mesh(commands: &mut Commands, maps: &mut Maps, pen_mesh: &PenMesh) {
+    if let Some(entity) = maps.mesh_map.remove(&pen_mesh.id) {
+        // without a PenMesh, the mesh cannot be selected, moved or regenerated
+        commands
+            .entity(entity)
+            .remove::<PenMesh>()
+            .insert(Visibility { is_visible: false });
+        maps.deleted_meshes
+            .insert(pen_mesh.id, (entity, pen_mesh.clone()));
+    }
+}
+
+// brings back a mesh hidden by stash_mesh
+pub fn restore_mesh(commands: &mut Commands, maps: &mut Maps, mesh_id: MeshId) {
+    if let Some((entity, pen_mesh)) = maps.deleted_meshes.remove(&mesh_id) {
+        commands
+            .entity(entity)
+            .insert(pen_mesh)
+            .insert(Visibility { is_visible: true });
+        maps.mesh_map.insert(mesh_id, entity);
+    }
+}
+
+// despawns a mesh hidden by stash_mesh once no history entry can bring it back
+pub fn free_stashed_mesh(commands: &mut Commands, maps: &mut Maps, mesh_id: MeshId) {
+    if let Some((entity, _pen_mesh)) = maps.deleted_meshes.remove(&mesh_id) {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// the fill shader draws the vertex colors
+pub fn set_mesh_color(mesh: &mut Mesh, color: Color) {
+    let colors = vec![[color.r(), color.g(), color.b(), color.a()]; mesh.count_vertices()];
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+}
+
+// color of a road band, tinted by the color the road was recolored with
+pub fn road_band_color(band_color: Color, tint: Color) -> Vec4 {
+    return Vec4::from(band_color.as_linear_rgba_f32()) * Vec4::from(tint.as_linear_rgba_f32());
+}
+
+// spawns a child mesh per band of a road, each with its own material, drawn on top of the
+// bands before it. Returns the band entities, the material of the first band (used to
+// highlight the road) and the bounding box of the road
+pub fn spawn_road_bands(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    road_materials: &mut Assets<RoadMesh2dMaterial>,
+    maps: &Maps,
+    profile: &RoadProfile,
+    tint: Color,
+    band_meshes: Vec<(Mesh, MinsMaxes)>,
+    center_of_mass: Vec2,
+) -> (Vec<Entity>, Option<Handle<RoadMesh2dMaterial>>, MinsMaxes) {
+    let mut mins_maxes = MinsMaxes::default();
+    let mut band_entities = Vec::new();
+    let mut road_material = None;
+
+    for (idx, (band, (mesh, band_mins_maxes))) in profile.bands.iter().zip(band_meshes).enumerate()
+    {
+        mins_maxes.update(Vec2::new(band_mins_maxes.min_x, band_mins_maxes.min_y));
+        mins_maxes.update(Vec2::new(band_mins_maxes.max_x, band_mins_maxes.max_y));
+
+        let texture_handle = match &band.texture {
+            Some(name) => maps.textures.get(name.as_str()).cloned(),
+            None => None,
+        };
+        let mat_handle = road_materials.add(RoadMesh2dMaterial {
+            // the default image is white
+            road_texture: texture_handle.unwrap_or(DEFAULT_IMAGE_HANDLE.typed()),
+            color: road_band_color(band.color, tint),
+            center_of_mass: center_of_mass,
+            show_com: 0.0,
+        });
+        if road_material.is_none() {
+            road_material = Some(mat_handle.clone());
+        }
+
+        let band_entity = commands
+            .spawn_bundle(MaterialMesh2dBundle {
+                mesh: Mesh2dHandle(meshes.add(mesh)),
+                material: mat_handle,
+                transform: Transform::from_translation(Vec3::new(0.0, 0.0, idx as f32 * 0.0001)),
+                ..default()
+            })
+            .insert(RoadBandMesh(idx))
+            .insert(RoadBandColor(band.color))
+            .id();
+        band_entities.push(band_entity);
+    }
+
+    return (band_entities, road_material, mins_maxes);
+}
+
 // spawn a road along the selected group
 //
 //
@@ -85,10 +179,11 @@ pub fn make_road(
     globals: Res<Globals>,
     selection: ResMut<Selection>,
     mut meshes: ResMut<Assets<Mesh>>,
-    mut groups: ResMut<Assets<Group>>,
+    groups: Res<Assets<Group>>,
 
     mut road_materials: ResMut<Assets<RoadMesh2dMaterial>>,
     mut maps: ResMut<Maps>,
+    mut add_to_history_event_writer: EventWriter<HistoryAction>,
 ) {
     if action_event_reader.iter().any(|x| x == &Action::SpawnRoad) {
         if selection.selected.iter().count() == 1 {
@@ -110,114 +205,22 @@ pub fn make_road(
                     info!("cannot spawn road from curves in different groups");
                     return;
                 }
-                if let Some(group_handle) = maps.group_map.get(group_id_set.iter().next().unwrap())
                 // unwrap never fails
-                {
-                    let group = groups.get_mut(&group_handle).unwrap();
-
-                    let bezier_assets = curves
-                        .iter()
-                        .collect::<HashMap<bevy::asset::HandleId, &Bezier>>();
-
-                    group.find_connected_ends(&bezier_assets, maps.bezier_map.clone());
-
-                    group.group_lut(&bezier_assets, maps.bezier_map.clone());
-                    group.compute_standalone_lut(&bezier_assets, globals.group_lut_num_points);
-
-                    let center_of_mass = group.center_of_mass(&bezier_assets);
-
-                    let num_points = globals.group_lut_num_points;
-
-                    let crop = 0.000001;
-                    let t_range: Vec<f32> = (0..num_points)
-                        .map(|x| {
-                            (x as f32) / (num_points as f32 - 0.99999) / (1.0 + 2.0 * crop) + crop
-                        })
-                        .collect();
-
-                    let mut mesh_contour: Vec<Vec3> = Vec::new();
-
-                    for t in t_range {
-                        let position = group.compute_position_with_lut(t) - center_of_mass;
-                        let normal = group
-                            .compute_normal_with_bezier(&bezier_assets, t as f64)
-                            .normalize();
-
-                        let v1 = Vec3::new(
-                            (position.x + normal.x * globals.road_width) as f32,
-                            (position.y + normal.y * globals.road_width) as f32,
-                            globals.z_pos.road,
-                        );
-
-                        let v2 = Vec3::new(
-                            (position.x - normal.x * globals.road_width) as f32,
-                            (position.y - normal.y * globals.road_width) as f32,
-                            globals.z_pos.road,
-                        );
-
-                        mesh_contour.push(v1);
-                        mesh_contour.push(v2);
-                    }
-
-                    mesh_contour.push(mesh_contour[0]);
-                    mesh_contour.push(mesh_contour[1]);
-
-                    // indices
-                    let mut new_indices: Vec<u32> = Vec::new();
-                    // for kk in 0..(num_points - 1) {
-                    for kk in 0..(num_points) {
-                        let k = kk * 2;
-                        let mut local_inds = vec![k, (k + 1), (k + 2), (k + 1), (k + 3), (k + 2)];
-                        new_indices.append(&mut local_inds);
-                    }
-
-                    // uvs
-                    let path_length = group.standalone_lut.path_length;
-                    let num_repeats = path_length / 100.0;
-                    let mut mesh_attr_uvs: Vec<[f32; 2]> = Vec::new();
-                    for k in 0..(num_points + 1) * 2 {
-                        // let (pos_x, pos_y) = (pos[0], pos[1]);
-                        let v = k as f32 / (num_points as f32 / num_repeats);
-                        mesh_attr_uvs.push([v % 1.0, (k as f32) % 2.0]);
-                    }
-
-                    let mut mesh_pos_attributes: Vec<[f32; 3]> = Vec::new();
-
-                    // show points from look-up table
-                    let color = globals.picked_color.unwrap();
-                    let mut colors = Vec::new();
-                    let mut normals = Vec::new();
-
-                    let mut mins_maxes = MinsMaxes::default();
-
-                    for position in mesh_contour {
-                        mesh_pos_attributes.push([position.x, position.y, 0.0]);
-
-                        colors.push([color.r(), color.g(), color.b(), 1.0]);
-                        normals.push([0.0, 0.0, 1.0]);
-
-                        mins_maxes.update(Vec2::new(position.x, position.y));
-                    }
-
-                    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
-
-                    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, mesh_pos_attributes.clone());
-
-                    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
-                    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
-
-                    mesh.set_indices(Some(Indices::U32(new_indices)));
-
-                    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, mesh_attr_uvs);
-
-                    let texture_handle = maps.textures.get("single_lane_road").unwrap();
-
-                    let mat_handle = road_materials.add(RoadMesh2dMaterial {
-                        road_texture: texture_handle.clone(),
-                        center_of_mass: center_of_mass,
-                        show_com: 0.0,
-                    });
-
+                let group_id = *group_id_set.iter().next().unwrap();
+
+                let bezier_assets = curves
+                    .iter()
+                    .collect::<HashMap<bevy::asset::HandleId, &Bezier>>();
+
+                let profile = &globals.road_profile;
+                if let Some((center_of_mass, band_meshes)) = build_road_meshes(
+                    &group_id,
+                    &groups,
+                    &bezier_assets,
+                    &maps,
+                    profile,
+                    globals.group_lut_num_points,
+                ) {
                     let mut road_transform = Transform::from_translation(Vec3::new(
                         center_of_mass.x,
                         center_of_mass.y,
@@ -228,20 +231,45 @@ pub fn make_road(
                     let mut rng = thread_rng();
                     let id = rng.gen::<u64>();
 
-                    let entity = commands
-                        .spawn_bundle(MaterialMesh2dBundle {
-                            mesh: Mesh2dHandle(meshes.add(mesh)),
-                            material: mat_handle,
-                            transform: road_transform,
-                            ..default()
-                        })
-                        .insert(PenMesh {
-                            id,
-                            bounding_box: mins_maxes.to_vec2_pair(),
-                        })
-                        .id();
-
-                    maps.mesh_map.insert(id, entity);
+                    let (band_entities, road_material, mins_maxes) = spawn_road_bands(
+                        &mut commands,
+                        &mut meshes,
+                        &mut road_materials,
+                        &maps,
+                        profile,
+                        Color::WHITE,
+                        band_meshes,
+                        center_of_mass,
+                    );
+
+                    if let Some(road_material) = road_material {
+                        let entity = commands
+                            .spawn_bundle(SpatialBundle {
+                                transform: road_transform,
+                                ..default()
+                            })
+                            // the material of the first band is used to highlight the road
+                            .insert(road_material)
+                            .insert(PenMesh {
+                                id,
+                                bounding_box: mins_maxes.to_vec2_pair(),
+                            })
+                            .insert(MeshSource::Road {
+                                group: group_id,
+                                profile: profile.clone(),
+                                num_points: globals.group_lut_num_points,
+                            })
+                            .insert(MeshSourceOrigin(center_of_mass))
+                            .insert(RoadTint(Color::WHITE))
+                            .push_children(&band_entities)
+                            .id();
+
+                        maps.mesh_map.insert(id, entity);
+                        add_to_history_event_writer
+                            .send(HistoryAction::SpawnedMesh { mesh_id: id });
+                    } else {
+                        info!("cannot spawn a road with an empty road profile");
+                    }
                 }
             }
         } else {
@@ -250,8 +278,8 @@ pub fn make_road(
     }
 }
 
-// generate a fill mesh inside of the group
-//
+// generate a fill mesh inside of the selected groups. Each group is a closed contour,
+// and contours inside other contours are holes
 //
 //
 pub fn make_fill_mesh(
@@ -262,8 +290,9 @@ pub fn make_fill_mesh(
     mut fill_materials: ResMut<Assets<FillMesh2dMaterial>>,
     selection: ResMut<Selection>,
     mut meshes: ResMut<Assets<Mesh>>,
-    mut groups: ResMut<Assets<Group>>,
+    groups: Res<Assets<Group>>,
     mut maps: ResMut<Maps>,
+    mut add_to_history_event_writer: EventWriter<HistoryAction>,
 ) {
     if action_event_reader.iter().any(|x| x == &Action::MakeMesh) {
         if selection.selected.iter().count() == 1 {
@@ -271,7 +300,7 @@ pub fn make_fill_mesh(
             let selected = temp.iter().next().unwrap().clone();
             if let SelectionChoice::CurveSet(curve_set) = selected {
                 //
-                // check whether the curve set is part of the same group
+                // find the groups of the selected curves
                 let mut group_id_set = HashSet::new();
 
                 for curve in &curve_set {
@@ -281,129 +310,22 @@ pub fn make_fill_mesh(
                         group_id_set.insert(bezier.group);
                     }
                 }
-                if group_id_set.iter().count() != 1 {
-                    info!("cannot spawn road from curves in different groups");
-                    return;
-                }
-                if let Some(group_handle) = maps.group_map.get(group_id_set.iter().next().unwrap())
-                // unwrap never fails
-                {
-                    let group = groups.get_mut(&group_handle).unwrap();
-
-                    let bezier_assets = curves
-                        .iter()
-                        .collect::<HashMap<bevy::asset::HandleId, &Bezier>>();
-
-                    group.find_connected_ends(&bezier_assets, maps.bezier_map.clone());
-
-                    group.group_lut(&bezier_assets, maps.bezier_map.clone());
-                    group.compute_standalone_lut(&bezier_assets, globals.group_lut_num_points);
-
-                    let center_of_mass = group.center_of_mass(&bezier_assets);
-
-                    let mut path_builder = Path::builder();
-
-                    let lut = group.standalone_lut.lut.clone();
-
-                    let first = lut[0];
-                    path_builder.begin(point(first.x, first.y));
-
-                    let resto: Vec<Vec2> = lut[1..].to_vec();
-
-                    for e in resto.iter() {
-                        path_builder.line_to(point(e.x, e.y));
-                    }
-
-                    path_builder.end(true);
-                    let path = path_builder.build();
-
-                    // Create the destination vertex and index buffers.
-                    let mut buffers: VertexBuffers<Point, u16> = VertexBuffers::new();
-
-                    {
-                        let mut vertex_builder = simple_builder(&mut buffers);
-
-                        // Create the tessellator.
-                        let mut tessellator = FillTessellator::new();
-
-                        // Compute the tessellation.
-                        let result = tessellator.tessellate_path(
-                            &path,
-                            &FillOptions::default(),
-                            &mut vertex_builder,
-                        );
-                        assert!(result.is_ok());
-                    }
-
-                    let mut mesh_pos_attributes: Vec<[f32; 3]> = Vec::new();
-                    let mut mesh_attr_uvs: Vec<[f32; 2]> = Vec::new();
-                    let mut new_indices: Vec<u32> = Vec::new();
-
-                    // show points from look-up table
-                    let color = globals.picked_color.unwrap();
-                    let mut colors = Vec::new();
-
-                    let mut mins_maxes = MinsMaxes::default();
-
-                    for position in buffers.vertices[..].iter() {
-                        let pos_x = position.x - center_of_mass.x;
-                        let pos_y = position.y - center_of_mass.y;
-                        mesh_pos_attributes.push([pos_x, pos_y, 0.0]);
-
-                        colors.push([color.r(), color.g(), color.b(), 1.0]);
-
-                        mins_maxes.update(Vec2::new(pos_x, pos_y));
-                    }
-
-                    //////////////////////////// uvs ///////////////////////////////
-                    let xs: Vec<f32> = mesh_pos_attributes.iter().map(|v| v[0]).collect();
-                    let ys: Vec<f32> = mesh_pos_attributes.iter().map(|v| v[1]).collect();
-
-                    use std::cmp::Ordering;
-
-                    fn bounds(v: &Vec<f32>) -> (f32, f32) {
-                        let max_v: &f32 = v
-                            .iter()
-                            .max_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal))
-                            .unwrap();
-
-                        let min_v: &f32 = v
-                            .iter()
-                            .min_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal))
-                            .unwrap();
-
-                        return (*min_v, *max_v);
-                    }
-
-                    let bounds_x = bounds(&xs);
-                    let size_x = bounds_x.1 - bounds_x.0;
-                    let bounds_y = bounds(&ys);
-                    let size_y = bounds_y.1 - bounds_y.0;
-
-                    let mut normals = Vec::new();
-                    for pos in &mesh_pos_attributes {
-                        let (pos_x, pos_y) = (pos[0], pos[1]);
-
-                        mesh_attr_uvs.push([
-                            1.0 * (pos_x - bounds_x.0) / size_x,
-                            1.0 * (pos_y - bounds_y.0) / size_y,
-                        ]);
-
-                        normals.push([0.0, 0.0, 1.0]);
-                    }
-
-                    for ind in buffers.indices[..].iter().rev() {
-                        new_indices.push(ind.clone() as u32);
-                    }
-
-                    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
-
-                    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, mesh_pos_attributes.clone());
-                    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
-                    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
-                    mesh.set_indices(Some(Indices::U32(new_indices)));
-                    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, mesh_attr_uvs);
-
+                let group_ids = group_id_set.into_iter().collect::<Vec<GroupId>>();
+
+                let bezier_assets = curves
+                    .iter()
+                    .collect::<HashMap<bevy::asset::HandleId, &Bezier>>();
+
+                let color = globals.picked_color.unwrap();
+                if let Some((center_of_mass, mesh, mins_maxes)) = build_fill_mesh(
+                    &group_ids,
+                    &groups,
+                    &bezier_assets,
+                    &maps,
+                    globals.fill_rule,
+                    globals.fill_tolerance,
+                    color,
+                ) {
                     let mut fill_transform =
                         Transform::from_translation(center_of_mass.extend(globals.z_pos.fill));
 
@@ -430,13 +352,100 @@ pub fn make_fill_mesh(
                             id,
                             bounding_box: mins_maxes.to_vec2_pair(), // bounding box relative to center of mass
                         })
+                        .insert(MeshSource::Fill {
+                            groups: group_ids,
+                            fill_rule: globals.fill_rule,
+                            tolerance: globals.fill_tolerance,
+                            color,
+                        })
+                        .insert(MeshSourceOrigin(center_of_mass))
                         .id();
 
                     maps.mesh_map.insert(id, entity);
+                    add_to_history_event_writer.send(HistoryAction::SpawnedMesh { mesh_id: id });
+                } else {
+                    info!("Select one or more closed groups to spawn a fill mesh");
                 }
             } else {
-                info!("Select a single bezier chain to spawn a fill mesh");
+                info!("Select one or more closed groups to spawn a fill mesh");
+            }
+        }
+    }
+}
+
+// generate a stroke mesh along the selected group, or along each selected curve
+// if the curves do not form a single connected group
+//
+//
+pub fn make_stroke_mesh(
+    mut action_event_reader: EventReader<Action>,
+    mut commands: Commands,
+    globals: Res<Globals>,
+    curves: Res<Assets<Bezier>>,
+    mut fill_materials: ResMut<Assets<FillMesh2dMaterial>>,
+    selection: ResMut<Selection>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    groups: Res<Assets<Group>>,
+    mut maps: ResMut<Maps>,
+    mut add_to_history_event_writer: EventWriter<HistoryAction>,
+) {
+    if action_event_reader.iter().any(|x| x == &Action::MakeStroke) {
+        if let Some(SelectionChoice::CurveSet(curve_set)) = selection.selected.iter().next() {
+            let bezier_assets = curves
+                .iter()
+                .collect::<HashMap<bevy::asset::HandleId, &Bezier>>();
+
+            let curve_ids = curve_set.iter().cloned().collect::<Vec<BezierId>>();
+
+            let color = globals.picked_color.unwrap_or(Color::WHITE);
+            if let Some((center_of_mass, mesh, mins_maxes)) = build_stroke_mesh(
+                &curve_ids,
+                &groups,
+                &bezier_assets,
+                &maps,
+                &globals.stroke_style,
+                globals.group_lut_num_points,
+                color,
+            ) {
+                let mut stroke_transform =
+                    Transform::from_translation(center_of_mass.extend(globals.z_pos.stroke));
+                stroke_transform.scale = Vec3::new(globals.scale, globals.scale, 1.0);
+
+                let mat_handle = fill_materials.add(FillMesh2dMaterial {
+                    color: color.into(),
+                    center_of_mass: center_of_mass,
+                    show_com: 0.0,
+                });
+
+                let mut rng = thread_rng();
+                let id = rng.gen::<u64>();
+                let entity = commands
+                    .spawn_bundle(MaterialMesh2dBundle {
+                        mesh: Mesh2dHandle(meshes.add(mesh)),
+                        material: mat_handle,
+                        transform: stroke_transform,
+                        ..default()
+                    })
+                    .insert(PenMesh {
+                        id,
+                        bounding_box: mins_maxes.to_vec2_pair(),
+                    })
+                    .insert(MeshSource::Stroke {
+                        curves: curve_ids,
+                        style: globals.stroke_style.clone(),
+                        num_points: globals.group_lut_num_points,
+                        color,
+                    })
+                    .insert(MeshSourceOrigin(center_of_mass))
+                    .id();
+
+                maps.mesh_map.insert(id, entity);
+                add_to_history_event_writer.send(HistoryAction::SpawnedMesh { mesh_id: id });
+            } else {
+                info!("Select a group or curves to make a stroke mesh");
             }
+        } else {
+            info!("Select a group or curves to make a stroke mesh");
         }
     }
 }
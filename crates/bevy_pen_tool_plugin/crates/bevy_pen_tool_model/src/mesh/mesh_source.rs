@@ -0,0 +1,507 @@
+use crate::inputs::Action;
+use crate::mesh::{
+    fill_mesh, road_band_mesh, road_samples, spawn_road_bands, stroke_mesh, taper_road_samples,
+    CubicContour, FillRule, MinsMaxes, PenMesh, RoadBandMesh, RoadEndCap, RoadProfile, RoadTint,
+    StrokeStyle,
+};
+use crate::model::*;
+use crate::{FillMesh2dMaterial, RoadMesh2dMaterial};
+
+use bevy::{prelude::*, sprite::Mesh2dHandle};
+
+use flo_curves::bezier::BezierCurve;
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+/// Curves and parameters a mesh was generated from. Meshes holding a MeshSource are rebuilt
+/// when their curves are edited. Removing it (the bake action) freezes the mesh
+#[derive(Component, Clone, Debug)]
+pub enum MeshSource {
+    Road {
+        group: GroupId,
+        profile: RoadProfile,
+        num_points: u32,
+    },
+    Fill {
+        groups: Vec<GroupId>,
+        fill_rule: FillRule,
+        tolerance: f32,
+        color: Color,
+    },
+    Stroke {
+        curves: Vec<BezierId>,
+        style: StrokeStyle,
+        num_points: u32,
+        color: Color,
+    },
+}
+
+impl MeshSource {
+    // whether editing the given curve, member of the given group, changes the mesh
+    fn depends_on(&self, curve: &BezierId, group: &GroupId, bezier_assets: &BezierAssets) -> bool {
+        match self {
+            MeshSource::Road { group: g, .. } => g == group,
+            MeshSource::Fill { groups, .. } => groups.contains(group),
+            MeshSource::Stroke { curves, .. } => {
+                // strokes along a group change with any curve of the group
+                curves.contains(curve)
+                    || curves.iter().any(|id| {
+                        bezier_assets
+                            .get(&id.0)
+                            .map(|bezier| &bezier.group == group)
+                            .unwrap_or(false)
+                    })
+            }
+        }
+    }
+}
+
+/// Meshes waiting to be rebuilt, with the time at which their curves were last modified.
+/// A mesh is rebuilt once its curves have not moved for `delay` seconds,
+/// so that dragging an anchor does not rebuild it every frame
+pub struct MeshRegeneration {
+    pub delay: f64,
+    pub pending: HashMap<Entity, f64>,
+}
+
+impl Default for MeshRegeneration {
+    fn default() -> Self {
+        MeshRegeneration {
+            delay: 0.2,
+            pending: HashMap::new(),
+        }
+    }
+}
+
+/// Center of mass of the source curves of a mesh when it was last built. The distance between
+/// the translation of the mesh and its origin is kept when the mesh is rebuilt, so that meshes
+/// moved by the user stay where they were put
+#[derive(Component, Clone, Copy, Debug)]
+pub struct MeshSourceOrigin(pub Vec2);
+
+// copy of a group with its curves in traversal order. Meshes are built from copies, so that
+// building them does not change the look-up table of the group, which is used for animation
+fn ordered_group(
+    group_id: &GroupId,
+    groups: &Assets<Group>,
+    bezier_assets: &BezierAssets,
+    maps: &Maps,
+) -> Option<Group> {
+    let group_handle = maps.group_map.get(group_id)?;
+    let mut group = groups.get(group_handle)?.clone();
+    group.find_connected_ends(bezier_assets, maps.bezier_map.clone());
+    group.group_lut(bezier_assets, maps.bezier_map.clone());
+    return Some(group);
+}
+
+/// Builds the band meshes of a road along a group, relative to the returned center of mass
+pub fn build_road_meshes(
+    group_id: &GroupId,
+    groups: &Assets<Group>,
+    bezier_assets: &BezierAssets,
+    maps: &Maps,
+    profile: &RoadProfile,
+    num_points: u32,
+) -> Option<(Vec2, Vec<(Mesh, MinsMaxes)>)> {
+    let mut group = ordered_group(group_id, groups, bezier_assets, maps)?;
+    group.compute_standalone_lut(bezier_assets, num_points);
+
+    let center_of_mass = group.center_of_mass(bezier_assets);
+
+    let mut samples = road_samples(&group, bezier_assets, profile, num_points, center_of_mass);
+
+    // only loops are closed, open chains get end caps
+    let closed = group.is_closed();
+    if let (false, RoadEndCap::Tapered(taper_length)) = (closed, profile.end_cap) {
+        taper_road_samples(&mut samples, taper_length);
+    }
+
+    let band_meshes = profile
+        .bands
+        .iter()
+        .zip(profile.band_offsets())
+        .map(|(band, offsets)| road_band_mesh(&samples, offsets, band, closed, profile.end_cap))
+        .collect();
+
+    return Some((center_of_mass, band_meshes));
+}
+
+/// Builds a fill mesh inside groups, relative to the returned center of mass.
+/// Each group is a closed contour, and contours inside other contours are holes
+pub fn build_fill_mesh(
+    group_ids: &Vec<GroupId>,
+    groups: &Assets<Group>,
+    bezier_assets: &BezierAssets,
+    maps: &Maps,
+    fill_rule: FillRule,
+    tolerance: f32,
+    color: Color,
+) -> Option<(Vec2, Mesh, MinsMaxes)> {
+    let mut contours: Vec<CubicContour> = Vec::new();
+    for group_id in group_ids.iter() {
+        if let Some(group) = ordered_group(group_id, groups, bezier_assets, maps) {
+            // only loops can be filled
+            if !group.is_closed() {
+                info!(
+                    "skipping open group {:?}: only closed groups can be filled",
+                    group.id
+                );
+                continue;
+            }
+
+            // the mesh is made from the curves themselves, so its accuracy
+            // does not depend on the resolution of the look-up table
+            let contour = group.cubic_segments(bezier_assets);
+            if !contour.is_empty() {
+                contours.push(contour);
+            }
+        }
+    }
+
+    if contours.is_empty() {
+        return None;
+    }
+
+    // average of the anchors
+    let num_anchors: usize = contours.iter().map(|c| c.len()).sum();
+    let center_of_mass = contours
+        .iter()
+        .flat_map(|c| c.iter())
+        .fold(Vec2::ZERO, |acc, segment| acc + segment[0])
+        / num_anchors as f32;
+    for contour in contours.iter_mut() {
+        for segment in contour.iter_mut() {
+            for p in segment.iter_mut() {
+                *p -= center_of_mass;
+            }
+        }
+    }
+
+    let (mesh, mins_maxes) = fill_mesh(&contours, fill_rule, tolerance, color)?;
+    return Some((center_of_mass, mesh, mins_maxes));
+}
+
+/// Builds a stroke mesh along the group of the curves, or along each curve if the curves
+/// do not form a single connected group. The mesh is relative to the returned center of mass
+pub fn build_stroke_mesh(
+    curve_ids: &Vec<BezierId>,
+    groups: &Assets<Group>,
+    bezier_assets: &BezierAssets,
+    maps: &Maps,
+    style: &StrokeStyle,
+    num_points: u32,
+    color: Color,
+) -> Option<(Vec2, Mesh, MinsMaxes)> {
+    let group_id_set = curve_ids
+        .iter()
+        .filter_map(|id| bezier_assets.get(&id.0).map(|bezier| bezier.group))
+        .collect::<HashSet<GroupId>>();
+
+    // (polyline, is closed)
+    let mut polylines: Vec<(Vec<Vec2>, bool)> = Vec::new();
+
+    if group_id_set.len() == 1 {
+        let group_id = group_id_set.iter().next().unwrap();
+        if let Some(mut group) = ordered_group(group_id, groups, bezier_assets, maps) {
+            group.compute_standalone_lut(bezier_assets, num_points);
+
+            if group.ends.is_some() && group.standalone_lut.lut.len() > 1 {
+                let mut lut = group.standalone_lut.lut.clone();
+                let closed = group.is_closed();
+                if closed {
+                    // the last point of a loop is its first point
+                    lut.pop();
+                }
+                polylines.push((lut, closed));
+            }
+        }
+    }
+
+    // loose curves
+    if polylines.is_empty() {
+        let num_points = num_points.max(2);
+        for curve_id in curve_ids.iter() {
+            if let Some(bezier) = bezier_assets.get(&curve_id.0) {
+                let flo_curve = bezier.to_curve();
+                let polyline = (0..num_points)
+                    .map(|k| {
+                        let t = k as f64 / (num_points - 1) as f64;
+                        let p = flo_curve.point_at_pos(t);
+                        Vec2::new(p.0 as f32, p.1 as f32)
+                    })
+                    .collect::<Vec<Vec2>>();
+                polylines.push((polyline, false));
+            }
+        }
+    }
+
+    let num_polyline_points: usize = polylines.iter().map(|(p, _)| p.len()).sum();
+    if num_polyline_points == 0 {
+        return None;
+    }
+    let center_of_mass = polylines
+        .iter()
+        .flat_map(|(p, _)| p.iter())
+        .fold(Vec2::ZERO, |acc, p| acc + *p)
+        / num_polyline_points as f32;
+    for (polyline, _) in polylines.iter_mut() {
+        for p in polyline.iter_mut() {
+            *p -= center_of_mass;
+        }
+    }
+
+    let (mesh, mins_maxes) = stroke_mesh(&polylines, style, color)?;
+    return Some((center_of_mass, mesh, mins_maxes));
+}
+
+// rebuilds the meshes linked to curves that were edited, once the curves stop moving.
+// Roads are also rebuilt when the width keyframes of their group or the road profile change
+pub fn regenerate_linked_meshes(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut regeneration: ResMut<MeshRegeneration>,
+    mut bezier_asset_event: EventReader<AssetEvent<Bezier>>,
+    curves: Res<Assets<Bezier>>,
+    groups: Res<Assets<Group>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut fill_materials: ResMut<Assets<FillMesh2dMaterial>>,
+    mut road_materials: ResMut<Assets<RoadMesh2dMaterial>>,
+    maps: Res<Maps>,
+    globals: Res<Globals>,
+    mut road_inputs: Local<(Option<RoadProfile>, HashMap<Entity, Vec<WidthKeyframe>>)>,
+    mut mesh_query: Query<(
+        Entity,
+        &mut Transform,
+        &mut PenMesh,
+        &mut MeshSource,
+        Option<&MeshSourceOrigin>,
+        Option<&Mesh2dHandle>,
+        Option<&Handle<FillMesh2dMaterial>>,
+        Option<&Children>,
+    )>,
+    band_query: Query<&RoadBandMesh>,
+    tint_query: Query<&RoadTint>,
+) {
+    let now = time.seconds_since_startup();
+
+    let bezier_assets = curves
+        .iter()
+        .collect::<HashMap<bevy::asset::HandleId, &Bezier>>();
+
+    for ev in bezier_asset_event.iter() {
+        if let AssetEvent::Modified { handle } = ev {
+            if let Some(bezier) = curves.get(handle) {
+                let curve_id: BezierId = handle.id.into();
+                for (entity, _, _, source, _, _, _, _) in mesh_query.iter() {
+                    if source.depends_on(&curve_id, &bezier.group, &bezier_assets) {
+                        regeneration.pending.insert(entity, now);
+                    }
+                }
+            }
+        }
+    }
+
+    // linked roads follow the road profile of Globals
+    let (seen_profile, seen_width_keyframes) = &mut *road_inputs;
+    let profile_changed = seen_profile.as_ref() != Some(&globals.road_profile);
+    if profile_changed && seen_profile.is_some() {
+        for (entity, _, _, mut source, _, _, _, _) in mesh_query.iter_mut() {
+            if let MeshSource::Road { profile, .. } = &mut *source {
+                *profile = globals.road_profile.clone();
+                regeneration.pending.insert(entity, now);
+            }
+        }
+    }
+    if profile_changed {
+        *seen_profile = Some(globals.road_profile.clone());
+    }
+
+    for (entity, _, _, source, _, _, _, _) in mesh_query.iter() {
+        if let MeshSource::Road { group, .. } = &*source {
+            let width_keyframes = maps
+                .group_map
+                .get(group)
+                .and_then(|group_handle| groups.get(group_handle))
+                .map(|group| group.width_keyframes.clone())
+                .unwrap_or_default();
+            match seen_width_keyframes.get(&entity) {
+                Some(seen) if seen == &width_keyframes => {}
+                Some(_) => {
+                    regeneration.pending.insert(entity, now);
+                    seen_width_keyframes.insert(entity, width_keyframes);
+                }
+                // the road was just built with these keyframes
+                None => {
+                    seen_width_keyframes.insert(entity, width_keyframes);
+                }
+            }
+        }
+    }
+    seen_width_keyframes.retain(|entity, _| mesh_query.get(*entity).is_ok());
+
+    let delay = regeneration.delay;
+    let ready = regeneration
+        .pending
+        .iter()
+        .filter(|(_, last_modified)| now - **last_modified > delay)
+        .map(|(entity, _)| *entity)
+        .collect::<Vec<Entity>>();
+
+    for entity in ready {
+        regeneration.pending.remove(&entity);
+
+        // the mesh may have been deleted or baked in the meantime
+        if let Ok((
+            _,
+            mut transform,
+            mut pen_mesh,
+            source,
+            origin,
+            mesh_handle,
+            fill_handle,
+            children,
+        )) = mesh_query.get_mut(entity)
+        {
+            let (center_of_mass, new_meshes) = match &*source {
+                MeshSource::Road {
+                    group,
+                    profile,
+                    num_points,
+                } => match build_road_meshes(
+                    group,
+                    &groups,
+                    &bezier_assets,
+                    &maps,
+                    profile,
+                    *num_points,
+                ) {
+                    Some(road) => road,
+                    None => continue,
+                },
+                MeshSource::Fill {
+                    groups: group_ids,
+                    fill_rule,
+                    tolerance,
+                    color,
+                } => match build_fill_mesh(
+                    group_ids,
+                    &groups,
+                    &bezier_assets,
+                    &maps,
+                    *fill_rule,
+                    *tolerance,
+                    *color,
+                ) {
+                    Some((center_of_mass, mesh, mins_maxes)) => {
+                        (center_of_mass, vec![(mesh, mins_maxes)])
+                    }
+                    None => continue,
+                },
+                MeshSource::Stroke {
+                    curves: curve_ids,
+                    style,
+                    num_points,
+                    color,
+                } => match build_stroke_mesh(
+                    curve_ids,
+                    &groups,
+                    &bezier_assets,
+                    &maps,
+                    style,
+                    *num_points,
+                    *color,
+                ) {
+                    Some((center_of_mass, mesh, mins_maxes)) => {
+                        (center_of_mass, vec![(mesh, mins_maxes)])
+                    }
+                    None => continue,
+                },
+            };
+
+            // the mesh keeps its offset from its curves, in case the user moved it
+            let offset = match origin {
+                Some(MeshSourceOrigin(origin)) => transform.translation.truncate() - *origin,
+                None => Vec2::ZERO,
+            };
+            let position = center_of_mass + offset;
+            commands
+                .entity(entity)
+                .insert(MeshSourceOrigin(center_of_mass));
+
+            let mins_maxes = if let MeshSource::Road { profile, .. } = &*source {
+                // the bands are respawned, since the profile may have a different number of bands
+                if let Some(children) = children {
+                    for child in children.iter() {
+                        if band_query.get(*child).is_ok() {
+                            commands.entity(*child).despawn_recursive();
+                        }
+                    }
+                }
+                let tint = match tint_query.get(entity) {
+                    Ok(RoadTint(tint)) => *tint,
+                    Err(_) => Color::WHITE,
+                };
+                let (band_entities, road_material, mins_maxes) = spawn_road_bands(
+                    &mut commands,
+                    &mut meshes,
+                    &mut road_materials,
+                    &maps,
+                    profile,
+                    tint,
+                    new_meshes,
+                    position,
+                );
+                let mut road_commands = commands.entity(entity);
+                road_commands.push_children(&band_entities);
+                if let Some(road_material) = road_material {
+                    road_commands.insert(road_material);
+                }
+                mins_maxes
+            } else {
+                let mut mins_maxes = MinsMaxes::default();
+                for (new_mesh, new_mins_maxes) in new_meshes {
+                    mins_maxes.update(Vec2::new(new_mins_maxes.min_x, new_mins_maxes.min_y));
+                    mins_maxes.update(Vec2::new(new_mins_maxes.max_x, new_mins_maxes.max_y));
+                    if let Some(mesh) =
+                        mesh_handle.and_then(|Mesh2dHandle(handle)| meshes.get_mut(handle))
+                    {
+                        *mesh = new_mesh;
+                    }
+                }
+                if let Some(material) =
+                    fill_handle.and_then(|handle| fill_materials.get_mut(handle))
+                {
+                    material.center_of_mass = position;
+                }
+                mins_maxes
+            };
+
+            transform.translation.x = position.x;
+            transform.translation.y = position.y;
+            pen_mesh.bounding_box = mins_maxes.to_vec2_pair();
+        }
+    }
+}
+
+// freezes the selected mesh: it is not rebuilt anymore when its curves are edited
+pub fn bake_mesh(
+    mut commands: Commands,
+    mut action_event_reader: EventReader<Action>,
+    selection: Res<Selection>,
+    maps: Res<Maps>,
+    source_query: Query<&MeshSource>,
+) {
+    if action_event_reader.iter().any(|x| x == &Action::BakeMesh) {
+        for selected in selection.selected.iter() {
+            if let SelectionChoice::Mesh(PenMesh { id, .. }, _) = selected {
+                if let Some(entity) = maps.mesh_map.get(id) {
+                    if source_query.get(*entity).is_ok() {
+                        commands.entity(*entity).remove::<MeshSource>();
+                        info!("baked mesh {}", id);
+                    }
+                }
+            }
+        }
+    }
+}
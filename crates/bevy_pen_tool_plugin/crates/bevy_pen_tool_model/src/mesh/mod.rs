@@ -1,5 +1,13 @@
+mod fill;
 mod material_mesh;
 mod mesh_making;
+mod mesh_source;
+mod road_profile;
+mod stroke;
 
+pub use fill::*;
 pub use material_mesh::*;
 pub use mesh_making::*;
+pub use mesh_source::*;
+pub use road_profile::*;
+pub use stroke::*;
@@ -0,0 +1,407 @@
+use crate::mesh::MinsMaxes;
+use crate::model::*;
+
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Strip of a road running along the whole path: a lane, a shoulder, a sidewalk, a curb
+/// or a painted marking. Each band becomes a separate mesh with its own material
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoadBand {
+    pub name: String,
+    pub width: f32,
+    // multiplies the texture, or is the color of the band when there is no texture
+    pub color: Color,
+    // name of the texture in Maps::textures
+    pub texture: Option<String>,
+    // distance along the road covered by one repetition of the texture
+    pub texture_length: f32,
+    // stored in the z coordinate of the vertices, for 3D exports
+    pub height: f32,
+    // (dash length, gap length) for dashed markings
+    pub dashes: Option<(f32, f32)>,
+}
+
+impl RoadBand {
+    pub fn new(name: &str, width: f32, color: Color) -> Self {
+        RoadBand {
+            name: name.to_string(),
+            width,
+            color,
+            texture: None,
+            texture_length: 100.0,
+            height: 0.0,
+            dashes: None,
+        }
+    }
+
+    pub fn lane(width: f32) -> Self {
+        RoadBand::new("lane", width, Color::rgb(0.25, 0.25, 0.27))
+    }
+
+    pub fn shoulder(width: f32) -> Self {
+        RoadBand::new("shoulder", width, Color::rgb(0.35, 0.35, 0.35))
+    }
+
+    pub fn curb(width: f32) -> Self {
+        RoadBand {
+            height: 0.15,
+            ..RoadBand::new("curb", width, Color::rgb(0.7, 0.7, 0.7))
+        }
+    }
+
+    pub fn sidewalk(width: f32) -> Self {
+        RoadBand {
+            height: 0.15,
+            ..RoadBand::new("sidewalk", width, Color::rgb(0.6, 0.58, 0.55))
+        }
+    }
+
+    pub fn marking(width: f32) -> Self {
+        RoadBand::new("marking", width, Color::rgb(0.95, 0.95, 0.95))
+    }
+
+    pub fn dashed_marking(width: f32, dash: f32, gap: f32) -> Self {
+        RoadBand {
+            dashes: Some((dash, gap)),
+            ..RoadBand::marking(width)
+        }
+    }
+
+    pub fn with_texture(mut self, texture: &str, texture_length: f32) -> Self {
+        self.texture = Some(texture.to_string());
+        self.texture_length = texture_length;
+        self
+    }
+
+    pub fn with_height(mut self, height: f32) -> Self {
+        self.height = height;
+        self
+    }
+}
+
+// child of a road PenMesh, holding the mesh of the band with the given index
+#[derive(Component)]
+pub struct RoadBandMesh(pub usize);
+
+// color of a road band before it is tinted, kept so that baked roads can be recolored
+#[derive(Component, Clone, Copy)]
+pub struct RoadBandColor(pub Color);
+
+// color multiplying the colors of the bands of a road, set by recoloring the road
+#[derive(Component, Clone, Copy)]
+pub struct RoadTint(pub Color);
+
+/// Shape of the ends of roads made along open chains. Roads along loops have no ends
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RoadEndCap {
+    /// The road stops at the ends of the path
+    Flat,
+    /// Half discs extend the road beyond the ends of the path
+    Rounded,
+    /// The road narrows down to a point over the given distance from each end
+    Tapered(f32),
+}
+
+impl Default for RoadEndCap {
+    fn default() -> Self {
+        RoadEndCap::Flat
+    }
+}
+
+/// Cross-section of a road: its bands, ordered from the left edge to the right edge
+/// in the direction of travel. The road is centered on the path
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoadProfile {
+    pub bands: Vec<RoadBand>,
+    #[serde(default)]
+    pub end_cap: RoadEndCap,
+}
+
+impl Default for RoadProfile {
+    /// Two lanes separated by a dashed line, with solid edge lines, curbs and sidewalks
+    fn default() -> Self {
+        RoadProfile {
+            bands: vec![
+                RoadBand::sidewalk(3.0),
+                RoadBand::curb(0.5),
+                RoadBand::shoulder(1.0),
+                RoadBand::marking(0.3),
+                RoadBand::lane(6.0),
+                RoadBand::dashed_marking(0.3, 6.0, 6.0),
+                RoadBand::lane(6.0),
+                RoadBand::marking(0.3),
+                RoadBand::shoulder(1.0),
+                RoadBand::curb(0.5),
+                RoadBand::sidewalk(3.0),
+            ],
+            end_cap: RoadEndCap::Flat,
+        }
+    }
+}
+
+impl RoadProfile {
+    pub fn new(bands: Vec<RoadBand>) -> Self {
+        RoadProfile {
+            bands,
+            end_cap: RoadEndCap::Flat,
+        }
+    }
+
+    pub fn with_end_cap(mut self, end_cap: RoadEndCap) -> Self {
+        self.end_cap = end_cap;
+        self
+    }
+
+    /// The road made by earlier versions: a single textured band, `2 * half_width` wide
+    pub fn single_lane(half_width: f32) -> Self {
+        RoadProfile {
+            bands: vec![RoadBand::new("road", 2.0 * half_width, Color::WHITE)
+                .with_texture("single_lane_road", 100.0)],
+            end_cap: RoadEndCap::Flat,
+        }
+    }
+
+    pub fn width(&self) -> f32 {
+        self.bands.iter().map(|band| band.width).sum()
+    }
+
+    /// Signed distances from the path to the left and right edges of each band
+    pub fn band_offsets(&self) -> Vec<(f32, f32)> {
+        let mut left = self.width() / 2.0;
+        let mut offsets = Vec::new();
+        for band in self.bands.iter() {
+            offsets.push((left, left - band.width));
+            left -= band.width;
+        }
+        return offsets;
+    }
+}
+
+// point of the path used to build road meshes, relative to the center of the road
+#[derive(Debug, Clone, Copy)]
+pub struct RoadSample {
+    pub position: Vec2,
+    pub normal: Vec2,
+    // distance from the start of the path
+    pub distance: f32,
+    // multiplies the width of every band, following the width keyframes of the group
+    pub width_scale: f32,
+}
+
+// samples a group along its look-up table
+pub fn road_samples(
+    group: &Group,
+    bezier_assets: &BezierAssets,
+    profile: &RoadProfile,
+    num_points: u32,
+    center_of_mass: Vec2,
+) -> Vec<RoadSample> {
+    let crop = 0.000001;
+    let path_length = group.standalone_lut.path_length;
+    let profile_width = profile.width();
+
+    (0..num_points)
+        .map(|x| (x as f32) / (num_points as f32 - 0.99999) / (1.0 + 2.0 * crop) + crop)
+        .map(|t| RoadSample {
+            position: group.compute_position_with_lut(t) - center_of_mass,
+            normal: group
+                .compute_normal_with_bezier(bezier_assets, t as f64)
+                .normalize(),
+            distance: t * path_length,
+            width_scale: match group.road_width_at(t) {
+                Some(width) if profile_width > 0.0 => width / profile_width,
+                _ => 1.0,
+            },
+        })
+        .collect()
+}
+
+// narrows the road down to a point near both ends of an open path
+pub fn taper_road_samples(samples: &mut Vec<RoadSample>, taper_length: f32) {
+    if let Some(last) = samples.last() {
+        let path_length = last.distance;
+        for sample in samples.iter_mut() {
+            let from_end = sample.distance.min(path_length - sample.distance);
+            if taper_length > 0.0 && from_end < taper_length {
+                sample.width_scale *= (from_end / taper_length).max(0.0);
+            }
+        }
+    }
+}
+
+// number of steps used to draw a quarter of a rounded end cap
+const ROUND_CAP_STEPS: usize = 8;
+
+struct BandMeshBuilder {
+    positions: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+    mins_maxes: MinsMaxes,
+    height: f32,
+}
+
+impl BandMeshBuilder {
+    fn vertex(&mut self, position: Vec2, uv: [f32; 2]) -> u32 {
+        self.positions.push([position.x, position.y, self.height]);
+        self.uvs.push(uv);
+        self.mins_maxes.update(position);
+        return self.positions.len() as u32 - 1;
+    }
+
+    fn quad(&mut self, k: u32) {
+        self.indices
+            .extend_from_slice(&[k, k + 1, k + 2, k + 1, k + 3, k + 2]);
+    }
+
+    // sweeps the part of the band between the lateral offsets s0 and s1 (of the same sign)
+    // a quarter turn around the end of the path, towards the outward direction.
+    // u_direction is 1 at the end of the path and -1 at its start
+    fn round_cap(
+        &mut self,
+        end: &RoadSample,
+        outward: Vec2,
+        u_direction: f32,
+        (s0, s1): (f32, f32),
+        (v0, v1): (f32, f32),
+        u_scale: f32,
+    ) {
+        for step in 0..=ROUND_CAP_STEPS {
+            let angle = step as f32 / ROUND_CAP_STEPS as f32 * std::f32::consts::FRAC_PI_2;
+            for (s, v) in [(s0, v0), (s1, v1)] {
+                let s = s * end.width_scale;
+                let lateral = end.normal * s * angle.cos();
+                let forward = outward * s.abs() * angle.sin();
+                let u = (end.distance + u_direction * s.abs() * angle.sin()) / u_scale;
+                self.vertex(end.position + lateral + forward, [u, v]);
+            }
+            if step > 0 {
+                self.quad(self.positions.len() as u32 - 4);
+            }
+        }
+    }
+
+    fn round_caps(
+        &mut self,
+        end: &RoadSample,
+        outward: Vec2,
+        u_direction: f32,
+        offsets: (f32, f32),
+        u_scale: f32,
+    ) {
+        let (left, right) = offsets;
+        // a band crossing the path is split in two at the path,
+        // where the cap geometry has a kink
+        if left > 0.0 && right < 0.0 {
+            let v_path = left / (left - right);
+            self.round_cap(
+                end,
+                outward,
+                u_direction,
+                (left, 0.0),
+                (0.0, v_path),
+                u_scale,
+            );
+            self.round_cap(
+                end,
+                outward,
+                u_direction,
+                (0.0, right),
+                (v_path, 1.0),
+                u_scale,
+            );
+        } else {
+            self.round_cap(
+                end,
+                outward,
+                u_direction,
+                (left, right),
+                (0.0, 1.0),
+                u_scale,
+            );
+        }
+    }
+}
+
+// builds the mesh of a band lying between two offsets from the path.
+// Bands along a closed path form a loop, and their texture repeats a whole number
+// of times so that there is no seam where the loop closes. Bands along an open path
+// end with the given cap
+pub fn road_band_mesh(
+    samples: &Vec<RoadSample>,
+    offsets: (f32, f32),
+    band: &RoadBand,
+    closed: bool,
+    end_cap: RoadEndCap,
+) -> (Mesh, MinsMaxes) {
+    let mut builder = BandMeshBuilder {
+        positions: Vec::new(),
+        uvs: Vec::new(),
+        indices: Vec::new(),
+        mins_maxes: MinsMaxes::default(),
+        height: band.height,
+    };
+
+    let mut distances: Vec<f32> = samples.iter().map(|sample| sample.distance).collect();
+    let mut strip_samples = samples.clone();
+    if closed {
+        if let Some(first) = samples.first() {
+            strip_samples.push(*first);
+            let last = samples.last().unwrap();
+            distances.push(last.distance + last.position.distance(first.position));
+        }
+    }
+
+    let total_length = distances.last().cloned().unwrap_or(0.0);
+    let u_scale = if closed && total_length > 0.0 {
+        let num_repeats = (total_length / band.texture_length).round().max(1.0);
+        total_length / num_repeats
+    } else {
+        band.texture_length
+    };
+
+    for (sample, distance) in strip_samples.iter().zip(distances.iter()) {
+        let left = sample.position + sample.normal * offsets.0 * sample.width_scale;
+        let right = sample.position + sample.normal * offsets.1 * sample.width_scale;
+
+        let u = distance / u_scale;
+        builder.vertex(left, [u, 0.0]);
+        builder.vertex(right, [u, 1.0]);
+    }
+
+    for k in 0..(strip_samples.len().max(1) - 1) {
+        if let Some((dash, gap)) = band.dashes {
+            let middle = (distances[k] + distances[k + 1]) / 2.0;
+            if middle % (dash + gap) > dash {
+                continue;
+            }
+        }
+        builder.quad(k as u32 * 2);
+    }
+
+    // dashed markings stop at the end of the path
+    if !closed && end_cap == RoadEndCap::Rounded && band.dashes.is_none() && samples.len() > 1 {
+        let n = samples.len();
+        let start_outward = (samples[0].position - samples[1].position).normalize_or_zero();
+        let end_outward = (samples[n - 1].position - samples[n - 2].position).normalize_or_zero();
+        builder.round_caps(&samples[0], start_outward, -1.0, offsets, u_scale);
+        builder.round_caps(&samples[n - 1], end_outward, 1.0, offsets, u_scale);
+    }
+
+    let color = band.color.as_rgba_f32();
+    let colors = vec![color; builder.positions.len()];
+    let normals = vec![[0.0, 0.0, 1.0]; builder.positions.len()];
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, builder.positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, builder.uvs);
+    mesh.set_indices(Some(Indices::U32(builder.indices)));
+
+    return (mesh, builder.mins_maxes);
+}
@@ -0,0 +1,193 @@
+use crate::mesh::MinsMaxes;
+
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+};
+
+use lyon::tessellation::math::{point, Point};
+use lyon::tessellation::path::Path;
+use lyon::tessellation::{
+    BuffersBuilder, LineCap, LineJoin, StrokeOptions, StrokeTessellator, StrokeVertex,
+    VertexBuffers,
+};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum StrokeJoin {
+    Miter,
+    Round,
+    Bevel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum StrokeCap {
+    Butt,
+    Round,
+    Square,
+}
+
+/// Appearance of the stroke meshes made with the "make stroke" action. Contained within [`Globals`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StrokeStyle {
+    pub width: f32,
+    pub join: StrokeJoin,
+    pub cap: StrokeCap,
+    // alternating dash and gap lengths, starting with a dash. Empty for a solid line
+    pub dashes: Vec<f32>,
+}
+
+impl Default for StrokeStyle {
+    fn default() -> Self {
+        StrokeStyle {
+            width: 2.0,
+            join: StrokeJoin::Round,
+            cap: StrokeCap::Round,
+            dashes: Vec::new(),
+        }
+    }
+}
+
+impl StrokeStyle {
+    fn to_options(&self) -> StrokeOptions {
+        let join = match self.join {
+            StrokeJoin::Miter => LineJoin::Miter,
+            StrokeJoin::Round => LineJoin::Round,
+            StrokeJoin::Bevel => LineJoin::Bevel,
+        };
+        let cap = match self.cap {
+            StrokeCap::Butt => LineCap::Butt,
+            StrokeCap::Round => LineCap::Round,
+            StrokeCap::Square => LineCap::Square,
+        };
+        StrokeOptions::default()
+            .with_line_width(self.width)
+            .with_line_join(join)
+            .with_line_cap(cap)
+    }
+}
+
+// cuts a polyline into the dashes of a dash pattern. A closed polyline is opened first,
+// since its dashes have ends
+pub fn dash_polyline(polyline: &Vec<Vec2>, closed: bool, dashes: &Vec<f32>) -> Vec<Vec<Vec2>> {
+    let pattern_length: f32 = dashes.iter().sum();
+    if dashes.is_empty() || pattern_length <= 0.0 || polyline.len() < 2 {
+        return vec![polyline.clone()];
+    }
+
+    let mut points = polyline.clone();
+    if closed {
+        points.push(polyline[0]);
+    }
+
+    let mut pieces = Vec::new();
+    let mut current: Vec<Vec2> = vec![points[0]];
+    let mut pattern_idx = 0;
+    let mut left_in_pattern = dashes[0];
+    let mut drawing = true;
+
+    for segment in points.windows(2) {
+        let (mut start, end) = (segment[0], segment[1]);
+        let mut segment_length = start.distance(end);
+
+        // the segment may contain several changes between dash and gap
+        while segment_length > left_in_pattern {
+            let split = start + (end - start) * (left_in_pattern / segment_length);
+            if drawing {
+                current.push(split);
+                pieces.push(current);
+                current = Vec::new();
+            } else {
+                current = vec![split];
+            }
+            segment_length -= left_in_pattern;
+            start = split;
+            drawing = !drawing;
+            pattern_idx = (pattern_idx + 1) % dashes.len();
+            left_in_pattern = dashes[pattern_idx];
+        }
+
+        left_in_pattern -= segment_length;
+        if drawing {
+            current.push(end);
+        }
+    }
+
+    if drawing && current.len() > 1 {
+        pieces.push(current);
+    }
+    return pieces;
+}
+
+// tessellates the outline of a set of polylines, given with whether each one is closed
+pub fn stroke_mesh(
+    polylines: &Vec<(Vec<Vec2>, bool)>,
+    style: &StrokeStyle,
+    color: Color,
+) -> Option<(Mesh, MinsMaxes)> {
+    let mut path_builder = Path::builder();
+    let mut is_empty = true;
+
+    for (polyline, closed) in polylines.iter() {
+        let (pieces, closed) = if style.dashes.is_empty() {
+            (vec![polyline.clone()], *closed)
+        } else {
+            (dash_polyline(polyline, *closed, &style.dashes), false)
+        };
+
+        for piece in pieces.iter().filter(|piece| piece.len() > 1) {
+            path_builder.begin(point(piece[0].x, piece[0].y));
+            for p in piece[1..].iter() {
+                path_builder.line_to(point(p.x, p.y));
+            }
+            path_builder.end(closed);
+            is_empty = false;
+        }
+    }
+
+    if is_empty {
+        return None;
+    }
+    let path = path_builder.build();
+
+    let mut buffers: VertexBuffers<Point, u32> = VertexBuffers::new();
+    {
+        // simple_builder only writes u16 indices
+        let mut vertex_builder =
+            BuffersBuilder::new(&mut buffers, |vertex: StrokeVertex| vertex.position());
+        let mut tessellator = StrokeTessellator::new();
+        let result = tessellator.tessellate_path(&path, &style.to_options(), &mut vertex_builder);
+        if result.is_err() {
+            info!("could not tessellate stroke: {:?}", result);
+            return None;
+        }
+    }
+
+    let mut mins_maxes = MinsMaxes::default();
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    for position in buffers.vertices.iter() {
+        positions.push([position.x, position.y, 0.0]);
+        mins_maxes.update(Vec2::new(position.x, position.y));
+    }
+
+    // uvs span the bounding box, like the fill meshes
+    let (min, max) = mins_maxes.to_vec2_pair();
+    let size = (max - min).max(Vec2::splat(f32::EPSILON));
+    let uvs: Vec<[f32; 2]> = positions
+        .iter()
+        .map(|p| [(p[0] - min.x) / size.x, (p[1] - min.y) / size.y])
+        .collect();
+
+    let colors = vec![[color.r(), color.g(), color.b(), color.a()]; positions.len()];
+    let normals = vec![[0.0, 0.0, 1.0]; positions.len()];
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(buffers.indices)));
+
+    return Some((mesh, mins_maxes));
+}
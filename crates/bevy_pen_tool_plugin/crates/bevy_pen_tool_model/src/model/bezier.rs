@@ -1,4 +1,5 @@
 use crate::inputs::*;
+use crate::mesh::MeshId;
 // use crate::util::materials::*;
 use crate::model::*;
 
@@ -29,6 +30,20 @@ pub struct SpawningCurve {
     pub follow_mouse: bool,
 }
 
+// curves spawned in one go, already latched to each other, with a new group for each
+// list of curves. Used for shapes and pasted curves
+pub struct SpawningGroups {
+    pub groups: Vec<Vec<BezierHist>>,
+    pub do_send_to_history: bool,
+}
+
+// copies made by the symmetry tool of freshly spawned curves. They go into the history entry
+// that spawned the original curve, rather than into an entry of their own
+pub struct SpawnedSymmetricCopies {
+    pub original_id: BezierHistId,
+    pub groups: Vec<Vec<BezierHist>>,
+}
+
 pub type BezierHistId = u64;
 
 #[derive(Debug, Clone, Default, Inspectable)]
@@ -100,6 +115,22 @@ impl From<&Group> for GroupHist {
     }
 }
 
+// an anchor or a control point moved as part of a set, e.g. when nudging the selection
+#[derive(Debug, Clone, Default, Inspectable)]
+pub struct AnchorMove {
+    pub bezier_id: BezierHistId,
+    pub anchor: Anchor,
+    pub previous_position: Vec2,
+    pub new_position: Vec2,
+}
+
+#[derive(Debug, Clone, Default, Inspectable)]
+pub struct MeshMove {
+    pub mesh_id: MeshId,
+    pub previous_position: Vec2,
+    pub new_position: Vec2,
+}
+
 #[derive(Debug, Clone, Inspectable)]
 pub enum HistoryAction {
     MovedAnchor {
@@ -112,6 +143,8 @@ pub enum HistoryAction {
     SpawnedCurve {
         bezier_id: BezierHistId,
         bezier_hist: BezierHist,
+        // copies made by the symmetry tool, undone and redone along with the curve
+        copies: Vec<Vec<BezierHist>>,
     },
 
     DeletedCurve {
@@ -131,6 +164,58 @@ pub enum HistoryAction {
         partner_id: BezierHistId,
         self_anchor: AnchorEdge,
         partner_anchor: AnchorEdge,
+        corner: bool,
+    },
+
+    SpawnedMesh {
+        mesh_id: MeshId,
+    },
+
+    DeletedMesh {
+        mesh_id: MeshId,
+    },
+
+    MovedMesh {
+        mesh_id: MeshId,
+        previous_position: Vec2,
+        new_position: Vec2,
+    },
+
+    RecoloredMesh {
+        mesh_id: MeshId,
+        previous_color: Color,
+        new_color: Color,
+    },
+
+    SpawnedGroups {
+        groups: Vec<Vec<BezierHist>>,
+    },
+
+    // anchors and meshes moved together, undone and redone in one step
+    MovedSelection {
+        moves: Vec<AnchorMove>,
+        mesh_moves: Vec<MeshMove>,
+    },
+
+    AddedPathMarker {
+        group_id: GroupId,
+        marker_id: PathMarkerId,
+        name: String,
+        t: f32,
+    },
+
+    RemovedPathMarker {
+        group_id: GroupId,
+        marker_id: PathMarkerId,
+        name: String,
+        t: f32,
+    },
+
+    MovedPathMarker {
+        group_id: GroupId,
+        marker_id: PathMarkerId,
+        previous_t: f32,
+        new_t: f32,
     },
 
     // MovedGroup {
@@ -294,6 +379,9 @@ pub struct LatchData {
     pub latched_to_id: BezierId,
     pub self_edge: AnchorEdge,
     pub partners_edge: AnchorEdge,
+    // the control points of a corner are not mirrored: moving one leaves the other in place
+    #[serde(default)]
+    pub corner: bool,
 }
 
 impl Default for LatchData {
@@ -302,6 +390,7 @@ impl Default for LatchData {
             latched_to_id: BezierId::default(),
             self_edge: AnchorEdge::default(),
             partners_edge: AnchorEdge::default(),
+            corner: false,
         }
     }
 }
@@ -449,7 +538,9 @@ impl Bezier {
 
         let whole_distance = curve.estimate_length();
 
-        let mut look_up_table: LutDistance = Vec::new();
+        // the first entry corresponds to the start of the curve, so that the entry at index i
+        // is the curve t-value at a distance of i sections from the start
+        let mut look_up_table: LutDistance = vec![0.0];
 
         flo_curves::bezier::walk_curve_evenly(&curve, whole_distance / num_sections as f64, 0.001)
             .for_each(|section| {
@@ -464,6 +555,79 @@ impl Bezier {
         self.to_curve().estimate_length() as f32
     }
 
+    // position, first and second derivatives of the curve at t
+    pub fn derivatives_at(&self, t: f32) -> (Vec2, Vec2, Vec2) {
+        let p = &self.positions;
+        let s = 1.0 - t;
+        let position = p.start * s * s * s
+            + p.control_start * 3.0 * s * s * t
+            + p.control_end * 3.0 * s * t * t
+            + p.end * t * t * t;
+        let first = (p.control_start - p.start) * 3.0 * s * s
+            + (p.control_end - p.control_start) * 6.0 * s * t
+            + (p.end - p.control_end) * 3.0 * t * t;
+        let second = (p.control_end - p.control_start * 2.0 + p.start) * 6.0 * s
+            + (p.end - p.control_end * 2.0 + p.control_start) * 6.0 * t;
+        return (position, first, second);
+    }
+
+    // t-value and distance of the point of the curve closest to a position, refined around
+    // the closest of evenly spaced samples
+    pub fn closest_t(&self, position: Vec2, num_samples: usize) -> (f32, f32) {
+        let num_samples = num_samples.max(2);
+        let distance_at = |t: f32| self.derivatives_at(t).0.distance(position);
+
+        let mut best_t = 0.0;
+        let mut best_distance = distance_at(0.0);
+        for k in 1..=num_samples {
+            let t = k as f32 / num_samples as f32;
+            let distance = distance_at(t);
+            if distance < best_distance {
+                best_t = t;
+                best_distance = distance;
+            }
+        }
+
+        let mut step = 0.5 / num_samples as f32;
+        for _ in 0..10 {
+            for t in [(best_t - step).max(0.0), (best_t + step).min(1.0)] {
+                let distance = distance_at(t);
+                if distance < best_distance {
+                    best_t = t;
+                    best_distance = distance;
+                }
+            }
+            step *= 0.5;
+        }
+
+        return (best_t, best_distance);
+    }
+
+    // radius and center of the circle that best fits the curve at t. None where the curve
+    // is straight or degenerate
+    pub fn osculating_circle(&self, t: f32) -> Option<(f32, Vec2)> {
+        let (position, first, second) = self.derivatives_at(t);
+        let speed = first.length();
+        let cross = first.perp_dot(second);
+        if speed < f32::EPSILON || cross.abs() < f32::EPSILON {
+            return None;
+        }
+        let signed_curvature = cross / (speed * speed * speed);
+        let center = position + first.perp() / speed / signed_curvature;
+        return Some((1.0 / signed_curvature.abs(), center));
+    }
+
+    // unsigned angle between the two handles, in degrees. None if a handle has no length
+    pub fn handle_angle(&self) -> Option<f32> {
+        let p = &self.positions;
+        let handle_start = p.control_start - p.start;
+        let handle_end = p.control_end - p.end;
+        if handle_start.length() < f32::EPSILON || handle_end.length() < f32::EPSILON {
+            return None;
+        }
+        return Some(handle_start.angle_between(handle_end).abs().to_degrees());
+    }
+
     pub fn update_previous_pos(&mut self) {
         self.previous_positions = self.positions.clone();
     }
@@ -629,6 +793,53 @@ impl Bezier {
         }
     }
 
+    // moves a dragged anchor onto the snapping targets or the grid, along with its control point,
+    // and turns a dragged control point to the closest angle step. Whole curves that are moved
+    // together share the shift computed for the first one, so that they stay connected
+    pub fn snap_positions(
+        &mut self,
+        anchor: Anchor,
+        snapping: &Snapping,
+        targets: &Vec<Vec2>,
+        whole_curve_shift: &mut Option<Vec2>,
+    ) {
+        match anchor {
+            Anchor::None => {}
+
+            Anchor::Start => {
+                let shift =
+                    snapping.snap_point(self.positions.start, targets) - self.positions.start;
+                self.positions.start += shift;
+                self.positions.control_start += shift;
+            }
+            Anchor::End => {
+                let shift = snapping.snap_point(self.positions.end, targets) - self.positions.end;
+                self.positions.end += shift;
+                self.positions.control_end += shift;
+            }
+
+            Anchor::ControlStart => {
+                self.positions.control_start =
+                    snapping.snap_handle(self.positions.start, self.positions.control_start);
+            }
+            Anchor::ControlEnd => {
+                self.positions.control_end =
+                    snapping.snap_handle(self.positions.end, self.positions.control_end);
+            }
+
+            // the whole curve follows its start anchor
+            Anchor::All => {
+                let start = self.positions.start;
+                let shift = *whole_curve_shift
+                    .get_or_insert_with(|| snapping.snap_point(start, targets) - start);
+                self.positions.start += shift;
+                self.positions.end += shift;
+                self.positions.control_start += shift;
+                self.positions.control_end += shift;
+            }
+        }
+    }
+
     // gives the LatchData of the anchor that is attached to the moving anchor
     pub fn get_anchor_latch_info(&self, anchor: Anchor) -> Option<(LatchData, Vec2, Vec2)> {
         match anchor {
@@ -691,6 +902,7 @@ impl Bezier {
             latched_to_id: latch.latcher_id,
             self_edge: anchor_edge,
             partners_edge: AnchorEdge::Start,
+            corner: false,
         };
 
         self.latches.insert(anchor_edge, latch_start);
@@ -790,12 +1002,14 @@ impl Bezier {
     }
 
     pub fn compute_real_distance(&self, t: f64) -> f64 {
-        let idx_f64 = t * (self.lut.len() - 1) as f64;
-        let p1 = self.lut[(idx_f64 as usize)];
-        let p2 = self.lut[idx_f64 as usize + 1];
-        //
-        // TODO: is the minus one useful here?
-        let rem = (idx_f64 - 1.0) % 1.0;
+        if self.lut.len() < 2 {
+            return t;
+        }
+        let idx_f64 = t.clamp(0.0, 1.0) * (self.lut.len() - 1) as f64;
+        let idx = (idx_f64 as usize).min(self.lut.len() - 2);
+        let p1 = self.lut[idx];
+        let p2 = self.lut[idx + 1];
+        let rem = idx_f64 - idx as f64;
         let t_distance = interpolate(p1, p2, rem);
         return t_distance;
     }
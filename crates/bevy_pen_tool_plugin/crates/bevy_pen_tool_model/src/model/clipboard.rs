@@ -0,0 +1,278 @@
+use crate::model::*;
+
+use bevy::prelude::*;
+
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use std::collections::{HashMap, HashSet};
+
+/// Latch between two copied curves. The partner is the index of the curve in the document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClipboardLatch {
+    pub self_edge: AnchorEdge,
+    pub partner: usize,
+    pub partners_edge: AnchorEdge,
+    #[serde(default)]
+    pub corner: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClipboardCurve {
+    pub positions: BezierPositions,
+    pub color: Option<Color>,
+    // curves latched to each other, directly or through other curves, share the same group
+    pub group: usize,
+    pub latches: Vec<ClipboardLatch>,
+}
+
+/// Copied curves, independent of the ids of the editor they were copied from, so that they
+/// can be pasted in another running editor through a file or the system clipboard
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClipboardDocument {
+    pub curves: Vec<ClipboardCurve>,
+}
+
+impl ClipboardDocument {
+    pub fn from_curves(
+        ids: &HashSet<BezierId>,
+        bezier_curves: &Assets<Bezier>,
+        maps: &Maps,
+    ) -> Self {
+        let beziers: Vec<&Bezier> = ids
+            .iter()
+            .filter_map(|id| maps.bezier_map.get(id))
+            .filter_map(|handle_entity| bezier_curves.get(&handle_entity.handle))
+            .collect();
+
+        Self::from_beziers(&beziers)
+    }
+
+    // the latches between the copied curves point to their index in the document
+    pub fn from_beziers(beziers: &[&Bezier]) -> Self {
+        let indices: HashMap<BezierId, usize> = beziers
+            .iter()
+            .enumerate()
+            .map(|(idx, bezier)| (bezier.id, idx))
+            .collect();
+
+        // latches to curves that were not copied are dropped
+        let latches: Vec<Vec<ClipboardLatch>> = beziers
+            .iter()
+            .map(|bezier| {
+                bezier
+                    .latches
+                    .values()
+                    .filter_map(|latch| {
+                        indices
+                            .get(&latch.latched_to_id)
+                            .map(|partner| ClipboardLatch {
+                                self_edge: latch.self_edge,
+                                partner: *partner,
+                                partners_edge: latch.partners_edge,
+                                corner: latch.corner,
+                            })
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // connected components of the copied curves, following the latches
+        let mut groups: Vec<Option<usize>> = vec![None; beziers.len()];
+        let mut num_groups = 0;
+        for first in 0..beziers.len() {
+            if groups[first].is_some() {
+                continue;
+            }
+            let mut stack = vec![first];
+            while let Some(idx) = stack.pop() {
+                if groups[idx].is_some() {
+                    continue;
+                }
+                groups[idx] = Some(num_groups);
+                stack.extend(latches[idx].iter().map(|latch| latch.partner));
+            }
+            num_groups += 1;
+        }
+
+        let curves = beziers
+            .iter()
+            .zip(latches.into_iter())
+            .zip(groups.into_iter())
+            .map(|((bezier, latches), group)| ClipboardCurve {
+                positions: bezier.positions,
+                color: bezier.color,
+                group: group.unwrap(),
+                latches,
+            })
+            .collect();
+
+        ClipboardDocument { curves }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.curves.is_empty()
+    }
+
+    // curves ready to be spawned, one list per group, with fresh ids and moved by offset
+    pub fn to_groups(&self, offset: Vec2) -> Vec<Vec<BezierHist>> {
+        let mut rng = thread_rng();
+        let ids: Vec<BezierHistId> = self.curves.iter().map(|_| rng.gen()).collect();
+
+        let mut groups: HashMap<usize, Vec<BezierHist>> = HashMap::new();
+        for (curve, id) in self.curves.iter().zip(ids.iter()) {
+            let positions = BezierPositions {
+                start: curve.positions.start + offset,
+                end: curve.positions.end + offset,
+                control_start: curve.positions.control_start + offset,
+                control_end: curve.positions.control_end + offset,
+            };
+
+            // a document coming from the outside may point to curves that do not exist
+            let latches = curve
+                .latches
+                .iter()
+                .filter_map(|latch| {
+                    ids.get(latch.partner).map(|partner_id| {
+                        (
+                            latch.self_edge,
+                            LatchData {
+                                latched_to_id: (*partner_id).into(),
+                                self_edge: latch.self_edge,
+                                partners_edge: latch.partners_edge,
+                                corner: latch.corner,
+                            },
+                        )
+                    })
+                })
+                .collect();
+
+            groups.entry(curve.group).or_default().push(BezierHist {
+                color: curve.color,
+                latches,
+                ..BezierHist::new(positions, *id)
+            });
+        }
+
+        groups.into_values().collect()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn from_json(text: &str) -> Option<Self> {
+        serde_json::from_str(text).ok()
+    }
+}
+
+/// Last copied curves. Each paste of the same document is offset a bit further, so that
+/// the copies do not pile up on top of each other
+#[derive(Debug, Clone, Default)]
+pub struct Clipboard {
+    pub document: Option<ClipboardDocument>,
+    pub pastes: u32,
+    // set when cutting, so that the copied curves get deleted
+    pub delete_copied: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn latch(partner: BezierId, self_edge: AnchorEdge, partners_edge: AnchorEdge) -> LatchData {
+        LatchData {
+            latched_to_id: partner,
+            self_edge,
+            partners_edge,
+            corner: true,
+        }
+    }
+
+    fn bezier(id: BezierHistId, start: Vec2, latches: Vec<LatchData>) -> Bezier {
+        Bezier {
+            positions: BezierPositions {
+                start,
+                end: start + Vec2::new(10.0, 0.0),
+                control_start: start + Vec2::new(3.0, 5.0),
+                control_end: start + Vec2::new(7.0, 5.0),
+            },
+            id: id.into(),
+            latches: latches
+                .into_iter()
+                .map(|latch| (latch.self_edge, latch))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn pasted_curves_get_new_ids_and_keep_their_latches() {
+        let (id_a, id_b, id_c, id_not_copied) = (1, 2, 3, 4);
+        let a = bezier(
+            id_a,
+            Vec2::new(0.0, 0.0),
+            vec![latch(id_b.into(), AnchorEdge::End, AnchorEdge::Start)],
+        );
+        let b = bezier(
+            id_b,
+            Vec2::new(10.0, 0.0),
+            vec![latch(id_a.into(), AnchorEdge::Start, AnchorEdge::End)],
+        );
+        // the latch to a curve that was not copied is dropped
+        let c = bezier(
+            id_c,
+            Vec2::new(50.0, 50.0),
+            vec![latch(
+                id_not_copied.into(),
+                AnchorEdge::Start,
+                AnchorEdge::End,
+            )],
+        );
+
+        let document = ClipboardDocument::from_beziers(&[&a, &b, &c]);
+        assert_eq!(document.curves.len(), 3);
+        assert_eq!(document.curves[0].group, document.curves[1].group);
+        assert_ne!(document.curves[0].group, document.curves[2].group);
+        assert!(document.curves[2].latches.is_empty());
+
+        // the document survives the round trip through the system clipboard
+        let document = ClipboardDocument::from_json(&document.to_json()).unwrap();
+
+        let offset = Vec2::new(20.0, -20.0);
+        let mut groups = document.to_groups(offset);
+        groups.sort_by_key(|group| group.len());
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].len(), 1);
+        assert_eq!(groups[1].len(), 2);
+
+        let pasted_c = &groups[0][0];
+        assert!(pasted_c.latches.is_empty());
+        assert_eq!(pasted_c.positions.start, c.positions.start + offset);
+
+        let (pasted_a, pasted_b) = if groups[1][0].positions.start == a.positions.start + offset {
+            (&groups[1][0], &groups[1][1])
+        } else {
+            (&groups[1][1], &groups[1][0])
+        };
+        assert_eq!(pasted_a.positions.end, a.positions.end + offset);
+        assert_eq!(
+            pasted_b.positions.control_end,
+            b.positions.control_end + offset
+        );
+
+        let pasted_ids = [pasted_a.id, pasted_b.id, pasted_c.id];
+        for id in [id_a, id_b, id_c] {
+            assert!(!pasted_ids.contains(&id));
+        }
+
+        assert_eq!(
+            pasted_a.latches[&AnchorEdge::End],
+            latch(pasted_b.id.into(), AnchorEdge::End, AnchorEdge::Start)
+        );
+        assert_eq!(
+            pasted_b.latches[&AnchorEdge::Start],
+            latch(pasted_a.id.into(), AnchorEdge::Start, AnchorEdge::End)
+        );
+    }
+}
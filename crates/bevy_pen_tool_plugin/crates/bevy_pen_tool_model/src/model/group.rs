@@ -1,5 +1,7 @@
 use crate::model::*;
 
+use bevy_pen_tool_runtime::{speed_multiplier_at, PathMarker, SpeedRange, StandaloneLut};
+
 use bevy::{prelude::*, reflect::TypeUuid};
 
 use serde::{Deserialize, Serialize};
@@ -18,8 +20,6 @@ use flo_curves::*;
 // map from t-values (between 0 and 1) to distance on Bezier curve.
 // A t-values is converted to an index in the LUT
 pub type LutDistance = Vec<f64>;
-// map from t-values (between 0 and 1) to point on Bezier curve
-type LutPosition = Vec<Vec2>;
 
 pub struct ComputeGroupLut(pub GroupId);
 
@@ -28,15 +28,76 @@ pub struct GroupMiddleQuad(pub usize);
 
 pub struct GroupBoxEvent;
 
+/// Identifies a path marker within its group. Markers are reordered by t-value,
+/// so their index cannot be used to refer to them over time
+pub type PathMarkerId = u32;
+
+// quad showing the PathMarker with the given id on the canvas
+#[derive(Component)]
+pub struct PathMarkerQuad(pub PathMarkerId);
+
+// attached to a PathMarkerQuad while it is being dragged along its group. previous_t is the
+// t-value of the marker when the drag started
+#[derive(Component)]
+pub struct MovingPathMarker {
+    pub previous_t: f32,
+}
+
+// handle on the edge of the road showing the WidthKeyframe with the given index
+#[derive(Component)]
+pub struct WidthKeyframeQuad(pub usize);
+
+// attached to a WidthKeyframeQuad while it is being dragged
+#[derive(Component)]
+pub struct MovingWidthKeyframe;
+
+/// Handles drawn along a group, which can be added, removed and dragged on the canvas
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupHandleKind {
+    PathMarker,
+    WidthKeyframe,
+}
+
+/// Total width of the roads made along a group at a given t-value.
+/// Widths between keyframes are interpolated smoothly
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct WidthKeyframe {
+    pub t: f32,
+    pub width: f32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LutSaveLoad {
     pub lut: Vec<((f64, f64), LutDistance)>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub struct StandaloneLut {
-    pub path_length: f32,
-    pub lut: LutPosition,
+/// How the points of the standalone look-up table of a group are placed along the path
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum LutSampling {
+    /// Evenly spaced group t-values. Each curve covers a share of t proportional to its length,
+    /// and t is converted to a distance on the curve with the curve's own look-up table
+    GroupT,
+    /// Points at equal arc-length intervals over the whole group, for constant-speed motion
+    ArcLength,
+    /// As few points as possible while keeping the path within the group's `lut_tolerance`
+    /// of the curves: many points in tight turns, few on straight parts. The look-up table
+    /// stores the distance of each point, and `Globals::group_lut_num_points` is ignored
+    Adaptive,
+}
+
+pub fn default_lut_tolerance() -> f32 {
+    0.5
+}
+
+/// Layout of the curve look-up tables stored in group files. In layout 0, the table did not
+/// start with the t-value of the start of the curve. Since layout 1, the entry at index i
+/// is the curve t-value at a distance of i sections from the start
+pub const CURVE_LUT_LAYOUT: u32 = 1;
+
+impl Default for LutSampling {
+    fn default() -> Self {
+        LutSampling::GroupT
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,6 +105,32 @@ pub struct GroupSaveLoad {
     // the AnchorEdge corresponds to first anchor encountered when traversing the group
     pub lut: Vec<(Bezier, AnchorEdge, (f64, f64), LutDistance)>,
     pub standalone_lut: StandaloneLut,
+    // older group files do not have a speed profile
+    #[serde(default)]
+    pub speed_profile: Vec<SpeedRange>,
+    #[serde(default)]
+    pub markers: Vec<PathMarker>,
+    #[serde(default)]
+    pub lut_sampling: LutSampling,
+    #[serde(default = "default_lut_tolerance")]
+    pub lut_tolerance: f32,
+    #[serde(default)]
+    pub width_keyframes: Vec<WidthKeyframe>,
+    // older group files use layout 0
+    #[serde(default)]
+    pub lut_layout: u32,
+}
+
+impl GroupSaveLoad {
+    /// Converts the curve look-up tables of a group file saved with an older layout
+    pub fn migrate_lut_layout(&mut self) {
+        if self.lut_layout == 0 {
+            for (_bezier, _anchor, _t_ends, local_lut) in self.lut.iter_mut() {
+                local_lut.insert(0, 0.0);
+            }
+        }
+        self.lut_layout = CURVE_LUT_LAYOUT;
+    }
 }
 
 // #[derive(Debug, Clone)]
@@ -69,6 +156,26 @@ pub struct Group {
     // the AnchorEdge is the starting
     pub lut: Vec<(Handle<Bezier>, AnchorEdge, (f64, f64), LutDistance)>,
     pub standalone_lut: StandaloneLut,
+    //
+    // speed multipliers applied to followers on parts of the path
+    pub speed_profile: Vec<SpeedRange>,
+    //
+    // named positions along the path, sorted by t-value
+    pub markers: Vec<PathMarker>,
+    //
+    // ids of the markers, in the same order as the markers
+    pub marker_ids: Vec<PathMarkerId>,
+    //
+    // road widths along the path, sorted by t-value
+    pub width_keyframes: Vec<WidthKeyframe>,
+    pub lut_sampling: LutSampling,
+    //
+    // maximum distance (in world units) between the path and the adaptive look-up table
+    pub lut_tolerance: f32,
+    //
+    // largest difference (in world units) between the distance separating two consecutive
+    // points of the standalone look-up table and the ideal spacing. Updated with the table
+    pub lut_spacing_error: f32,
     pub id: GroupId,
     pub entity: Option<Entity>,
 }
@@ -81,10 +188,14 @@ impl Default for Group {
             bezier_handles: HashSet::new(),
             lut: Vec::new(),
             ends: None,
-            standalone_lut: StandaloneLut {
-                path_length: 0.0,
-                lut: Vec::new(),
-            },
+            standalone_lut: StandaloneLut::default(),
+            speed_profile: Vec::new(),
+            markers: Vec::new(),
+            marker_ids: Vec::new(),
+            width_keyframes: Vec::new(),
+            lut_sampling: LutSampling::default(),
+            lut_tolerance: default_lut_tolerance(),
+            lut_spacing_error: 0.0,
             id: GroupId::default(),
             entity: None,
             // ..Default::default() // group_id: HandleId::default(),
@@ -132,9 +243,211 @@ impl Group {
         GroupSaveLoad {
             lut,
             standalone_lut: self.standalone_lut.clone(),
+            speed_profile: self.speed_profile.clone(),
+            markers: self.markers.clone(),
+            lut_sampling: self.lut_sampling,
+            lut_tolerance: self.lut_tolerance,
+            width_keyframes: self.width_keyframes.clone(),
+            lut_layout: CURVE_LUT_LAYOUT,
         }
     }
 
+    /// Adds a named marker at a group t-value (between 0 and 1), and returns its id
+    pub fn add_marker(&mut self, name: &str, t: f32) -> PathMarkerId {
+        let id = self.marker_ids.iter().max().map_or(0, |id| id + 1);
+        self.insert_marker(
+            id,
+            PathMarker {
+                name: name.to_string(),
+                t,
+            },
+        );
+        return id;
+    }
+
+    /// Adds a marker under a given id, as when a marker removal is undone
+    pub fn insert_marker(&mut self, id: PathMarkerId, marker: PathMarker) {
+        self.markers.push(PathMarker {
+            t: marker.t.clamp(0.0, 1.0),
+            ..marker
+        });
+        self.marker_ids.push(id);
+        self.sort_markers();
+    }
+
+    /// Adds a named marker at a distance from the start of the group
+    pub fn add_marker_at_distance(&mut self, name: &str, distance: f32) {
+        let path_length = self.standalone_lut.path_length;
+        if path_length > 0.0 {
+            self.add_marker(name, distance / path_length);
+        }
+    }
+
+    /// Replaces the markers of the group, giving them new ids
+    pub fn set_markers(&mut self, markers: Vec<PathMarker>) {
+        self.marker_ids = (0..markers.len() as PathMarkerId).collect();
+        self.markers = markers;
+        self.sort_markers();
+    }
+
+    /// Removes the marker with the given id, and returns it
+    pub fn remove_marker(&mut self, id: PathMarkerId) -> Option<PathMarker> {
+        let idx = self.marker_index(id)?;
+        self.marker_ids.remove(idx);
+        let marker = self.markers.remove(idx);
+        self.standalone_lut.markers = self.markers.clone();
+        return Some(marker);
+    }
+
+    /// Index of the marker with the given id in the sorted markers
+    pub fn marker_index(&self, id: PathMarkerId) -> Option<usize> {
+        return self
+            .marker_ids
+            .iter()
+            .position(|marker_id| *marker_id == id);
+    }
+
+    /// Marker with the given id
+    pub fn marker(&self, id: PathMarkerId) -> Option<&PathMarker> {
+        return self.marker_index(id).map(|idx| &self.markers[idx]);
+    }
+
+    /// Moves the marker with the given id without sorting the markers, as done while the
+    /// marker is being dragged
+    pub fn set_marker_t(&mut self, id: PathMarkerId, t: f32) {
+        if let Some(idx) = self.marker_index(id) {
+            self.markers[idx].t = t.clamp(0.0, 1.0);
+        }
+    }
+
+    pub fn sort_markers(&mut self) {
+        let mut markers = self
+            .markers
+            .drain(..)
+            .zip(self.marker_ids.drain(..))
+            .collect::<Vec<_>>();
+        markers
+            .sort_by(|(a, _), (b, _)| a.t.partial_cmp(&b.t).unwrap_or(std::cmp::Ordering::Equal));
+        let (markers, marker_ids) = markers.into_iter().unzip();
+        self.markers = markers;
+        self.marker_ids = marker_ids;
+        self.standalone_lut.markers = self.markers.clone();
+    }
+
+    /// Sets the total road width at a group t-value (between 0 and 1)
+    pub fn add_width_keyframe(&mut self, t: f32, width: f32) {
+        self.width_keyframes.push(WidthKeyframe {
+            t: t.clamp(0.0, 1.0),
+            width: width.max(0.0),
+        });
+        self.sort_width_keyframes();
+    }
+
+    /// Sets the total road width at a distance from the start of the group
+    pub fn add_width_keyframe_at_distance(&mut self, distance: f32, width: f32) {
+        let path_length = self.standalone_lut.path_length;
+        if path_length > 0.0 {
+            self.add_width_keyframe(distance / path_length, width);
+        }
+    }
+
+    pub fn sort_width_keyframes(&mut self) {
+        self.width_keyframes
+            .sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap_or(std::cmp::Ordering::Equal));
+    }
+
+    /// Road width at a given t-value, or None if the group has no width keyframes.
+    /// The width is constant before the first keyframe and after the last one,
+    /// and follows a smoothstep between two keyframes
+    pub fn road_width_at(&self, t: f32) -> Option<f32> {
+        let keyframes = &self.width_keyframes;
+        let first = keyframes.first()?;
+        let last = keyframes.last()?;
+        if t <= first.t {
+            return Some(first.width);
+        }
+        if t >= last.t {
+            return Some(last.width);
+        }
+        for pair in keyframes.windows(2) {
+            let (k0, k1) = (pair[0], pair[1]);
+            if t >= k0.t && t <= k1.t {
+                if k1.t - k0.t <= 0.0 {
+                    return Some(k1.width);
+                }
+                let x = (t - k0.t) / (k1.t - k0.t);
+                let smooth = x * x * (3.0 - 2.0 * x);
+                return Some(k0.width + (k1.width - k0.width) * smooth);
+            }
+        }
+        return Some(last.width);
+    }
+
+    /// Position of a width keyframe handle, on the left edge of the road.
+    /// The normal is the one used to build the road meshes
+    pub fn width_keyframe_position(
+        &self,
+        bezier_curves: &BezierAssets,
+        keyframe: &WidthKeyframe,
+    ) -> Vec2 {
+        let normal = self
+            .compute_normal_with_bezier(bezier_curves, keyframe.t as f64)
+            .normalize_or_zero();
+        self.compute_position_with_lut(keyframe.t) + normal * keyframe.width / 2.0
+    }
+
+    /// Number of path markers or width keyframes of the group
+    pub fn handle_count(&self, kind: GroupHandleKind) -> usize {
+        match kind {
+            GroupHandleKind::PathMarker => self.markers.len(),
+            GroupHandleKind::WidthKeyframe => self.width_keyframes.len(),
+        }
+    }
+
+    /// Canvas position of the handle of the path marker or width keyframe at index idx
+    pub fn handle_position(
+        &self,
+        bezier_curves: &BezierAssets,
+        kind: GroupHandleKind,
+        idx: usize,
+    ) -> Option<Vec2> {
+        match kind {
+            GroupHandleKind::PathMarker => self
+                .markers
+                .get(idx)
+                .map(|marker| self.compute_position_with_lut(marker.t)),
+            GroupHandleKind::WidthKeyframe => self
+                .width_keyframes
+                .get(idx)
+                .map(|keyframe| self.width_keyframe_position(bezier_curves, keyframe)),
+        }
+    }
+
+    /// Finds the t-value of the point of the group closest to a position,
+    /// along with the distance between the two
+    pub fn closest_t(&self, position: Vec2) -> Option<(f32, f32)> {
+        self.standalone_lut.closest_t(position)
+    }
+
+    /// Adds a speed multiplier for followers travelling between t_min and t_max
+    pub fn add_speed_range(&mut self, t_min: f32, t_max: f32, multiplier: f32) {
+        let (t_min, t_max) = if t_min <= t_max {
+            (t_min, t_max)
+        } else {
+            (t_max, t_min)
+        };
+        self.speed_profile.push(SpeedRange {
+            t_min: t_min.clamp(0.0, 1.0),
+            t_max: t_max.clamp(0.0, 1.0),
+            multiplier,
+        });
+        self.standalone_lut.speed_profile = self.speed_profile.clone();
+    }
+
+    pub fn speed_multiplier_at(&self, t: f32) -> f32 {
+        speed_multiplier_at(&self.speed_profile, t)
+    }
+
     pub fn find_connected_ends(
         &mut self,
         bezier_curves: &BezierAssets, //&Res<Assets<Bezier>>,
@@ -244,6 +557,15 @@ impl Group {
         }
     }
 
+    /// Whether the curves of the group form a loop. Only valid after find_connected_ends,
+    /// which finds no ends for loops
+    pub fn is_closed(&self) -> bool {
+        match &self.ends {
+            Some(ends) => ends.is_empty() && self.bezier_handles.len() > 1,
+            None => false,
+        }
+    }
+
     pub fn group_lut(
         &mut self,
         bezier_curves: &BezierAssets,
@@ -457,6 +779,19 @@ impl Group {
     }
 
     pub fn compute_standalone_lut(&mut self, bezier_curves: &BezierAssets, num_points: u32) {
+        let mut standalone_lut = match self.lut_sampling {
+            LutSampling::GroupT => self.group_t_lut(bezier_curves, num_points),
+            LutSampling::ArcLength => self.arc_length_lut(bezier_curves, num_points),
+            LutSampling::Adaptive => self.adaptive_lut(bezier_curves),
+        };
+        standalone_lut.markers = self.markers.clone();
+        standalone_lut.speed_profile = self.speed_profile.clone();
+
+        self.lut_spacing_error = spacing_error(&standalone_lut);
+        self.standalone_lut = standalone_lut;
+    }
+
+    fn group_t_lut(&self, bezier_curves: &BezierAssets, num_points: u32) -> StandaloneLut {
         let mut total_length: f32 = 0.0;
         for lut in self.lut.clone() {
             if let Some(bezier) = bezier_curves.get(&lut.0.id) {
@@ -471,6 +806,7 @@ impl Group {
         let mut standalone_lut: StandaloneLut = StandaloneLut {
             path_length: total_length,
             lut: Vec::new(),
+            ..Default::default()
         };
         for t in t_range {
             let val = self.compute_position_with_bezier(bezier_curves, t);
@@ -478,22 +814,143 @@ impl Group {
             standalone_lut.lut.push(val);
         }
 
-        self.standalone_lut = standalone_lut;
+        return standalone_lut;
+    }
+
+    // densely samples every curve of the group in traversal order
+    fn dense_polyline(&self, bezier_curves: &BezierAssets, samples_per_curve: usize) -> Vec<Vec2> {
+        let mut polyline: Vec<Vec2> = Vec::new();
+        for (handle, anchor, _t_range, _lut) in &self.lut {
+            if let Some(bezier) = bezier_curves.get(&handle.id) {
+                let curve = bezier.to_curve();
+                // the first point of a curve is the last point of the previous one
+                let first_sample = if polyline.is_empty() { 0 } else { 1 };
+                for k in first_sample..=samples_per_curve {
+                    let mut t = k as f64 / samples_per_curve as f64;
+                    if anchor == &AnchorEdge::Start {
+                        t = 1.0 - t;
+                    }
+                    let point = curve.point_at_pos(t);
+                    polyline.push(Vec2::new(point.0 as f32, point.1 as f32));
+                }
+            }
+        }
+        return polyline;
+    }
+
+    // places the points of the look-up table at equal distances along a dense polyline
+    fn arc_length_lut(&self, bezier_curves: &BezierAssets, num_points: u32) -> StandaloneLut {
+        let samples_per_curve = (num_points as usize * 10).max(100);
+        let polyline = self.dense_polyline(bezier_curves, samples_per_curve);
+
+        let mut standalone_lut = StandaloneLut::default();
+        if polyline.len() < 2 || num_points < 2 {
+            standalone_lut.lut = polyline;
+            return standalone_lut;
+        }
+
+        let distances = cumulative_distances(&polyline);
+        let path_length = *distances.last().unwrap();
+        standalone_lut.path_length = path_length;
+
+        let spacing = path_length / (num_points - 1) as f32;
+        let mut idx = 0;
+        for k in 0..num_points {
+            let target = (k as f32 * spacing).min(path_length);
+            while idx < polyline.len() - 2 && distances[idx + 1] < target {
+                idx += 1;
+            }
+            let segment_length = distances[idx + 1] - distances[idx];
+            let rem = if segment_length > 0.0 {
+                ((target - distances[idx]) / segment_length).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            standalone_lut
+                .lut
+                .push(interpolate_vec2(polyline[idx], polyline[idx + 1], rem));
+        }
+
+        return standalone_lut;
+    }
+
+    // keeps the points of a dense polyline that are needed for the path to stay within
+    // lut_tolerance of it (Douglas-Peucker), along with their distance from the start
+    fn adaptive_lut(&self, bezier_curves: &BezierAssets) -> StandaloneLut {
+        let polyline = self.dense_polyline(bezier_curves, 500);
+
+        let mut standalone_lut = StandaloneLut::default();
+        if polyline.len() < 2 {
+            standalone_lut.lut = polyline;
+            return standalone_lut;
+        }
+
+        let distances = cumulative_distances(&polyline);
+        standalone_lut.path_length = *distances.last().unwrap();
+
+        let tolerance = self.lut_tolerance.max(0.001);
+        let mut keep = vec![false; polyline.len()];
+        keep[0] = true;
+        keep[polyline.len() - 1] = true;
+
+        let mut ranges = vec![(0, polyline.len() - 1)];
+        while let Some((first, last)) = ranges.pop() {
+            let mut farthest: Option<(usize, f32)> = None;
+            for idx in first + 1..last {
+                let deviation = distance_to_segment(polyline[idx], polyline[first], polyline[last]);
+                if farthest.is_none() || deviation > farthest.unwrap().1 {
+                    farthest = Some((idx, deviation));
+                }
+            }
+            if let Some((idx, deviation)) = farthest {
+                if deviation > tolerance {
+                    keep[idx] = true;
+                    ranges.push((first, idx));
+                    ranges.push((idx, last));
+                }
+            }
+        }
+
+        for (idx, point) in polyline.iter().enumerate() {
+            if keep[idx] {
+                standalone_lut.lut.push(*point);
+                standalone_lut.distances.push(distances[idx]);
+            }
+        }
+
+        return standalone_lut;
     }
+
     // this is now used inside the plugin, but this would be the function used in
     // an application where the look-up table (lut) would be loaded
     pub fn compute_position_with_lut(&self, t: f32) -> Vec2 {
-        let lut = self.standalone_lut.lut.clone();
-        if lut.len() > 0 {
-            let idx_f64 = t * (lut.len() - 1) as f32;
-            let p1 = lut[(idx_f64 as usize)];
-            let p2 = lut[idx_f64 as usize + 1];
-            let rem = idx_f64 % 1.0;
-            let position = interpolate_vec2(p1, p2, rem);
-            return position;
-        } else {
-            return Vec2::ZERO;
+        self.standalone_lut.position_at(t)
+    }
+
+    /// Unit tangent of the group at a given t-value, in the direction of increasing t
+    pub fn compute_tangent_with_lut(&self, t: f32) -> Vec2 {
+        self.standalone_lut.tangent_at(t)
+    }
+
+    pub fn compute_normal_with_lut(&self, t: f32) -> Vec2 {
+        self.standalone_lut.normal_at(t)
+    }
+
+    /// Control points (start, start control, end control, end) of the curves of the group,
+    /// in the order and direction in which the group is traversed
+    pub fn cubic_segments(&self, bezier_curves: &BezierAssets) -> Vec<[Vec2; 4]> {
+        let mut segments = Vec::new();
+        for (handle, anchor, _t_range, _lut) in &self.lut {
+            if let Some(bezier) = bezier_curves.get(&handle.id) {
+                let p = bezier.positions;
+                if anchor == &AnchorEdge::Start {
+                    segments.push([p.end, p.control_end, p.control_start, p.start]);
+                } else {
+                    segments.push([p.start, p.control_start, p.control_end, p.end]);
+                }
+            }
         }
+        return segments;
     }
 
     // compute the average position of the anchors making up the group
@@ -513,3 +970,81 @@ impl Group {
         return center_of_mass;
     }
 }
+
+fn cumulative_distances(polyline: &Vec<Vec2>) -> Vec<f32> {
+    let mut distances = vec![0.0];
+    for segment in polyline.windows(2) {
+        let last = *distances.last().unwrap();
+        distances.push(last + segment[0].distance(segment[1]));
+    }
+    return distances;
+}
+
+fn distance_to_segment(point: Vec2, start: Vec2, end: Vec2) -> f32 {
+    let direction = end - start;
+    let length_squared = direction.length_squared();
+    if length_squared == 0.0 {
+        return point.distance(start);
+    }
+    let rem = ((point - start).dot(direction) / length_squared).clamp(0.0, 1.0);
+    return point.distance(start + direction * rem);
+}
+
+// largest difference between the distance separating consecutive points of a look-up table
+// and the spacing they would have if they were equally spaced along the path.
+// For look-up tables with a distance column, the spacing given by the column is used instead
+pub fn spacing_error(standalone_lut: &StandaloneLut) -> f32 {
+    let lut = &standalone_lut.lut;
+    if lut.len() < 2 {
+        return 0.0;
+    }
+    if standalone_lut.distances.len() == lut.len() {
+        return lut
+            .windows(2)
+            .zip(standalone_lut.distances.windows(2))
+            .map(|(segment, distances)| {
+                (segment[0].distance(segment[1]) - (distances[1] - distances[0])).abs()
+            })
+            .fold(0.0, f32::max);
+    }
+    let ideal_spacing = standalone_lut.path_length / (lut.len() - 1) as f32;
+    lut.windows(2)
+        .map(|segment| (segment[0].distance(segment[1]) - ideal_spacing).abs())
+        .fold(0.0, f32::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marker_ids_follow_their_markers_when_sorted() {
+        let mut group = Group::default();
+        let late = group.add_marker("late", 0.8);
+        let early = group.add_marker("early", 0.2);
+        assert_ne!(late, early);
+        assert_eq!(group.marker_index(early), Some(0));
+
+        // dragging the early marker past the late one reorders them on release
+        group.set_marker_t(early, 0.9);
+        group.sort_markers();
+        assert_eq!(group.marker_index(late), Some(0));
+        assert_eq!(group.marker(early).unwrap().name, "early");
+        assert_eq!(group.standalone_lut.markers, group.markers);
+    }
+
+    #[test]
+    fn removed_markers_come_back_under_their_id() {
+        let mut group = Group::default();
+        let first = group.add_marker("first", 0.3);
+        let second = group.add_marker("second", 0.6);
+
+        let removed = group.remove_marker(first).unwrap();
+        assert_eq!(group.markers.len(), 1);
+        assert!(group.marker(first).is_none());
+
+        group.insert_marker(first, removed);
+        assert_eq!(group.marker(first).unwrap().t, 0.3);
+        assert_eq!(group.marker_index(second), Some(1));
+    }
+}
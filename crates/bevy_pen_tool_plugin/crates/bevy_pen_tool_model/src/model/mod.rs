@@ -1,7 +1,19 @@
 mod bezier;
+mod clipboard;
 mod group;
+mod reference_image;
+mod ruler;
+mod shapes;
+mod snapping;
+mod symmetry;
 pub mod util;
 
 pub use bezier::*;
+pub use clipboard::*;
 pub use group::*;
+pub use reference_image::*;
+pub use ruler::*;
+pub use shapes::*;
+pub use snapping::*;
+pub use symmetry::*;
 pub use util::*;
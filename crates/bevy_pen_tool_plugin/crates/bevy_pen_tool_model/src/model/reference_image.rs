@@ -0,0 +1,121 @@
+use crate::model::*;
+
+use bevy::prelude::*;
+
+use serde::{Deserialize, Serialize};
+
+/// Background image to trace roads and paths over, loaded with Left Control + B. It is drawn
+/// below everything else and never takes the clicks meant for the curves. Its settings are
+/// saved along with the groups, in a .canvas file next to the .group file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReferenceImage {
+    pub path: Option<String>,
+    // center of the image, in the units of the curves
+    pub position: Vec2,
+    // size of a pixel, in the units of the curves
+    pub scale: f32,
+    // counterclockwise, in degrees
+    pub rotation: f32,
+    pub opacity: f32,
+    pub visible: bool,
+    // the settings of a locked image cannot be edited in the panel
+    pub locked: bool,
+    #[serde(skip)]
+    pub texture: Option<Handle<Image>>,
+}
+
+impl Default for ReferenceImage {
+    fn default() -> Self {
+        ReferenceImage {
+            path: None,
+            position: Vec2::ZERO,
+            scale: 1.0,
+            rotation: 0.0,
+            opacity: 0.5,
+            visible: true,
+            locked: false,
+            texture: None,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ReferenceImageSprite;
+
+// keeps the sprite of the reference image in line with its settings and with globals.scale
+pub fn show_reference_image(
+    mut commands: Commands,
+    mut reference_image: ResMut<ReferenceImage>,
+    asset_server: Res<AssetServer>,
+    globals: Res<Globals>,
+    mut sprite_query: Query<
+        (
+            Entity,
+            &Handle<Image>,
+            &mut Sprite,
+            &mut Transform,
+            &mut Visibility,
+        ),
+        With<ReferenceImageSprite>,
+    >,
+) {
+    let path = match reference_image.path.clone() {
+        Some(path) => path,
+        None => {
+            for (entity, _, _, _, _) in sprite_query.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+            return;
+        }
+    };
+
+    // the texture is dropped whenever another image is picked or loaded with a document
+    let texture = match &reference_image.texture {
+        Some(texture) => texture.clone(),
+        None => {
+            let texture: Handle<Image> = asset_server.load(path.as_str());
+            reference_image.texture = Some(texture.clone());
+            texture
+        }
+    };
+
+    let color = Color::rgba(1.0, 1.0, 1.0, reference_image.opacity.clamp(0.0, 1.0));
+    let mut transform = Transform::from_translation(
+        (reference_image.position * globals.scale).extend(globals.z_pos.reference_image),
+    );
+    transform.rotation = Quat::from_rotation_z(reference_image.rotation.to_radians());
+    transform.scale = Vec3::new(
+        reference_image.scale * globals.scale,
+        reference_image.scale * globals.scale,
+        1.0,
+    );
+
+    let mut is_shown = false;
+    for (entity, handle, mut sprite, mut sprite_transform, mut visibility) in
+        sprite_query.iter_mut()
+    {
+        if handle != &texture || is_shown {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        sprite.color = color;
+        *sprite_transform = transform;
+        visibility.is_visible = reference_image.visible;
+        is_shown = true;
+    }
+
+    if !is_shown {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite { color, ..default() },
+                texture,
+                transform,
+                visibility: Visibility {
+                    is_visible: reference_image.visible,
+                },
+                ..default()
+            })
+            .insert(ReferenceImageSprite);
+    }
+}
@@ -0,0 +1,256 @@
+use crate::inputs::{Action, Cursor};
+use crate::model::*;
+
+use bevy::{
+    prelude::*,
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+};
+
+use std::collections::HashSet;
+
+/// Measurement mode, toggled with Left Shift + R. While it is active, two clicks on the canvas
+/// measure the distance between them, and the curve under the cursor shows the angle between
+/// its handles and its radius of curvature. The arc lengths of the selected curves and of their
+/// groups are shown as well. All the measurements are in the units of the curves, so they do
+/// not depend on `globals.scale`
+#[derive(Clone, Debug, Default)]
+pub struct Ruler {
+    pub active: bool,
+    pub start: Option<Vec2>,
+    pub end: Option<Vec2>,
+    // the end point follows the cursor until the second click
+    pub following: bool,
+    // text of the measurements of the current frame, with their positions on the canvas
+    pub labels: Vec<(Vec2, String)>,
+}
+
+impl Ruler {
+    // the first click starts a measurement, the second ends it and a third starts a new one
+    pub fn click(&mut self, position: Vec2) {
+        if self.start.is_some() && self.following {
+            self.end = Some(position);
+            self.following = false;
+        } else {
+            self.start = Some(position);
+            self.end = Some(position);
+            self.following = true;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.start = None;
+        self.end = None;
+        self.following = false;
+        self.labels.clear();
+    }
+}
+
+#[derive(Component)]
+pub struct RulerOverlay;
+
+pub fn toggle_ruler(mut action_event_reader: EventReader<Action>, mut ruler: ResMut<Ruler>) {
+    if action_event_reader
+        .iter()
+        .any(|x| x == &Action::ToggleRuler)
+    {
+        ruler.active = !ruler.active;
+        ruler.clear();
+        info!("ruler: {}", ruler.active);
+    }
+}
+
+// polyline of a circle, as segments
+fn circle_segments(center: Vec2, radius: f32, num_segments: usize) -> Vec<(Vec2, Vec2)> {
+    let point_at = |k: usize| {
+        let angle = k as f32 / num_segments as f32 * std::f32::consts::TAU;
+        center + Vec2::new(angle.cos(), angle.sin()) * radius
+    };
+    (0..num_segments)
+        .map(|k| (point_at(k), point_at(k + 1)))
+        .collect()
+}
+
+// computes the measurements every frame while the ruler is active, and redraws the overlay
+// when they change
+pub fn measure_with_ruler(
+    mut commands: Commands,
+    mut ruler: ResMut<Ruler>,
+    cursor: Res<Cursor>,
+    keyboard_input: Res<Input<KeyCode>>,
+    globals: Res<Globals>,
+    bezier_curves: Res<Assets<Bezier>>,
+    groups: Res<Assets<Group>>,
+    selection: Res<Selection>,
+    maps: Res<Maps>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+    mut overlay_query: Query<
+        (Entity, &Mesh2dHandle, &mut Transform, &mut Visibility),
+        With<RulerOverlay>,
+    >,
+    mut shown_overlay: Local<Option<(Vec<(Vec2, Vec2)>, f32)>>,
+) {
+    if !ruler.active {
+        for (entity, _, _, _) in overlay_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        *shown_overlay = None;
+        return;
+    }
+
+    let mut segments: Vec<(Vec2, Vec2)> = Vec::new();
+    let mut labels: Vec<(Vec2, String)> = Vec::new();
+
+    //
+    // distance between the two clicked points
+    if ruler.following {
+        let mut end = cursor.position;
+        if globals.snapping.is_active(&keyboard_input) {
+            let targets = globals
+                .snapping
+                .curve_targets(&bezier_curves, &HashSet::new());
+            end = globals.snapping.snap_point(end, &targets);
+        }
+        ruler.end = Some(end);
+    }
+
+    if let (Some(start), Some(end)) = (ruler.start, ruler.end) {
+        segments.push((start, end));
+        // ticks at both ends
+        let tick = (end - start).normalize_or_zero().perp() * 5.0;
+        segments.push((start - tick, start + tick));
+        segments.push((end - tick, end + tick));
+
+        labels.push(((start + end) / 2.0, format!("{:.1}", start.distance(end))));
+    }
+
+    //
+    // arc lengths of the selected curves and of their groups
+    let mut selected_ids = HashSet::new();
+    for choice in selection.selected.iter() {
+        if let SelectionChoice::CurveSet(ids) = choice {
+            selected_ids.extend(ids.iter().cloned());
+        }
+    }
+
+    let mut selected_length = 0.0;
+    let mut selected_groups = HashSet::new();
+    let mut label_position: Option<Vec2> = None;
+    for id in selected_ids.iter() {
+        if let Some(bezier) = maps
+            .bezier_map
+            .get(id)
+            .and_then(|handle_entity| bezier_curves.get(&handle_entity.handle))
+        {
+            selected_length += bezier.length();
+            selected_groups.insert(bezier.group);
+            label_position.get_or_insert(bezier.derivatives_at(0.5).0);
+        }
+    }
+
+    if let Some(position) = label_position {
+        labels.push((position, format!("arc {:.1}", selected_length)));
+
+        for group_id in selected_groups.iter() {
+            let group = match maps
+                .group_map
+                .get(group_id)
+                .and_then(|handle| groups.get(handle))
+            {
+                Some(group) if group.bezier_handles.len() > 1 => group,
+                _ => continue,
+            };
+
+            // the look-up table of the group may not be computed yet
+            let group_length = if group.standalone_lut.path_length > 0.0 {
+                group.standalone_lut.path_length
+            } else {
+                group
+                    .bezier_handles
+                    .iter()
+                    .filter_map(|handle| bezier_curves.get(handle))
+                    .map(|bezier| bezier.length())
+                    .sum()
+            };
+
+            labels.push((
+                position + Vec2::new(0.0, -15.0),
+                format!("group {:.1}", group_length),
+            ));
+        }
+    }
+
+    //
+    // angle between the handles and radius of curvature of the curve under the cursor
+    let closest = bezier_curves
+        .iter()
+        .map(|(_, bezier)| {
+            let (t, distance) = bezier.closest_t(cursor.position, 50);
+            (bezier, t, distance)
+        })
+        .filter(|(_, _, distance)| *distance < globals.anchor_clicking_dist * 2.0)
+        .min_by(|a, b| a.2.partial_cmp(&b.2).unwrap());
+
+    if let Some((bezier, t, _)) = closest {
+        let point = bezier.derivatives_at(t).0;
+        let mut text = Vec::new();
+
+        if let Some(angle) = bezier.handle_angle() {
+            text.push(format!("{:.1}°", angle));
+        }
+
+        match bezier.osculating_circle(t) {
+            // very large circles are just straight lines
+            Some((radius, center)) if radius < 10000.0 => {
+                segments.extend(circle_segments(center, radius, 64));
+                segments.push((point, center));
+                text.push(format!("r {:.1}", radius));
+            }
+            _ => text.push("r ∞".to_string()),
+        }
+
+        labels.push((point + Vec2::new(10.0, 10.0), text.join("  ")));
+    }
+
+    ruler.labels = labels;
+
+    //
+    // overlay, in the units of the curves
+    let wanted_overlay = (segments, globals.scale);
+    if shown_overlay.as_ref() == Some(&wanted_overlay) {
+        return;
+    }
+    let (segments, _) = shown_overlay.insert(wanted_overlay);
+
+    if segments.is_empty() {
+        for (_, _, _, mut visibility) in overlay_query.iter_mut() {
+            visibility.is_visible = false;
+        }
+        return;
+    }
+
+    let mesh = lines_mesh(segments, 0.5);
+    let scale = Vec3::new(globals.scale, globals.scale, 1.0);
+
+    if let Some((_, Mesh2dHandle(handle), mut transform, mut visibility)) =
+        overlay_query.iter_mut().next()
+    {
+        if let Some(overlay_mesh) = meshes.get_mut(handle) {
+            *overlay_mesh = mesh;
+        }
+        transform.scale = scale;
+        visibility.is_visible = true;
+    } else {
+        let mut transform = Transform::from_xyz(0.0, 0.0, globals.z_pos.ruler);
+        transform.scale = scale;
+
+        commands
+            .spawn_bundle(MaterialMesh2dBundle {
+                mesh: Mesh2dHandle(meshes.add(mesh)),
+                material: color_materials.add(ColorMaterial::from(Color::rgb(1.0, 0.85, 0.3))),
+                transform,
+                ..default()
+            })
+            .insert(RulerOverlay);
+    }
+}
@@ -0,0 +1,385 @@
+use crate::inputs::{Action, Cursor};
+use crate::model::*;
+
+use bevy::prelude::*;
+
+use rand::prelude::*;
+
+use std::collections::{HashMap, HashSet};
+use std::f32::consts::{FRAC_PI_2, PI};
+
+// distance of the control points of a quarter circle of radius 1, the standard cubic
+// approximation of a circle
+const KAPPA: f32 = 0.552_284_8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShapeKind {
+    Rectangle,
+    Ellipse,
+    Polygon,
+    Star,
+}
+
+/// Settings of the shape tools, which draw closed chains of latched curves by dragging a box
+/// on the canvas. Contained within [`Globals`]
+#[derive(Clone, Debug)]
+pub struct ShapeTool {
+    // shape drawn by dragging on the canvas, if any
+    pub kind: Option<ShapeKind>,
+    // radius of the rounded corners of rectangles. Zero for sharp corners
+    pub corner_radius: f32,
+    pub polygon_sides: usize,
+    pub star_points: usize,
+    // radius of the inner points of stars, relative to the outer points
+    pub star_inner_ratio: f32,
+}
+
+impl Default for ShapeTool {
+    fn default() -> Self {
+        ShapeTool {
+            kind: None,
+            corner_radius: 0.0,
+            polygon_sides: 6,
+            star_points: 5,
+            star_inner_ratio: 0.5,
+        }
+    }
+}
+
+// straight curve, with the control points on the line so that they stay off the anchors
+fn line(start: Vec2, end: Vec2) -> BezierPositions {
+    BezierPositions {
+        start,
+        end,
+        control_start: start + (end - start) / 3.0,
+        control_end: end - (end - start) / 3.0,
+    }
+}
+
+// quarter of an ellipse, given the tangents at both ends scaled by the radii
+fn quarter_arc(start: Vec2, start_tangent: Vec2, end: Vec2, end_tangent: Vec2) -> BezierPositions {
+    BezierPositions {
+        start,
+        end,
+        control_start: start + start_tangent * KAPPA,
+        control_end: end - end_tangent * KAPPA,
+    }
+}
+
+// closed polyline through the given points
+fn closed_polyline(points: &Vec<Vec2>) -> Vec<BezierPositions> {
+    (0..points.len())
+        .map(|k| line(points[k], points[(k + 1) % points.len()]))
+        .collect()
+}
+
+// points on an ellipse, evenly spaced in angle and starting from the top
+fn ellipse_points(center: Vec2, radii: Vec2, num_points: usize) -> Vec<Vec2> {
+    (0..num_points)
+        .map(|k| {
+            let angle = FRAC_PI_2 + 2.0 * PI * k as f32 / num_points as f32;
+            center + Vec2::new(angle.cos(), angle.sin()) * radii
+        })
+        .collect()
+}
+
+impl ShapeTool {
+    pub fn cycle(&mut self) {
+        self.kind = match self.kind {
+            None => Some(ShapeKind::Rectangle),
+            Some(ShapeKind::Rectangle) => Some(ShapeKind::Ellipse),
+            Some(ShapeKind::Ellipse) => Some(ShapeKind::Polygon),
+            Some(ShapeKind::Polygon) => Some(ShapeKind::Star),
+            Some(ShapeKind::Star) => None,
+        };
+    }
+
+    // curves of a shape fitting in the box between two corners, in counterclockwise order.
+    // The end of each curve is the start of the next one, and the last curve ends at the
+    // start of the first one
+    pub fn curves(&self, kind: ShapeKind, corner_a: Vec2, corner_b: Vec2) -> Vec<BezierPositions> {
+        let min = corner_a.min(corner_b);
+        let max = corner_a.max(corner_b);
+        let center = (min + max) / 2.0;
+        let radii = (max - min) / 2.0;
+
+        match kind {
+            ShapeKind::Rectangle => {
+                let radius = self.corner_radius.max(0.0).min(radii.min_element());
+                let directions = [Vec2::X, Vec2::Y, -Vec2::X, -Vec2::Y];
+                // corner k is at the end of the side going in directions[k]
+                let corners = [Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y), min];
+
+                let mut curves = Vec::new();
+                for k in 0..4 {
+                    let (side_direction, next_direction) = (directions[k], directions[(k + 1) % 4]);
+                    let side_start = corners[(k + 3) % 4] + side_direction * radius;
+                    let arc_start = corners[k] - side_direction * radius;
+                    let arc_end = corners[k] + next_direction * radius;
+
+                    // the sides vanish when the corners are as round as the rectangle is wide
+                    if side_start.distance(arc_start) > 0.01 {
+                        curves.push(line(side_start, arc_start));
+                    }
+                    if radius > 0.01 {
+                        curves.push(quarter_arc(
+                            arc_start,
+                            side_direction * radius,
+                            arc_end,
+                            next_direction * radius,
+                        ));
+                    }
+                }
+                curves
+            }
+
+            ShapeKind::Ellipse => (0..4)
+                .map(|k| {
+                    let angle_0 = FRAC_PI_2 * k as f32;
+                    let angle_1 = FRAC_PI_2 * (k + 1) as f32;
+                    let point = |angle: f32| center + Vec2::new(angle.cos(), angle.sin()) * radii;
+                    let tangent = |angle: f32| Vec2::new(-angle.sin(), angle.cos()) * radii;
+                    quarter_arc(
+                        point(angle_0),
+                        tangent(angle_0),
+                        point(angle_1),
+                        tangent(angle_1),
+                    )
+                })
+                .collect(),
+
+            ShapeKind::Polygon => {
+                closed_polyline(&ellipse_points(center, radii, self.polygon_sides.max(3)))
+            }
+
+            ShapeKind::Star => {
+                let num_points = self.star_points.max(3);
+                let outer = ellipse_points(center, radii, num_points * 2);
+                let points = outer
+                    .iter()
+                    .enumerate()
+                    .map(|(k, point)| {
+                        if k % 2 == 0 {
+                            *point
+                        } else {
+                            center + (*point - center) * self.star_inner_ratio
+                        }
+                    })
+                    .collect();
+                closed_polyline(&points)
+            }
+        }
+    }
+}
+
+// whether the control points on both sides of the join between two consecutive curves are
+// not mirrored, as at the corners of rectangles, polygons and stars
+fn is_corner(previous: &BezierPositions, next: &BezierPositions) -> bool {
+    let incoming = previous.end - previous.control_end;
+    let outgoing = next.control_start - next.start;
+    return incoming.distance(outgoing) > 0.01;
+}
+
+// gives ids to the curves of a closed chain and latches each curve to the next one
+pub fn shape_hists(curves: Vec<BezierPositions>) -> Vec<BezierHist> {
+    let mut rng = thread_rng();
+    let ids: Vec<BezierHistId> = curves.iter().map(|_| rng.gen()).collect();
+    let num_curves = curves.len();
+
+    // corners[k] is the join at the end of curve k
+    let corners: Vec<bool> = (0..num_curves)
+        .map(|k| is_corner(&curves[k], &curves[(k + 1) % num_curves]))
+        .collect();
+
+    curves
+        .into_iter()
+        .enumerate()
+        .map(|(k, positions)| {
+            let previous_id = ids[(k + num_curves - 1) % num_curves];
+            let next_id = ids[(k + 1) % num_curves];
+
+            let mut latches = HashMap::new();
+            latches.insert(
+                AnchorEdge::Start,
+                LatchData {
+                    latched_to_id: previous_id.into(),
+                    self_edge: AnchorEdge::Start,
+                    partners_edge: AnchorEdge::End,
+                    corner: corners[(k + num_curves - 1) % num_curves],
+                },
+            );
+            latches.insert(
+                AnchorEdge::End,
+                LatchData {
+                    latched_to_id: next_id.into(),
+                    self_edge: AnchorEdge::End,
+                    partners_edge: AnchorEdge::Start,
+                    corner: corners[k],
+                },
+            );
+
+            BezierHist {
+                latches,
+                ..BezierHist::new(positions, ids[k])
+            }
+        })
+        .collect()
+}
+
+pub fn toggle_shape_tool(
+    mut action_event_reader: EventReader<Action>,
+    mut globals: ResMut<Globals>,
+) {
+    if action_event_reader
+        .iter()
+        .any(|x| x == &Action::CycleShapeTool)
+    {
+        globals.shape_tool.cycle();
+        info!("shape tool: {:?}", globals.shape_tool.kind);
+    }
+}
+
+// turns the box dragged with a shape tool into a shape
+pub fn spawn_shape(
+    mut action_event_reader: EventReader<Action>,
+    mut spawning_groups_event_writer: EventWriter<SpawningGroups>,
+    cursor: Res<Cursor>,
+    keyboard_input: Res<Input<KeyCode>>,
+    globals: Res<Globals>,
+    bezier_curves: Res<Assets<Bezier>>,
+    mut visible_selecting_query: Query<&mut Visibility, With<SelectingBoxQuad>>,
+) {
+    if action_event_reader.iter().any(|x| x == &Action::SpawnShape) {
+        for mut visible in visible_selecting_query.iter_mut() {
+            visible.is_visible = false;
+        }
+
+        let kind = match globals.shape_tool.kind {
+            Some(kind) => kind,
+            None => return,
+        };
+
+        let mut corner_a = cursor.last_click_position;
+        let mut corner_b = cursor.position;
+        if globals.snapping.is_active(&keyboard_input) {
+            let targets = globals
+                .snapping
+                .curve_targets(&bezier_curves, &HashSet::new());
+            corner_a = globals.snapping.snap_point(corner_a, &targets);
+            corner_b = globals.snapping.snap_point(corner_b, &targets);
+        }
+
+        let size = (corner_b - corner_a).abs();
+        if size.x < 2.0 || size.y < 2.0 {
+            info!("shape too small: {:?}", size);
+            return;
+        }
+
+        let curves = globals.shape_tool.curves(kind, corner_a, corner_b);
+        spawning_groups_event_writer.send(SpawningGroups {
+            groups: vec![shape_hists(curves)],
+            do_send_to_history: true,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_closed_chain(curves: &Vec<BezierPositions>) {
+        for k in 0..curves.len() {
+            let next = &curves[(k + 1) % curves.len()];
+            assert!(
+                curves[k].end.distance(next.start) < 1e-4,
+                "curve {} ends at {:?}, the next one starts at {:?}",
+                k,
+                curves[k].end,
+                next.start
+            );
+        }
+    }
+
+    #[test]
+    fn shapes_are_closed_chains() {
+        let corner_a = Vec2::new(100.0, 50.0);
+        let corner_b = Vec2::new(-100.0, -50.0);
+        let rounded = ShapeTool {
+            corner_radius: 10.0,
+            ..ShapeTool::default()
+        };
+
+        let cases = [
+            (ShapeTool::default(), ShapeKind::Rectangle, 4),
+            (rounded, ShapeKind::Rectangle, 8),
+            (ShapeTool::default(), ShapeKind::Ellipse, 4),
+            (ShapeTool::default(), ShapeKind::Polygon, 6),
+            (ShapeTool::default(), ShapeKind::Star, 10),
+        ];
+        for (tool, kind, num_curves) in cases {
+            let curves = tool.curves(kind, corner_a, corner_b);
+            assert_eq!(curves.len(), num_curves, "{:?}", kind);
+            assert_closed_chain(&curves);
+        }
+    }
+
+    #[test]
+    fn fully_rounded_rectangles_have_no_sides() {
+        let tool = ShapeTool {
+            corner_radius: 1000.0,
+            ..ShapeTool::default()
+        };
+        // the radius is clamped to half of the height, so only the top and bottom sides remain
+        let curves = tool.curves(
+            ShapeKind::Rectangle,
+            Vec2::new(-100.0, -50.0),
+            Vec2::new(100.0, 50.0),
+        );
+        assert_eq!(curves.len(), 6);
+        assert_closed_chain(&curves);
+    }
+
+    #[test]
+    fn each_curve_is_latched_to_its_neighbours() {
+        let curves = ShapeTool::default().curves(
+            ShapeKind::Polygon,
+            Vec2::new(-50.0, -50.0),
+            Vec2::new(50.0, 50.0),
+        );
+        let hists = shape_hists(curves);
+        let num_curves = hists.len();
+
+        for (k, hist) in hists.iter().enumerate() {
+            let next_id = BezierId::from(hists[(k + 1) % num_curves].id);
+            let previous_id = BezierId::from(hists[(k + num_curves - 1) % num_curves].id);
+
+            let end = &hist.latches[&AnchorEdge::End];
+            assert_eq!(end.latched_to_id, next_id);
+            assert_eq!(end.partners_edge, AnchorEdge::Start);
+
+            let start = &hist.latches[&AnchorEdge::Start];
+            assert_eq!(start.latched_to_id, previous_id);
+            assert_eq!(start.partners_edge, AnchorEdge::End);
+        }
+    }
+
+    #[test]
+    fn corners_are_latched_as_corners() {
+        let corner_a = Vec2::new(-100.0, -50.0);
+        let corner_b = Vec2::new(100.0, 50.0);
+
+        // the sides of a rectangle meet at sharp corners
+        let rectangle =
+            shape_hists(ShapeTool::default().curves(ShapeKind::Rectangle, corner_a, corner_b));
+        for hist in rectangle.iter() {
+            assert!(hist.latches.values().all(|latch| latch.corner));
+        }
+
+        // the quarter arcs of an ellipse join smoothly
+        let ellipse =
+            shape_hists(ShapeTool::default().curves(ShapeKind::Ellipse, corner_a, corner_b));
+        for hist in ellipse.iter() {
+            assert!(hist.latches.values().all(|latch| !latch.corner));
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use crate::inputs::Action;
+use crate::mesh::{PenMesh, RoadBandMesh};
+use crate::model::*;
+
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        render_resource::PrimitiveTopology,
+    },
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+};
+
+use flo_curves::bezier::BezierCurve;
+
+use std::collections::HashSet;
+
+/// Snapping of anchors and control points while they are placed or dragged. Contained within
+/// [`Globals`]. Holding Left Alt disables snapping temporarily
+#[derive(Clone, Debug)]
+pub struct Snapping {
+    pub enabled: bool,
+    // snap anchors to the intersections of a grid
+    pub grid: bool,
+    pub grid_spacing: f32,
+    pub grid_origin: Vec2,
+    // draw the grid under the curves
+    pub show_grid: bool,
+    // snap the direction of control points to multiples of this angle, in degrees
+    pub angle_step: Option<f32>,
+    pub to_anchors: bool,
+    pub to_midpoints: bool,
+    pub to_mesh_vertices: bool,
+    // anchors, midpoints and mesh vertices attract within this distance, and take
+    // precedence over the grid
+    pub distance: f32,
+}
+
+impl Default for Snapping {
+    fn default() -> Self {
+        Snapping {
+            enabled: false,
+            grid: true,
+            grid_spacing: 20.0,
+            grid_origin: Vec2::ZERO,
+            show_grid: true,
+            angle_step: Some(15.0),
+            to_anchors: true,
+            to_midpoints: true,
+            to_mesh_vertices: true,
+            distance: 10.0,
+        }
+    }
+}
+
+impl Snapping {
+    pub fn is_active(&self, keyboard_input: &Input<KeyCode>) -> bool {
+        self.enabled && !keyboard_input.pressed(KeyCode::LAlt)
+    }
+
+    // moves a position onto the closest target within reach, or else onto the grid
+    pub fn snap_point(&self, position: Vec2, targets: &Vec<Vec2>) -> Vec2 {
+        let closest = targets
+            .iter()
+            .map(|target| (target.distance(position), *target))
+            .filter(|(distance, _)| *distance < self.distance)
+            .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        if let Some((_, target)) = closest {
+            return target;
+        }
+
+        if self.grid && self.grid_spacing > 0.0 {
+            let cell = ((position - self.grid_origin) / self.grid_spacing).round();
+            return self.grid_origin + cell * self.grid_spacing;
+        }
+
+        return position;
+    }
+
+    // turns a control point around its anchor to the closest multiple of the angle step,
+    // keeping the length of the handle
+    pub fn snap_handle(&self, anchor: Vec2, control: Vec2) -> Vec2 {
+        let handle = control - anchor;
+        let length = handle.length();
+        match self.angle_step {
+            Some(step) if step > 0.0 && length > 0.0 => {
+                let step = step.to_radians();
+                let angle = (handle.y.atan2(handle.x) / step).round() * step;
+                anchor + Vec2::new(angle.cos(), angle.sin()) * length
+            }
+            _ => control,
+        }
+    }
+
+    // anchors and midpoints of the curves, except for the excluded ones
+    pub fn curve_targets(
+        &self,
+        bezier_curves: &Assets<Bezier>,
+        excluded: &HashSet<BezierId>,
+    ) -> Vec<Vec2> {
+        let mut targets = Vec::new();
+        for (_, bezier) in bezier_curves.iter() {
+            if excluded.contains(&bezier.id) {
+                continue;
+            }
+            if self.to_anchors {
+                targets.push(bezier.positions.start);
+                targets.push(bezier.positions.end);
+            }
+            if self.to_midpoints {
+                let p = bezier.to_curve().point_at_pos(0.5);
+                targets.push(Vec2::new(p.0 as f32, p.1 as f32));
+            }
+        }
+        return targets;
+    }
+
+    // vertices of the fill, stroke and road meshes
+    pub fn mesh_targets(
+        &self,
+        meshes: &Assets<Mesh>,
+        mesh_query: &Query<(&Transform, Option<&Mesh2dHandle>, Option<&Children>), With<PenMesh>>,
+        band_query: &Query<&Mesh2dHandle, With<RoadBandMesh>>,
+    ) -> Vec<Vec2> {
+        let mut targets = Vec::new();
+        if !self.to_mesh_vertices {
+            return targets;
+        }
+
+        for (transform, mesh_handle, children) in mesh_query.iter() {
+            // roads have a child mesh per band
+            let mut handles = Vec::new();
+            if let Some(Mesh2dHandle(handle)) = mesh_handle {
+                handles.push(handle);
+            }
+            if let Some(children) = children {
+                for child in children.iter() {
+                    if let Ok(Mesh2dHandle(handle)) = band_query.get(*child) {
+                        handles.push(handle);
+                    }
+                }
+            }
+
+            // the vertices are relative to the center of mass of the mesh
+            let center_of_mass = transform.translation.truncate();
+            for handle in handles {
+                if let Some(VertexAttributeValues::Float32x3(positions)) = meshes
+                    .get(handle)
+                    .and_then(|mesh| mesh.attribute(Mesh::ATTRIBUTE_POSITION))
+                {
+                    targets.extend(
+                        positions
+                            .iter()
+                            .map(|p| center_of_mass + Vec2::new(p[0], p[1])),
+                    );
+                }
+            }
+        }
+        return targets;
+    }
+}
+
+#[derive(Component)]
+pub struct SnappingGrid;
+
+// line segments drawn as thin quads, for the canvas overlays
+pub fn lines_mesh(segments: &Vec<(Vec2, Vec2)>, half_thickness: f32) -> Mesh {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+
+    for (p0, p1) in segments.iter() {
+        let normal = (*p1 - *p0).normalize_or_zero().perp() * half_thickness;
+        let first = positions.len() as u32;
+        for p in [*p0 - normal, *p1 - normal, *p1 + normal, *p0 + normal] {
+            positions.push([p.x, p.y, 0.0]);
+        }
+        indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+
+    let normals = vec![[0.0, 0.0, 1.0]; positions.len()];
+    let uvs = vec![[0.0, 0.0]; positions.len()];
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    return mesh;
+}
+
+// lines of the grid, within a square of half size `extent` around the origin
+fn grid_mesh(snapping: &Snapping, extent: f32) -> Mesh {
+    let num_lines = (extent / snapping.grid_spacing).floor() as i32;
+    let origin = snapping.grid_origin;
+
+    let mut segments = Vec::new();
+    for k in -num_lines..=num_lines {
+        let offset = k as f32 * snapping.grid_spacing;
+        // vertical line
+        segments.push((
+            origin + Vec2::new(offset, -extent),
+            origin + Vec2::new(offset, extent),
+        ));
+        // horizontal line
+        segments.push((
+            origin + Vec2::new(-extent, offset),
+            origin + Vec2::new(extent, offset),
+        ));
+    }
+
+    return lines_mesh(&segments, 0.25);
+}
+
+// (re)spawns the grid overlay whenever the grid settings change
+pub fn spawn_snapping_grid(
+    mut commands: Commands,
+    globals: Res<Globals>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+    grid_query: Query<Entity, With<SnappingGrid>>,
+    mut shown_grid: Local<Option<(f32, Vec2, f32)>>,
+) {
+    let snapping = &globals.snapping;
+    let wanted_grid = if snapping.enabled && snapping.show_grid && snapping.grid_spacing > 0.0 {
+        Some((snapping.grid_spacing, snapping.grid_origin, globals.scale))
+    } else {
+        None
+    };
+
+    if *shown_grid == wanted_grid {
+        return;
+    }
+
+    for entity in grid_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    if wanted_grid.is_some() {
+        // the grid is in the same units as the curves, which are drawn at globals.scale
+        let mut transform = Transform::from_xyz(0.0, 0.0, globals.z_pos.grid);
+        transform.scale = Vec3::new(globals.scale, globals.scale, 1.0);
+
+        commands
+            .spawn_bundle(MaterialMesh2dBundle {
+                mesh: Mesh2dHandle(meshes.add(grid_mesh(snapping, 2000.0))),
+                material: color_materials
+                    .add(ColorMaterial::from(Color::rgba(1.0, 1.0, 1.0, 0.15))),
+                transform,
+                ..default()
+            })
+            .insert(SnappingGrid);
+    }
+
+    *shown_grid = wanted_grid;
+}
+
+pub fn toggle_snapping(mut action_event_reader: EventReader<Action>, mut globals: ResMut<Globals>) {
+    if action_event_reader
+        .iter()
+        .any(|x| x == &Action::ToggleSnapping)
+    {
+        globals.snapping.enabled = !globals.snapping.enabled;
+        info!("snapping: {}", globals.snapping.enabled);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!(a.distance(b) < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn points_snap_to_the_grid() {
+        let snapping = Snapping::default();
+        assert_close(
+            snapping.snap_point(Vec2::new(13.0, -27.0), &Vec::new()),
+            Vec2::new(20.0, -20.0),
+        );
+
+        let shifted = Snapping {
+            grid_origin: Vec2::new(5.0, 5.0),
+            ..Snapping::default()
+        };
+        assert_close(
+            shifted.snap_point(Vec2::new(13.0, -27.0), &Vec::new()),
+            Vec2::new(5.0, -35.0),
+        );
+    }
+
+    #[test]
+    fn targets_within_reach_take_precedence_over_the_grid() {
+        let snapping = Snapping::default();
+        let targets = vec![Vec2::new(11.0, 1.0), Vec2::new(30.0, 30.0)];
+        assert_close(
+            snapping.snap_point(Vec2::new(13.0, -2.0), &targets),
+            Vec2::new(11.0, 1.0),
+        );
+
+        // out of reach, the grid is used
+        assert_close(
+            snapping.snap_point(Vec2::new(45.0, 58.0), &targets),
+            Vec2::new(40.0, 60.0),
+        );
+    }
+
+    #[test]
+    fn points_stay_in_place_without_grid_or_targets() {
+        let snapping = Snapping {
+            grid: false,
+            ..Snapping::default()
+        };
+        let position = Vec2::new(13.0, -27.0);
+        assert_close(snapping.snap_point(position, &Vec::new()), position);
+    }
+
+    #[test]
+    fn handles_snap_to_the_angle_step_and_keep_their_length() {
+        let snapping = Snapping::default();
+        let anchor = Vec2::new(5.0, 5.0);
+        let control = anchor + Vec2::new(10.0, 2.0);
+
+        let snapped = snapping.snap_handle(anchor, control);
+        let angle = 15.0_f32.to_radians();
+        let length = Vec2::new(10.0, 2.0).length();
+        assert_close(
+            snapped,
+            anchor + Vec2::new(angle.cos(), angle.sin()) * length,
+        );
+
+        let free = Snapping {
+            angle_step: None,
+            ..Snapping::default()
+        };
+        assert_close(free.snap_handle(anchor, control), control);
+    }
+}
@@ -0,0 +1,638 @@
+use crate::inputs::Action;
+use crate::model::*;
+
+use bevy::{
+    math::Affine2,
+    prelude::*,
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+};
+
+use rand::prelude::*;
+
+use std::collections::{HashMap, HashSet};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SymmetryMode {
+    Off,
+    // mirror axis through the center
+    Vertical,
+    Horizontal,
+    // mirror axis through the center, at `Symmetry::angle`
+    Line,
+    // `Symmetry::radial_copies` rotated copies around the center, the original included
+    Radial,
+}
+
+/// Symmetry mode, cycled with Left Shift + S. Every curve spawned while it is active gets
+/// linked copies, mirrored across the axis or rotated around the center. Moving a curve or one
+/// of its copies moves all the others. Contained within [`Globals`]
+#[derive(Clone, Debug)]
+pub struct Symmetry {
+    pub mode: SymmetryMode,
+    pub center: Vec2,
+    // direction of the axis of SymmetryMode::Line, in degrees
+    pub angle: f32,
+    pub radial_copies: usize,
+    // the ends of a mirrored curve within this distance of the axis are moved onto it and
+    // latched to their twin
+    pub latch_distance: f32,
+    pub show_axis: bool,
+}
+
+impl Default for Symmetry {
+    fn default() -> Self {
+        Symmetry {
+            mode: SymmetryMode::Off,
+            center: Vec2::ZERO,
+            angle: 45.0,
+            radial_copies: 6,
+            latch_distance: 5.0,
+            show_axis: true,
+        }
+    }
+}
+
+impl Symmetry {
+    pub fn cycle(&mut self) {
+        self.mode = match self.mode {
+            SymmetryMode::Off => SymmetryMode::Vertical,
+            SymmetryMode::Vertical => SymmetryMode::Horizontal,
+            SymmetryMode::Horizontal => SymmetryMode::Line,
+            SymmetryMode::Line => SymmetryMode::Radial,
+            SymmetryMode::Radial => SymmetryMode::Off,
+        };
+    }
+
+    // point and unit direction of the mirror axis
+    pub fn axis(&self) -> Option<(Vec2, Vec2)> {
+        let angle: f32 = match self.mode {
+            SymmetryMode::Vertical => 90.0,
+            SymmetryMode::Horizontal => 0.0,
+            SymmetryMode::Line => self.angle,
+            _ => return None,
+        };
+        let angle = angle.to_radians();
+        Some((self.center, Vec2::new(angle.cos(), angle.sin())))
+    }
+
+    // transforms from the original curve to each of its copies, starting with the identity
+    // for the original itself
+    pub fn transforms(&self) -> Vec<Affine2> {
+        let around_center = |matrix: Mat2| {
+            Affine2::from_mat2_translation(matrix, self.center - matrix * self.center)
+        };
+
+        let mut transforms = vec![Affine2::IDENTITY];
+        if let Some((_, direction)) = self.axis() {
+            // reflection across the axis
+            let reflection = Mat2::from_cols(
+                Vec2::new(
+                    2.0 * direction.x * direction.x - 1.0,
+                    2.0 * direction.x * direction.y,
+                ),
+                Vec2::new(
+                    2.0 * direction.x * direction.y,
+                    2.0 * direction.y * direction.y - 1.0,
+                ),
+            );
+            transforms.push(around_center(reflection));
+        } else if self.mode == SymmetryMode::Radial {
+            for k in 1..self.radial_copies {
+                let angle = k as f32 / self.radial_copies as f32 * std::f32::consts::TAU;
+                transforms.push(around_center(Mat2::from_angle(angle)));
+            }
+        }
+        return transforms;
+    }
+}
+
+fn transform_positions(transform: &Affine2, positions: &BezierPositions) -> BezierPositions {
+    BezierPositions {
+        start: transform.transform_point2(positions.start),
+        end: transform.transform_point2(positions.end),
+        control_start: transform.transform_point2(positions.control_start),
+        control_end: transform.transform_point2(positions.control_end),
+    }
+}
+
+fn edge_position(positions: &BezierPositions, edge: AnchorEdge) -> Vec2 {
+    match edge {
+        AnchorEdge::Start => positions.start,
+        AnchorEdge::End => positions.end,
+    }
+}
+
+// moves an end of the curve along with its control point
+fn shift_edge(positions: &mut BezierPositions, edge: AnchorEdge, shift: Vec2) {
+    match edge {
+        AnchorEdge::Start => {
+            positions.start += shift;
+            positions.control_start += shift;
+        }
+        AnchorEdge::End => {
+            positions.end += shift;
+            positions.control_end += shift;
+        }
+    }
+}
+
+/// A curve and its copies. Each curve is its original, moved by the transform of the same
+/// index, so that the positions of any of them give the positions of all the others
+#[derive(Clone, Debug)]
+pub struct SymmetricCurves {
+    pub ids: Vec<BezierId>,
+    pub transforms: Vec<Affine2>,
+    // mirror axis, for the latching of the curve to its twin
+    pub axis: Option<(Vec2, Vec2)>,
+    // positions of the curves after the last update, to find out which one was moved
+    synced: Vec<Option<BezierPositions>>,
+}
+
+impl SymmetricCurves {
+    fn has_same_transforms(&self, other: &SymmetricCurves) -> bool {
+        self.transforms.len() == other.transforms.len()
+            && self
+                .transforms
+                .iter()
+                .zip(other.transforms.iter())
+                .all(|(a, b)| a.abs_diff_eq(*b, 1e-4))
+    }
+}
+
+/// Curves linked by the symmetry mode. The links stay after the mode is turned off, and
+/// after the curves are deleted, so that undoing the deletion links them again
+#[derive(Clone, Debug, Default)]
+pub struct SymmetryLinks {
+    pub sets: Vec<SymmetricCurves>,
+    // latches between copies are requested only once, so that the user can undo them
+    requested_latches: HashSet<(BezierId, AnchorEdge)>,
+}
+
+impl SymmetryLinks {
+    pub fn contains(&self, id: BezierId) -> bool {
+        self.sets.iter().any(|set| set.ids.contains(&id))
+    }
+}
+
+pub fn toggle_symmetry(mut action_event_reader: EventReader<Action>, mut globals: ResMut<Globals>) {
+    if action_event_reader
+        .iter()
+        .any(|x| x == &Action::CycleSymmetry)
+    {
+        globals.symmetry.cycle();
+        info!("symmetry: {:?}", globals.symmetry.mode);
+    }
+}
+
+// spawns the copies of the curves spawned by the user, as a single entry in the history
+pub fn spawn_symmetric_copies(
+    mut history_event_reader: EventReader<HistoryAction>,
+    globals: Res<Globals>,
+    mut links: ResMut<SymmetryLinks>,
+    mut spawn_groups_event_writer: EventWriter<SpawningGroups>,
+    mut symmetric_copies_event_writer: EventWriter<SpawnedSymmetricCopies>,
+) {
+    let transforms = globals.symmetry.transforms();
+    let mut rng = thread_rng();
+
+    for action in history_event_reader.iter() {
+        let spawned_groups = match action {
+            HistoryAction::SpawnedCurve { bezier_hist, .. } => vec![vec![bezier_hist.clone()]],
+            HistoryAction::SpawnedGroups { groups } => groups.clone(),
+            _ => continue,
+        };
+
+        if transforms.len() < 2 {
+            continue;
+        }
+
+        // the copies are spawned curves as well, and are not copied again
+        let spawned_groups: Vec<Vec<BezierHist>> = spawned_groups
+            .into_iter()
+            .map(|group| {
+                group
+                    .into_iter()
+                    .filter(|bezier_hist| !links.contains(bezier_hist.id.into()))
+                    .collect::<Vec<BezierHist>>()
+            })
+            .filter(|group| !group.is_empty())
+            .collect();
+
+        if spawned_groups.is_empty() {
+            continue;
+        }
+
+        // ids of the copies of each curve, one per transform
+        let copy_ids: HashMap<BezierId, Vec<BezierHistId>> = spawned_groups
+            .iter()
+            .flatten()
+            .map(|bezier_hist| {
+                let ids: Vec<BezierHistId> = transforms.iter().skip(1).map(|_| rng.gen()).collect();
+                (BezierId::from(bezier_hist.id), ids)
+            })
+            .collect();
+
+        let mut copies: Vec<Vec<BezierHist>> = Vec::new();
+        for (k, transform) in transforms.iter().enumerate().skip(1) {
+            for group in spawned_groups.iter() {
+                let copied_group = group
+                    .iter()
+                    .map(|bezier_hist| {
+                        // latches to curves spawned along with this one are copied right away,
+                        // the others once both copies exist
+                        let latches = bezier_hist
+                            .latches
+                            .iter()
+                            .filter_map(|(edge, latch)| {
+                                copy_ids.get(&latch.latched_to_id).map(|partner_ids| {
+                                    (
+                                        *edge,
+                                        LatchData {
+                                            latched_to_id: partner_ids[k - 1].into(),
+                                            ..latch.clone()
+                                        },
+                                    )
+                                })
+                            })
+                            .collect();
+
+                        BezierHist {
+                            positions: transform_positions(transform, &bezier_hist.positions),
+                            color: bezier_hist.color,
+                            latches,
+                            id: copy_ids[&BezierId::from(bezier_hist.id)][k - 1],
+                            do_send_to_history: false,
+                        }
+                    })
+                    .collect();
+                copies.push(copied_group);
+            }
+        }
+
+        for (id, ids) in copy_ids.iter() {
+            let mut set_ids = vec![*id];
+            set_ids.extend(ids.iter().map(|copy_id| BezierId::from(*copy_id)));
+
+            links.sets.push(SymmetricCurves {
+                ids: set_ids,
+                transforms: transforms.clone(),
+                axis: globals.symmetry.axis(),
+                synced: vec![None; transforms.len()],
+            });
+        }
+
+        // the copies are undone and redone along with the curves they are copied from
+        let original_id = spawned_groups[0][0].id;
+        symmetric_copies_event_writer.send(SpawnedSymmetricCopies {
+            original_id,
+            groups: copies.clone(),
+        });
+        spawn_groups_event_writer.send(SpawningGroups {
+            groups: copies,
+            do_send_to_history: false,
+        });
+    }
+}
+
+// handle, positions and latches of a spawned curve of a SymmetricCurves set
+type SpawnedBezier = (
+    Handle<Bezier>,
+    BezierPositions,
+    HashMap<AnchorEdge, LatchData>,
+);
+
+// moves the copies of a curve that was moved, and latches the copies to each other once the
+// user lets go of the anchors
+pub fn mirror_symmetric_curves(
+    mut commands: Commands,
+    mut links: ResMut<SymmetryLinks>,
+    mut bezier_curves: ResMut<Assets<Bezier>>,
+    maps: Res<Maps>,
+    globals: Res<Globals>,
+    moving_query: Query<&MovingAnchor>,
+    mut latch_event_writer: EventWriter<OfficialLatch>,
+) {
+    let is_idle = moving_query.iter().all(|moving| !moving.follow_mouse);
+    let latch_distance = globals.symmetry.latch_distance;
+
+    let SymmetryLinks {
+        sets,
+        requested_latches,
+    } = &mut *links;
+
+    let all_sets = sets.clone();
+
+    for set in sets.iter_mut() {
+        // the spawned curves of the set. The deleted ones keep their place, so that they are
+        // linked again when undoing or redoing brings them back. The copies may not be
+        // spawned yet either
+        let beziers: Vec<Option<SpawnedBezier>> = set
+            .ids
+            .iter()
+            .map(|id| {
+                let handle = maps.bezier_map.get(id)?.handle.clone();
+                let bezier = bezier_curves.get(&handle)?;
+                Some((handle, bezier.positions, bezier.latches.clone()))
+            })
+            .collect();
+
+        let spawned: Vec<usize> = (0..beziers.len())
+            .filter(|idx| beziers[*idx].is_some())
+            .collect();
+        for (idx, bezier) in beziers.iter().enumerate() {
+            if bezier.is_none() {
+                set.synced[idx] = None;
+            }
+        }
+        if spawned.len() < 2 {
+            continue;
+        }
+
+        // a curve that comes back follows the others instead of moving them
+        let moved = spawned.iter().copied().find(|idx| {
+            set.synced[*idx].is_some()
+                && set.synced[*idx] != beziers[*idx].as_ref().map(|bezier| bezier.1)
+        });
+        let is_synced = spawned.iter().all(|idx| set.synced[*idx].is_some());
+        if moved.is_none() && is_synced && !is_idle {
+            continue;
+        }
+        let driver = moved
+            .or_else(|| {
+                spawned
+                    .iter()
+                    .copied()
+                    .find(|idx| set.synced[*idx].is_some())
+            })
+            .unwrap_or(spawned[0]);
+        let driver_positions = match &beziers[driver] {
+            Some((_, positions, _)) => *positions,
+            None => continue,
+        };
+
+        let mut original =
+            transform_positions(&set.transforms[driver].inverse(), &driver_positions);
+        let mut new_latches: Vec<(usize, LatchData)> = Vec::new();
+
+        //
+        // the ends on the mirror axis stay on it, latched to their twin
+        if let (Some((point, direction)), [Some(first), Some(twin)]) =
+            (set.axis, beziers.as_slice())
+        {
+            for edge in [AnchorEdge::Start, AnchorEdge::End] {
+                let position = edge_position(&original, edge);
+                let on_axis = point + direction * (position - point).dot(direction);
+
+                let is_latched_to_twin = first
+                    .2
+                    .get(&edge)
+                    .map(|latch| latch.latched_to_id == set.ids[1] && latch.partners_edge == edge)
+                    .unwrap_or(false);
+
+                let do_latch = is_idle
+                    && position.distance(on_axis) < latch_distance
+                    && !first.2.contains_key(&edge)
+                    && !twin.2.contains_key(&edge)
+                    && !requested_latches.contains(&(set.ids[0], edge));
+
+                if is_latched_to_twin || do_latch {
+                    shift_edge(&mut original, edge, on_axis - position);
+                }
+
+                if do_latch {
+                    new_latches.push((
+                        0,
+                        LatchData {
+                            latched_to_id: set.ids[1],
+                            self_edge: edge,
+                            partners_edge: edge,
+                            corner: false,
+                        },
+                    ));
+                }
+            }
+        }
+
+        //
+        // the copies are latched to the copies of the curves their original is latched to
+        if let (true, Some((_, _, original_latches))) = (is_idle, &beziers[0]) {
+            for (edge, latch) in original_latches.iter() {
+                if set.ids.contains(&latch.latched_to_id) {
+                    continue;
+                }
+
+                let partner_set = match all_sets.iter().find(|other| {
+                    other.ids[0] == latch.latched_to_id && other.has_same_transforms(set)
+                }) {
+                    Some(partner_set) => partner_set,
+                    None => continue,
+                };
+
+                for idx in 1..set.ids.len() {
+                    let copy_latches = match &beziers[idx] {
+                        Some((_, _, copy_latches)) => copy_latches,
+                        None => continue,
+                    };
+                    let partner_id = partner_set.ids[idx];
+                    let is_partner_free = maps
+                        .bezier_map
+                        .get(&partner_id)
+                        .and_then(|handle_entity| bezier_curves.get(&handle_entity.handle))
+                        .map(|partner| !partner.latches.contains_key(&latch.partners_edge))
+                        .unwrap_or(false);
+
+                    if is_partner_free
+                        && !copy_latches.contains_key(edge)
+                        && !requested_latches.contains(&(set.ids[idx], *edge))
+                        && !requested_latches.contains(&(partner_id, latch.partners_edge))
+                    {
+                        new_latches.push((
+                            idx,
+                            LatchData {
+                                latched_to_id: partner_id,
+                                self_edge: *edge,
+                                partners_edge: latch.partners_edge,
+                                corner: latch.corner,
+                            },
+                        ));
+                    }
+                }
+            }
+        }
+
+        //
+        // positions of all the curves of the set
+        for (idx, bezier) in beziers.iter().enumerate() {
+            let (handle, previous_positions) = match bezier {
+                Some((handle, positions, _)) => (handle, *positions),
+                None => continue,
+            };
+            let positions = transform_positions(&set.transforms[idx], &original);
+
+            if positions != previous_positions {
+                if let Some(bezier) = bezier_curves.get_mut(handle) {
+                    bezier.positions = positions;
+                    bezier.do_compute_lut = true;
+                }
+
+                // the quads of the moved curve follow it already
+                if idx != driver {
+                    for entity in maps.bezier_map[&set.ids[idx]].anchor_entities.values() {
+                        let is_following_mouse = moving_query
+                            .get(*entity)
+                            .map(|moving| moving.follow_mouse)
+                            .unwrap_or(false);
+
+                        if !is_following_mouse {
+                            commands.entity(*entity).insert(MovingAnchor {
+                                once: true,
+                                follow_mouse: false,
+                            });
+                        }
+                    }
+                }
+            }
+
+            set.synced[idx] = Some(positions);
+        }
+
+        for (idx, latch) in new_latches {
+            requested_latches.insert((set.ids[idx], latch.self_edge));
+            requested_latches.insert((latch.latched_to_id, latch.partners_edge));
+            if let Some((handle, _, _)) = &beziers[idx] {
+                latch_event_writer.send(OfficialLatch(latch, handle.clone()));
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct SymmetryAxisOverlay;
+
+// (re)spawns the mirror axis, or the rays between the radial copies, whenever they change
+pub fn show_symmetry_axis(
+    mut commands: Commands,
+    globals: Res<Globals>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+    overlay_query: Query<Entity, With<SymmetryAxisOverlay>>,
+    mut shown_axis: Local<Option<(Vec<(Vec2, Vec2)>, f32)>>,
+) {
+    let symmetry = &globals.symmetry;
+    let extent = 2000.0;
+
+    let mut segments = Vec::new();
+    if symmetry.show_axis {
+        if let Some((point, direction)) = symmetry.axis() {
+            segments.push((point - direction * extent, point + direction * extent));
+        } else if symmetry.mode == SymmetryMode::Radial {
+            for k in 0..symmetry.radial_copies {
+                // halfway between two copies
+                let angle =
+                    (k as f32 + 0.5) / symmetry.radial_copies as f32 * std::f32::consts::TAU;
+                let direction = Vec2::new(angle.cos(), angle.sin());
+                segments.push((symmetry.center, symmetry.center + direction * extent));
+            }
+        }
+    }
+
+    let wanted_axis = if segments.is_empty() {
+        None
+    } else {
+        Some((segments, globals.scale))
+    };
+
+    if *shown_axis == wanted_axis {
+        return;
+    }
+
+    for entity in overlay_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    if let Some((segments, scale)) = &wanted_axis {
+        // the axis is in the same units as the curves, which are drawn at globals.scale
+        let mut transform = Transform::from_xyz(0.0, 0.0, globals.z_pos.symmetry_axis);
+        transform.scale = Vec3::new(*scale, *scale, 1.0);
+
+        commands
+            .spawn_bundle(MaterialMesh2dBundle {
+                mesh: Mesh2dHandle(meshes.add(lines_mesh(segments, 0.5))),
+                material: color_materials.add(ColorMaterial::from(Color::rgba(0.3, 0.8, 1.0, 0.4))),
+                transform,
+                ..default()
+            })
+            .insert(SymmetryAxisOverlay);
+    }
+
+    *shown_axis = wanted_axis;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!(a.distance(b) < 1e-3, "{:?} != {:?}", a, b);
+    }
+
+    fn symmetry(mode: SymmetryMode) -> Symmetry {
+        Symmetry {
+            mode,
+            center: Vec2::new(30.0, -10.0),
+            angle: 30.0,
+            ..Symmetry::default()
+        }
+    }
+
+    #[test]
+    fn only_the_original_is_kept_when_the_mode_is_off() {
+        let transforms = symmetry(SymmetryMode::Off).transforms();
+        assert_eq!(transforms, vec![Affine2::IDENTITY]);
+    }
+
+    #[test]
+    fn reflections_are_involutions_that_fix_their_axis() {
+        let point = Vec2::new(-12.0, 47.0);
+        for mode in [
+            SymmetryMode::Vertical,
+            SymmetryMode::Horizontal,
+            SymmetryMode::Line,
+        ] {
+            let symmetry = symmetry(mode);
+            let transforms = symmetry.transforms();
+            assert_eq!(transforms.len(), 2, "{:?}", mode);
+            assert_eq!(transforms[0], Affine2::IDENTITY);
+
+            let reflection = transforms[1];
+            let reflected = reflection.transform_point2(point);
+            assert!(reflected.distance(point) > 1.0, "{:?}", mode);
+            assert_close(reflection.transform_point2(reflected), point);
+
+            let (axis_point, direction) = symmetry.axis().unwrap();
+            let on_axis = axis_point + direction * 25.0;
+            assert_close(reflection.transform_point2(on_axis), on_axis);
+        }
+    }
+
+    #[test]
+    fn radial_copies_are_evenly_rotated_around_the_center() {
+        let symmetry = symmetry(SymmetryMode::Radial);
+        let transforms = symmetry.transforms();
+        assert_eq!(transforms.len(), symmetry.radial_copies);
+
+        let point = Vec2::new(-12.0, 47.0);
+        let radius = point.distance(symmetry.center);
+        for transform in transforms.iter() {
+            let copy = transform.transform_point2(point);
+            assert!((copy.distance(symmetry.center) - radius).abs() < 1e-3);
+        }
+
+        // a full turn brings the curve back onto itself
+        let mut turned = point;
+        for _ in 0..symmetry.radial_copies {
+            turned = transforms[1].transform_point2(turned);
+        }
+        assert_close(turned, point);
+    }
+}
@@ -3,6 +3,9 @@ use crate::materials::*;
 use crate::mesh::*;
 use crate::model::bezier::*;
 use crate::model::group::*;
+use crate::model::shapes::*;
+use crate::model::snapping::*;
+use crate::model::symmetry::*;
 
 use bevy::{asset::HandleId, prelude::*, sprite::Mesh2dHandle, utils::Uuid};
 
@@ -15,6 +18,8 @@ use std::collections::HashSet;
 
 use bevy_inspector_egui::Inspectable;
 
+use bevy_pen_tool_runtime::LutFormat;
+
 #[derive(Component)]
 pub struct CurrentlySelecting;
 
@@ -23,12 +28,6 @@ pub struct OnOffMaterial {
     pub material: Handle<Image>,
 }
 
-#[derive(Component)]
-pub struct FollowBezierAnimation {
-    pub animation_offset: f64,
-    pub initial_direction: Vec3,
-}
-
 // helicopter animation
 #[derive(Component)]
 pub struct TurnRoundAnimation;
@@ -48,6 +47,10 @@ pub struct SelectedBoxQuad;
 #[derive(Component)]
 pub struct SelectingBoxQuad;
 
+// marks the selected anchors and control points
+#[derive(Component)]
+pub struct SelectedAnchorsOverlay;
+
 #[derive(Component)]
 pub struct GroupBoxQuad;
 
@@ -58,6 +61,8 @@ pub struct Maps {
     pub bezier_map: HashMap<BezierId, BezierHandleEntity>,
     pub group_map: HashMap<GroupId, Handle<Group>>,
     pub mesh_map: HashMap<MeshId, Entity>,
+    // deleted meshes are hidden and kept here, so that the deletion can be undone
+    pub deleted_meshes: HashMap<MeshId, (Entity, PenMesh)>,
     pub sounds: HashMap<&'static str, Handle<AudioSource>>,
     pub textures: HashMap<&'static str, Handle<Image>>,
 }
@@ -90,6 +95,7 @@ impl Default for Maps {
             mesh_handles: HashMap::new(),
             // pipeline_handles: HashMap::new(),
             mesh_map: HashMap::new(),
+            deleted_meshes: HashMap::new(),
             bezier_map: HashMap::new(),
             group_map: HashMap::new(),
             sounds: HashMap::new(),
@@ -101,7 +107,8 @@ impl Default for Maps {
 #[derive(Clone, Debug)]
 pub enum SelectionChoice {
     CurveSet(HashSet<BezierId>),
-    Mesh(PenMesh, Vec2), // Vec2 is translation
+    Mesh(PenMesh, Vec2),                  // Vec2 is translation
+    Anchors(HashSet<(BezierId, Anchor)>), // anchors and control points, moved together
     None,
 }
 
@@ -117,6 +124,59 @@ impl Default for Selection {
     }
 }
 
+impl Selection {
+    // ids of the selected curves, over all the curve sets
+    pub fn curve_ids(&self) -> HashSet<BezierId> {
+        let mut ids = HashSet::new();
+        for selected in self.selected.iter() {
+            if let SelectionChoice::CurveSet(curve_set) = selected {
+                ids.extend(curve_set.iter().cloned());
+            }
+        }
+        ids
+    }
+
+    pub fn anchors(&self) -> HashSet<(BezierId, Anchor)> {
+        let mut anchors = HashSet::new();
+        for selected in self.selected.iter() {
+            if let SelectionChoice::Anchors(anchor_set) = selected {
+                anchors.extend(anchor_set.iter().cloned());
+            }
+        }
+        anchors
+    }
+
+    // adds a curve to the selection, or removes it if it was already selected
+    pub fn toggle_curve(&mut self, bezier_id: BezierId) {
+        let mut curve_ids = self.curve_ids();
+        if !curve_ids.remove(&bezier_id) {
+            curve_ids.insert(bezier_id);
+        }
+        self.selected
+            .retain(|selected| !matches!(selected, SelectionChoice::CurveSet(_)));
+        if !curve_ids.is_empty() {
+            self.selected.push(SelectionChoice::CurveSet(curve_ids));
+        }
+    }
+
+    // adds an anchor or a control point to the selection, or removes it
+    pub fn toggle_anchor(&mut self, bezier_id: BezierId, anchor: Anchor) {
+        let mut anchors = self.anchors();
+        if !anchors.remove(&(bezier_id, anchor)) {
+            anchors.insert((bezier_id, anchor));
+        }
+        self.set_anchors(anchors);
+    }
+
+    pub fn set_anchors(&mut self, anchors: HashSet<(BezierId, Anchor)>) {
+        self.selected
+            .retain(|selected| !matches!(selected, SelectionChoice::Anchors(_)));
+        if !anchors.is_empty() {
+            self.selected.push(SelectionChoice::Anchors(anchors));
+        }
+    }
+}
+
 /// Holds Z position information. Important for drawing order. Contained within [`Globals`].
 #[derive(Clone, Debug)]
 pub struct ZPos {
@@ -127,11 +187,19 @@ pub struct ZPos {
     pub group_parent: f32,
     pub group_bouding_box: f32,
     pub group_middles: f32,
+    pub path_markers: f32,
+    pub width_keyframes: f32,
     pub selecting_box: f32,
     pub selection_box: f32,
     pub bounding_box: f32,
     pub road: f32,
     pub fill: f32,
+    pub stroke: f32,
+    pub grid: f32,
+    pub ruler: f32,
+    pub symmetry_axis: f32,
+    pub reference_image: f32,
+    pub selected_anchors: f32,
     pub heli: f32,
     pub heli_top: f32,
     pub ui_board: f32,
@@ -152,11 +220,20 @@ impl Default for ZPos {
             group_parent: 0.33,
             group_bouding_box: 0.33,
             group_middles: 0.33,
+            path_markers: 0.34,
+            width_keyframes: 0.34,
             selecting_box: 0.33,
             selection_box: 0.53,
             bounding_box: 0.33,
             road: 0.35,
             fill: 0.33,
+            stroke: 0.34,
+            grid: 0.3,
+            ruler: 0.45,
+            symmetry_axis: 0.31,
+            // below everything else
+            reference_image: 0.0,
+            selected_anchors: 0.46,
             heli: 0.4,
             heli_top: 0.01,
             ui_board: 0.33,
@@ -180,8 +257,28 @@ pub struct Globals {
     pub sound_on: bool,
     pub hide_control_points: bool,
     pub group_lut_num_points: u32,
+    // half width of the road made by RoadProfile::single_lane
     pub road_width: f32,
+    // bands of the roads made with the road button
+    pub road_profile: RoadProfile,
+    // width, joins, caps and dashes of the stroke meshes
+    pub stroke_style: StrokeStyle,
+    // how overlapping contours of fill meshes are filled
+    pub fill_rule: FillRule,
+    // maximum distance between the curves and the edges of fill meshes, in world units.
+    // Independent from the look-up tables used for animations
+    pub fill_tolerance: f32,
     pub anchor_clicking_dist: f32,
+    // grid, angle and point snapping of the anchors and control points
+    pub snapping: Snapping,
+    // kind and proportions of the shapes drawn with the shape tools
+    pub shape_tool: ShapeTool,
+    // mirror axis or radial copies of the curves spawned while the symmetry mode is on
+    pub symmetry: Symmetry,
+    // encoding of the exported look-up tables (.lut files)
+    pub lut_format: LutFormat,
+    // whether the exported look-up tables include the tangents of the path
+    pub lut_tangents: bool,
     pub z_pos: ZPos,
 }
 
@@ -198,7 +295,16 @@ impl Default for Globals {
             num_points_on_curve: 25,
             group_lut_num_points: 100,
             road_width: 8.0,
+            road_profile: RoadProfile::default(),
+            stroke_style: StrokeStyle::default(),
+            fill_rule: FillRule::EvenOdd,
+            fill_tolerance: 0.05,
             anchor_clicking_dist: 12.0,
+            snapping: Snapping::default(),
+            shape_tool: ShapeTool::default(),
+            symmetry: Symmetry::default(),
+            lut_format: LutFormat::Json,
+            lut_tangents: false,
             z_pos: ZPos::default(),
         }
     }
@@ -317,9 +423,18 @@ pub fn update_latched_partner_position(
             //
             let bezier_partner = bezier_curves.get_mut(&bezier_handle.handle).unwrap();
 
+            let partner_anchor = partner_latch.partners_edge.to_anchor();
+            let partner_control = if partner_latch.corner {
+                // at a corner, the partner's control point is carried along by its anchor
+                let shift = mover_position - bezier_partner.get_position(partner_anchor);
+                bezier_partner.get_position(partner_anchor.adjoint()) + shift
+            } else {
+                opposite_control
+            };
+
             bezier_partner.update_latched_position(
                 partner_latch.partners_edge,
-                opposite_control,
+                partner_control,
                 mover_position,
             );
         } else {
@@ -596,6 +711,19 @@ pub fn adjust_selection_attributes(
                     }
                 }
             }
+            SelectionChoice::Anchors(anchors) => {
+                for (bezier_id, anchor) in anchors.iter() {
+                    if let Some(bezier_entity_handle) = maps.bezier_map.get(bezier_id) {
+                        if let Some(bezier) = bezier_curves.get(&bezier_entity_handle.handle) {
+                            let position = bezier.get_position(*anchor);
+                            minx = minx.min(position.x);
+                            maxx = maxx.max(position.x);
+                            miny = miny.min(position.y);
+                            maxy = maxy.max(position.y);
+                        }
+                    }
+                }
+            }
             SelectionChoice::Mesh(pen_mesh, translation) => {
                 minx = minx.min(pen_mesh.bounding_box.0.x + translation.x);
                 maxx = maxx.max(pen_mesh.bounding_box.1.x + translation.x);
@@ -5,7 +5,7 @@ use crate::materials::{BezierControlsMat, BezierEndsMat, SelectionMat};
 use crate::model::{
     AchorEdgeQuad, Anchor, AnchorEdge, Bezier, BezierHandleEntity, BezierHist, BezierId,
     BezierParent, BezierPositions, BoundingBoxQuad, ControlPointQuad, Globals, Group, GroupId,
-    HistoryAction, LatchData, MainUi, Maps, MovingAnchor, SpawningCurve,
+    HistoryAction, LatchData, MainUi, Maps, MovingAnchor, SpawningCurve, SpawningGroups,
 };
 
 use bevy::{asset::HandleId, prelude::*, sprite::MaterialMesh2dBundle};
@@ -91,6 +91,7 @@ pub fn spawn_bezier_system(
                 latched_to_id: latch_received.latchee_id,
                 self_edge: AnchorEdge::Start,
                 partners_edge: latch_received.latchee_edge,
+                corner: false,
             };
 
             latches.insert(AnchorEdge::Start, latch_local);
@@ -200,6 +201,90 @@ pub fn spawn_bezier_system(
     }
 }
 
+// spawns curves that are already latched to each other, and puts each list of curves in a
+// new group
+pub fn spawn_groups_system(
+    mut bezier_curves: ResMut<Assets<Bezier>>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut selection_params: ResMut<Assets<SelectionMat>>,
+    mut controls_params: ResMut<Assets<BezierControlsMat>>,
+    mut ends_params: ResMut<Assets<BezierEndsMat>>,
+    clearcolor_struct: Res<ClearColor>,
+    mut globals: ResMut<Globals>,
+    mut maps: ResMut<Maps>,
+    mut add_to_history_event_writer: EventWriter<HistoryAction>,
+    mut spawn_groups_event_reader: EventReader<SpawningGroups>,
+    mut groups: ResMut<Assets<Group>>,
+    mut group_event_writer: EventWriter<Handle<Group>>,
+) {
+    for SpawningGroups {
+        groups: bezier_hist_groups,
+        do_send_to_history,
+    } in spawn_groups_event_reader.iter()
+    {
+        for bezier_hists in bezier_hist_groups.iter() {
+            let group_id = GroupId::default();
+            let mut group = Group::default();
+            group.id = group_id;
+
+            for bezier_hist in bezier_hists.iter() {
+                let mut bezier = Bezier {
+                    positions: bezier_hist.positions.clone(),
+                    latches: bezier_hist.latches.clone(),
+                    color: bezier_hist.color.clone(),
+                    id: bezier_hist.id.into(),
+                    group: group_id,
+                    do_compute_lut: true,
+                    ..Default::default()
+                };
+                bezier.update_previous_pos();
+
+                let (entity, handle) = spawn_bezier(
+                    &mut bezier,
+                    &mut bezier_curves,
+                    &mut commands,
+                    &mut meshes,
+                    &mut selection_params,
+                    &mut controls_params,
+                    &mut ends_params,
+                    clearcolor_struct.0,
+                    &mut globals,
+                    &mut maps,
+                    &mut add_to_history_event_writer,
+                    &Some(bezier_hist.id.into()),
+                    false, // do not send to history
+                    false, // do not follow mouse
+                );
+
+                group.add_curve(entity, handle);
+            }
+
+            let bezier_assets = bezier_curves
+                .iter()
+                .collect::<HashMap<bevy::asset::HandleId, &Bezier>>();
+
+            group.find_connected_ends(&bezier_assets, maps.bezier_map.clone());
+            group.group_lut(&bezier_assets, maps.bezier_map.clone());
+            group.compute_standalone_lut(&bezier_assets, globals.group_lut_num_points);
+
+            let mut group_handle: Handle<Group> = bevy::asset::Handle::weak(group.id.0);
+            group_handle.make_strong(&groups);
+            let strong_handle = groups.set(group_handle, group);
+
+            maps.group_map.insert(group_id, strong_handle.clone());
+            group_event_writer.send(strong_handle);
+        }
+
+        // the curves go into the history as a whole, rather than curve by curve
+        if *do_send_to_history {
+            add_to_history_event_writer.send(HistoryAction::SpawnedGroups {
+                groups: bezier_hist_groups.clone(),
+            });
+        }
+    }
+}
+
 pub fn spawn_bezier(
     mut bezier: &mut Bezier,
     bezier_curves: &mut ResMut<Assets<Bezier>>,
@@ -330,6 +415,7 @@ pub fn spawn_bezier(
             // bezier_handle: bezier_handle.clone(),
             bezier_id: bezier.id.into(),
             bezier_hist: BezierHist::from(&bezier.clone()),
+            copies: Vec::new(),
             // entity: parent,
             // id: bezier.id,
         });
@@ -1,8 +1,10 @@
 use crate::model::{
-    Bezier, FollowBezierAnimation, Globals, Group, GroupBoxQuad, GroupMiddleQuad, GroupParent,
-    Maps, SelectedBoxQuad, SelectingBoxQuad, TurnRoundAnimation,
+    Bezier, Globals, Group, GroupBoxQuad, GroupHandleKind, GroupMiddleQuad, GroupParent, Maps,
+    PathMarkerQuad, SelectedBoxQuad, SelectingBoxQuad, TurnRoundAnimation, WidthKeyframeQuad,
 };
 
+use bevy_pen_tool_runtime::{OrientationMode, PathFollower};
+
 use crate::materials::{BezierMidMat, SelectingMat, SelectionMat};
 
 use crate::inputs::Action;
@@ -212,6 +214,149 @@ pub fn spawn_group_entities(
     }
 }
 
+// (re)spawns the quads showing the markers of a group whenever markers are added or removed
+pub fn spawn_path_marker_quads(
+    mut commands: Commands,
+    groups: Res<Assets<Group>>,
+    bezier_curves: Res<Assets<Bezier>>,
+    marker_query: Query<(Entity, &Handle<Group>), With<PathMarkerQuad>>,
+    parent_query: Query<Entity, With<GroupParent>>,
+    mut mids_shader_params: ResMut<Assets<BezierMidMat>>,
+    clearcolor_struct: Res<ClearColor>,
+    globals: Res<Globals>,
+    maps: Res<Maps>,
+) {
+    let quads = marker_query
+        .iter()
+        .map(|(entity, group_handle)| (entity, group_handle.id))
+        .collect::<Vec<_>>();
+    spawn_group_handle_quads(
+        GroupHandleKind::PathMarker,
+        &quads,
+        &mut commands,
+        &groups,
+        &bezier_curves,
+        &parent_query,
+        &mut mids_shader_params,
+        clearcolor_struct.0,
+        &globals,
+        &maps,
+    );
+}
+
+// (re)spawns the handles showing the width keyframes of a group whenever keyframes are added
+// or removed. Each handle sits on the left edge of the road, at the keyframe width
+pub fn spawn_width_keyframe_quads(
+    mut commands: Commands,
+    groups: Res<Assets<Group>>,
+    bezier_curves: Res<Assets<Bezier>>,
+    keyframe_query: Query<(Entity, &Handle<Group>), With<WidthKeyframeQuad>>,
+    parent_query: Query<Entity, With<GroupParent>>,
+    mut mids_shader_params: ResMut<Assets<BezierMidMat>>,
+    clearcolor_struct: Res<ClearColor>,
+    globals: Res<Globals>,
+    maps: Res<Maps>,
+) {
+    let quads = keyframe_query
+        .iter()
+        .map(|(entity, group_handle)| (entity, group_handle.id))
+        .collect::<Vec<_>>();
+    spawn_group_handle_quads(
+        GroupHandleKind::WidthKeyframe,
+        &quads,
+        &mut commands,
+        &groups,
+        &bezier_curves,
+        &parent_query,
+        &mut mids_shader_params,
+        clearcolor_struct.0,
+        &globals,
+        &maps,
+    );
+}
+
+// respawns the handle quads of a kind for the groups whose number of quads does not match
+// their number of markers or keyframes. quads are the existing handle quads of that kind
+fn spawn_group_handle_quads(
+    kind: GroupHandleKind,
+    quads: &[(Entity, bevy::asset::HandleId)],
+    commands: &mut Commands,
+    groups: &Assets<Group>,
+    bezier_curves: &Assets<Bezier>,
+    parent_query: &Query<Entity, With<GroupParent>>,
+    mids_shader_params: &mut Assets<BezierMidMat>,
+    clearcolor: Color,
+    globals: &Globals,
+    maps: &Maps,
+) {
+    let bezier_assets = bezier_curves
+        .iter()
+        .collect::<HashMap<bevy::asset::HandleId, &Bezier>>();
+
+    let (color, z) = match kind {
+        GroupHandleKind::PathMarker => (Color::WHITE, globals.z_pos.path_markers),
+        GroupHandleKind::WidthKeyframe => (Color::ORANGE, globals.z_pos.width_keyframes),
+    };
+
+    for (group_handle_id, group) in groups.iter() {
+        if let Some(parent_id) = group.entity {
+            // the group parent may have been despawned during this frame
+            if parent_query.get(parent_id).is_err() {
+                continue;
+            }
+
+            let group_quads = quads
+                .iter()
+                .filter(|(_, quad_group_id)| *quad_group_id == group_handle_id)
+                .map(|(entity, _)| *entity)
+                .collect::<Vec<Entity>>();
+
+            let count = group.handle_count(kind);
+            if group_quads.len() == count {
+                continue;
+            }
+
+            for entity in group_quads {
+                commands.entity(entity).despawn_recursive();
+            }
+
+            let handle_mesh_handle = maps.mesh_handles["path_marker"].clone();
+
+            for idx in 0..count {
+                let pos = group
+                    .handle_position(&bezier_assets, kind, idx)
+                    .unwrap_or_default();
+
+                let handle_shader_params_handle = mids_shader_params.add(BezierMidMat {
+                    color: color.into(),
+                    t: 0.5,
+                    zoom: 0.15 / globals.scale,
+                    size: Vec2::new(1.0, 1.0),
+                    clearcolor: clearcolor.into(),
+                    ..Default::default()
+                });
+
+                let mut child = commands.spawn_bundle(MaterialMesh2dBundle {
+                    mesh: handle_mesh_handle.clone(),
+                    visibility: Visibility { is_visible: true },
+                    transform: Transform::from_xyz(pos.x, pos.y, z),
+                    material: handle_shader_params_handle,
+                    ..Default::default()
+                });
+                match kind {
+                    GroupHandleKind::PathMarker => {
+                        child.insert(PathMarkerQuad(group.marker_ids[idx]))
+                    }
+                    GroupHandleKind::WidthKeyframe => child.insert(WidthKeyframeQuad(idx)),
+                };
+                let child = child.insert(Handle::<Group>::weak(group_handle_id)).id();
+
+                commands.entity(parent_id).push_children(&[child]);
+            }
+        }
+    }
+}
+
 pub fn spawn_heli(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
@@ -241,9 +386,14 @@ pub fn spawn_heli(
                     visibility: Visibility { is_visible: true },
                     ..Default::default()
                 })
-                .insert(FollowBezierAnimation {
+                .insert(PathFollower {
                     animation_offset: -0.1,
                     initial_direction: Vec3::X,
+                    orientation: OrientationMode::Smoothed {
+                        max_angular_speed: std::f32::consts::PI,
+                    },
+                    lane_offset: 4.0,
+                    ..Default::default()
                 })
                 .id();
             let copter_handle = asset_server.load("textures/copter.png");
@@ -0,0 +1,112 @@
+use bevy_pen_tool_model::model::*;
+use bevy_pen_tool_plugin::{pen::*, BevyPenToolPlugin, Bezier};
+
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+pub struct TargetPositions(pub HashMap<BezierId, BezierPositions>);
+
+fn main() {
+    let mut app = App::new();
+    app.insert_resource(BezierTestHashed(HashMap::new()))
+        .add_plugins(DefaultPlugins)
+        .add_plugin(BevyPenToolPlugin)
+        .insert_resource(TargetPositions(HashMap::new()))
+        .add_system(update_bez);
+
+    // Run systems once
+    app.update();
+
+    // a rectangle with sharp corners, made of four straight curves latched to each other
+    let curves = ShapeTool::default().curves(
+        ShapeKind::Rectangle,
+        Vec2::new(-100., -50.),
+        Vec2::new(100., 50.),
+    );
+    let hists = shape_hists(curves.clone());
+    let ids: Vec<BezierId> = hists.iter().map(|hist| hist.id.into()).collect();
+
+    app.world.send_event(SpawningGroups {
+        groups: vec![hists],
+        do_send_to_history: true,
+    });
+
+    // the app needs some time to perform the tasks,
+    // since they are event and asset based
+    app.update();
+    app.update();
+    app.update();
+
+    // the corner at the end of the first side is the start of the second side
+    let new_corner = Vec2::new(150., -80.);
+    let shift = new_corner - curves[0].end;
+
+    let mut pen_commands = app.world.get_resource_mut::<PenCommandVec>().unwrap();
+    pen_commands.move_anchor(ids[0], Anchor::End, new_corner);
+
+    let mut target_positions = app.world.get_resource_mut::<TargetPositions>().unwrap();
+
+    // the control points of both sides follow the corner, without being mirrored
+    target_positions.0.insert(
+        ids[0],
+        BezierPositions {
+            end: new_corner,
+            control_end: curves[0].control_end + shift,
+            ..curves[0]
+        },
+    );
+    target_positions.0.insert(
+        ids[1],
+        BezierPositions {
+            start: new_corner,
+            control_start: curves[1].control_start + shift,
+            ..curves[1]
+        },
+    );
+
+    // the other corners stay in place
+    target_positions.0.insert(ids[2], curves[2]);
+    target_positions.0.insert(ids[3], curves[3]);
+
+    app.update();
+    app.update();
+    app.update();
+
+    let bezier_curves = app.world.resource::<BezierTestHashed>();
+    let target_positions = app.world.resource::<TargetPositions>();
+
+    for (id, target_pos) in target_positions.0.iter() {
+        let bezier = bezier_curves.0.get(&id).unwrap();
+        assert_close(&bezier.positions, target_pos);
+    }
+    println!("move_shape_corner_test passed");
+}
+
+fn assert_close(positions: &BezierPositions, target: &BezierPositions) {
+    let pairs = [
+        (positions.start, target.start),
+        (positions.end, target.end),
+        (positions.control_start, target.control_start),
+        (positions.control_end, target.control_end),
+    ];
+    for (position, target_position) in pairs {
+        assert!(
+            position.distance(target_position) < 0.001,
+            "{:?} != {:?}",
+            positions,
+            target
+        );
+    }
+}
+
+pub struct BezierTestHashed(pub HashMap<BezierId, Bezier>);
+
+pub fn update_bez(
+    bezier_curves: Res<Assets<Bezier>>,
+    mut bezier_curves_test: ResMut<BezierTestHashed>,
+) {
+    for (handle_id, bez) in bezier_curves.iter() {
+        let id = BezierId(handle_id);
+        bezier_curves_test.0.insert(id, bez.clone());
+    }
+}
@@ -84,11 +84,13 @@ fn main() {
         latched_to_id: id2,
         partners_edge: AnchorEdge::Start,
         self_edge: AnchorEdge::Start,
+        corner: false,
     };
     let expected_latch_data2 = LatchData {
         latched_to_id: id1,
         partners_edge: AnchorEdge::Start,
         self_edge: AnchorEdge::Start,
+        corner: false,
     };
 
     assert_eq!(bezier1.latches[&AnchorEdge::Start], expected_latch_data1);
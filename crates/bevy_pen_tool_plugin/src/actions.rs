@@ -1,8 +1,19 @@
-use bevy_pen_tool_model::inputs::{Action, Cursor};
-use bevy_pen_tool_model::mesh::PenMesh;
+use bevy_pen_tool_model::inputs::{Action, Alignment, Cursor, MouseClickEvent};
+use bevy_pen_tool_model::mesh::{
+    road_band_color, set_mesh_color, stash_mesh, FillMesh2dMaterial, MeshId, MeshSource, PenMesh,
+    RoadBandColor, RoadBandMesh, RoadMesh2dMaterial, RoadTint,
+};
 use bevy_pen_tool_model::model::*;
 
-use bevy::prelude::*;
+use crate::pen::{move_anchors, transformed_anchors};
+
+use bevy::{
+    prelude::*,
+    render::mesh::VertexAttributeValues,
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+};
+
+use rand::{thread_rng, Rng};
 
 use std::collections::HashMap;
 use std::collections::HashSet;
@@ -68,9 +79,35 @@ pub fn update_anchors(
     mut query: Query<(&Handle<Bezier>, &Anchor, &MovingAnchor)>,
     cursor: Res<Cursor>,
     maps: ResMut<Maps>,
+    keyboard_input: Res<Input<KeyCode>>,
+    globals: Res<Globals>,
+    meshes: Res<Assets<Mesh>>,
+    mesh_query: Query<(&Transform, Option<&Mesh2dHandle>, Option<&Children>), With<PenMesh>>,
+    band_query: Query<&Mesh2dHandle, With<RoadBandMesh>>,
 ) {
     // TODO: remove dependency on Cursor
     if cursor.latch.is_empty() {
+        let snapping = &globals.snapping;
+        let do_snap = snapping.is_active(&keyboard_input)
+            && query
+                .iter()
+                .any(|(_, _, moving_anchor)| moving_anchor.follow_mouse);
+
+        let mut snap_targets = Vec::new();
+        if do_snap {
+            // the moving curves and their latched partners would attract their own anchors
+            let mut excluded = HashSet::new();
+            for (bezier_handle, _, _) in query.iter() {
+                if let Some(bezier) = bezier_curves.get(bezier_handle) {
+                    excluded.insert(bezier.id);
+                    excluded.extend(bezier.latches.values().map(|latch| latch.latched_to_id));
+                }
+            }
+            snap_targets = snapping.curve_targets(&bezier_curves, &excluded);
+            snap_targets.extend(snapping.mesh_targets(&meshes, &mesh_query, &band_query));
+        }
+        let mut whole_curve_shift = None;
+
         for (bezier_handle, anchor, moving_anchor) in query.iter_mut() {
             //
             if let Some(bezier) = bezier_curves.get_mut(bezier_handle) {
@@ -78,6 +115,15 @@ pub fn update_anchors(
                 if moving_anchor.follow_mouse {
                     bezier.update_positions_cursor(&cursor, *anchor);
 
+                    if do_snap {
+                        bezier.snap_positions(
+                            *anchor,
+                            snapping,
+                            &snap_targets,
+                            &mut whole_curve_shift,
+                        );
+                    }
+
                     let latch_info = bezier.get_anchor_latch_info(*anchor);
 
                     if let Some(_) = latch_info {
@@ -241,10 +287,12 @@ pub fn selection_box_init(
     }
 }
 
-// inserts curves inside box in the Selection resource
+// inserts curves inside box in the Selection resource. With Left Alt held, the anchors and
+// control points inside the box are selected instead
 pub fn selection_area_finalize(
     mut selection: ResMut<Selection>,
     cursor: ResMut<Cursor>,
+    keyboard_input: Res<Input<KeyCode>>,
     bezier_curves: ResMut<Assets<Bezier>>,
     // groups: ResMut<Assets<Group>>,
     mut query_set: ParamSet<(
@@ -261,12 +309,15 @@ pub fn selection_area_finalize(
 ) {
     if action_event_reader.iter().any(|x| x == &Action::Selected) {
         selection.selected.clear();
+        let select_anchors = keyboard_input.pressed(KeyCode::LAlt);
 
         info!("selection_area_finalize");
         // check for meshes within the selection area
         for (transform, pen_mesh) in mesh_query.iter() {
-            if cursor
-                .anchor_is_within_selection_box(transform.translation.truncate() * globals.scale)
+            if !select_anchors
+                && cursor.anchor_is_within_selection_box(
+                    transform.translation.truncate() * globals.scale,
+                )
             {
                 selection.selected.push(SelectionChoice::Mesh(
                     pen_mesh.clone(),
@@ -276,12 +327,28 @@ pub fn selection_area_finalize(
         }
 
         let mut selected_curves = HashSet::new();
+        let mut selected_anchors = HashSet::new();
         let mut found_anchor_in_box = false;
 
         // check for anchors inside selection area
         for (_entity, bezier_handle) in bezier_query.iter() {
             let bezier = bezier_curves.get(bezier_handle).unwrap();
 
+            if select_anchors {
+                let mut anchors = vec![Anchor::Start, Anchor::End];
+                if !globals.hide_control_points {
+                    anchors.extend([Anchor::ControlStart, Anchor::ControlEnd]);
+                }
+                for anchor in anchors {
+                    if cursor
+                        .anchor_is_within_selection_box(bezier.get_position(anchor) * globals.scale)
+                    {
+                        selected_anchors.insert((bezier.id, anchor));
+                    }
+                }
+                continue;
+            }
+
             if cursor.anchor_is_within_selection_box(bezier.positions.start * globals.scale)
                 || cursor.anchor_is_within_selection_box(bezier.positions.end * globals.scale)
             {
@@ -301,6 +368,7 @@ pub fn selection_area_finalize(
                 .selected
                 .push(SelectionChoice::CurveSet(selected_curves));
         }
+        selection.set_anchors(selected_anchors);
 
         // selection box visible
         for mut visible_selected in query_set.p1().iter_mut() {
@@ -318,6 +386,523 @@ pub fn selection_area_finalize(
     }
 }
 
+// Left Shift + click on a curve and Left Alt + click on an anchor add it to the selection,
+// or remove it
+pub fn toggle_selected_items(
+    mut selection: ResMut<Selection>,
+    mut mouse_event_reader: EventReader<MouseClickEvent>,
+    mut visible_selection_query: Query<
+        &mut Visibility,
+        Or<(With<SelectedBoxQuad>, With<GroupBoxQuad>)>,
+    >,
+) {
+    for mouse_event in mouse_event_reader.iter() {
+        match mouse_event {
+            MouseClickEvent::ToggleSelectedCurve(bezier_id) => selection.toggle_curve(*bezier_id),
+            MouseClickEvent::ToggleSelectedAnchor((anchor, bezier_id)) => {
+                selection.toggle_anchor(*bezier_id, *anchor)
+            }
+            _ => continue,
+        }
+
+        for mut visible in visible_selection_query.iter_mut() {
+            visible.is_visible = !selection.selected.is_empty();
+        }
+    }
+}
+
+// select all, invert the selection, and select the curves connected to the selection
+pub fn edit_selection(
+    mut action_event_reader: EventReader<Action>,
+    mut selection: ResMut<Selection>,
+    bezier_curves: ResMut<Assets<Bezier>>,
+    maps: Res<Maps>,
+    mesh_query: Query<(&Transform, &PenMesh)>,
+    mut visible_selection_query: Query<
+        &mut Visibility,
+        Or<(With<SelectedBoxQuad>, With<GroupBoxQuad>)>,
+    >,
+) {
+    for action in action_event_reader.iter() {
+        if action != &Action::SelectAll
+            && action != &Action::InvertSelection
+            && action != &Action::SelectConnected
+        {
+            continue;
+        }
+
+        let all_curves: HashSet<BezierId> =
+            bezier_curves.iter().map(|(_, bezier)| bezier.id).collect();
+        // deleted meshes are hidden, but they are still around
+        let all_meshes: Vec<(PenMesh, Vec2)> = mesh_query
+            .iter()
+            .filter(|(_, pen_mesh)| maps.mesh_map.contains_key(&pen_mesh.id))
+            .map(|(transform, pen_mesh)| (pen_mesh.clone(), transform.translation.truncate()))
+            .collect();
+
+        let selected_curves = selection.curve_ids();
+        let selected_meshes: HashSet<MeshId> = selection
+            .selected
+            .iter()
+            .filter_map(|selected| match selected {
+                SelectionChoice::Mesh(pen_mesh, _) => Some(pen_mesh.id),
+                _ => None,
+            })
+            .collect();
+
+        let (curves, meshes) = match action {
+            Action::SelectAll => (all_curves, all_meshes),
+
+            Action::InvertSelection => (
+                all_curves.difference(&selected_curves).cloned().collect(),
+                all_meshes
+                    .into_iter()
+                    .filter(|(pen_mesh, _)| !selected_meshes.contains(&pen_mesh.id))
+                    .collect(),
+            ),
+
+            // the selected anchors count for their curves
+            _ => {
+                let mut curves = selected_curves.clone();
+                curves.extend(selection.anchors().iter().map(|(bezier_id, _)| *bezier_id));
+
+                for bezier_id in curves.clone().iter() {
+                    if !maps.bezier_map.contains_key(bezier_id) {
+                        continue;
+                    }
+                    let connected =
+                        find_connected_curves(*bezier_id, &bezier_curves, &maps.bezier_map);
+                    curves.extend(connected.iter().map(|handle| BezierId::from(handle.id)));
+                }
+
+                let meshes = all_meshes
+                    .into_iter()
+                    .filter(|(pen_mesh, _)| selected_meshes.contains(&pen_mesh.id))
+                    .collect();
+                (curves, meshes)
+            }
+        };
+
+        selection.selected = meshes
+            .into_iter()
+            .map(|(pen_mesh, translation)| SelectionChoice::Mesh(pen_mesh, translation))
+            .collect();
+        if !curves.is_empty() {
+            selection.selected.push(SelectionChoice::CurveSet(curves));
+        }
+
+        for mut visible in visible_selection_query.iter_mut() {
+            visible.is_visible = !selection.selected.is_empty();
+        }
+    }
+}
+
+// anchors moved by a nudge: the selected anchors and control points, and both ends of the
+// selected curves. Control points whose anchor moves are carried along by it
+fn nudged_anchors(
+    selection: &Selection,
+    bezier_curves: &Assets<Bezier>,
+    maps: &Maps,
+    shift: Vec2,
+) -> Vec<AnchorMove> {
+    let mut anchors = selection.anchors();
+    for bezier_id in selection.curve_ids() {
+        anchors.insert((bezier_id, Anchor::Start));
+        anchors.insert((bezier_id, Anchor::End));
+    }
+
+    let mut moves = Vec::new();
+    for (bezier_id, anchor) in anchors.iter() {
+        if !anchor.is_edge() && anchors.contains(&(*bezier_id, anchor.adjoint())) {
+            continue;
+        }
+        if let Some(bezier) = maps
+            .bezier_map
+            .get(bezier_id)
+            .and_then(|handle_entity| bezier_curves.get(&handle_entity.handle))
+        {
+            let previous_position = bezier.get_position(*anchor);
+            moves.push(AnchorMove {
+                bezier_id: (*bezier_id).into(),
+                anchor: *anchor,
+                previous_position,
+                new_position: previous_position + shift,
+            });
+        }
+    }
+    moves
+}
+
+// moves the selection with the arrow keys, by one unit or ten with Left Shift
+pub fn nudge_selection(
+    mut commands: Commands,
+    mut action_event_reader: EventReader<Action>,
+    selection: Res<Selection>,
+    mut bezier_curves: ResMut<Assets<Bezier>>,
+    maps: ResMut<Maps>,
+    mut add_to_history_event_writer: EventWriter<HistoryAction>,
+) {
+    for action in action_event_reader.iter() {
+        if let Action::Nudge(shift) = action {
+            let moves = nudged_anchors(&selection, &bezier_curves, &maps, *shift);
+            if moves.is_empty() {
+                continue;
+            }
+
+            move_anchors(&mut commands, &moves, false, &mut bezier_curves, &maps);
+            add_to_history_event_writer.send(HistoryAction::MovedSelection {
+                moves,
+                mesh_moves: Vec::new(),
+            });
+        }
+    }
+}
+
+// part of the selection that is aligned as a whole: the selected curves of a group, a mesh,
+// or an anchor
+struct AlignedItem {
+    min: Vec2,
+    max: Vec2,
+    anchors: Vec<(BezierId, Anchor)>,
+    mesh: Option<(MeshId, Entity, Vec2)>,
+}
+
+impl AlignedItem {
+    fn center(&self) -> Vec2 {
+        (self.min + self.max) / 2.0
+    }
+}
+
+fn aligned_items(
+    selection: &Selection,
+    bezier_curves: &Assets<Bezier>,
+    maps: &Maps,
+    position_query: &Query<(
+        &mut Transform,
+        Option<&Handle<FillMesh2dMaterial>>,
+        Option<&Handle<RoadMesh2dMaterial>>,
+    )>,
+) -> Vec<AlignedItem> {
+    let bezier_of = |bezier_id: &BezierId| {
+        maps.bezier_map
+            .get(bezier_id)
+            .and_then(|handle_entity| bezier_curves.get(&handle_entity.handle))
+    };
+
+    // the selected curves of a group stay together
+    let curve_ids = selection.curve_ids();
+    let mut group_items: HashMap<GroupId, AlignedItem> = HashMap::new();
+    for bezier_id in curve_ids.iter() {
+        if let Some(bezier) = bezier_of(bezier_id) {
+            let (min, max) = bezier.bounding_box();
+            let item = group_items.entry(bezier.group).or_insert(AlignedItem {
+                min,
+                max,
+                anchors: Vec::new(),
+                mesh: None,
+            });
+            item.min = item.min.min(min);
+            item.max = item.max.max(max);
+            item.anchors.push((*bezier_id, Anchor::Start));
+            item.anchors.push((*bezier_id, Anchor::End));
+        }
+    }
+    let mut items: Vec<AlignedItem> = group_items.into_values().collect();
+
+    // anchors of the selected curves move with their curve, and control points with their anchor
+    let anchors = selection.anchors();
+    for (bezier_id, anchor) in anchors.iter() {
+        if curve_ids.contains(bezier_id)
+            || (!anchor.is_edge() && anchors.contains(&(*bezier_id, anchor.adjoint())))
+        {
+            continue;
+        }
+        if let Some(bezier) = bezier_of(bezier_id) {
+            let position = bezier.get_position(*anchor);
+            items.push(AlignedItem {
+                min: position,
+                max: position,
+                anchors: vec![(*bezier_id, *anchor)],
+                mesh: None,
+            });
+        }
+    }
+
+    for selected in selection.selected.iter() {
+        if let SelectionChoice::Mesh(pen_mesh, _) = selected {
+            let entity = match maps.mesh_map.get(&pen_mesh.id) {
+                Some(entity) => *entity,
+                None => continue,
+            };
+            if let Ok((transform, _, _)) = position_query.get(entity) {
+                let translation = transform.translation.truncate();
+                items.push(AlignedItem {
+                    min: pen_mesh.bounding_box.0 + translation,
+                    max: pen_mesh.bounding_box.1 + translation,
+                    anchors: Vec::new(),
+                    mesh: Some((pen_mesh.id, entity, translation)),
+                });
+            }
+        }
+    }
+
+    items
+}
+
+// shift of each item for a given alignment
+fn alignment_shifts(items: &Vec<AlignedItem>, alignment: Alignment) -> Vec<Vec2> {
+    let min = items
+        .iter()
+        .fold(Vec2::splat(f32::MAX), |acc, item| acc.min(item.min));
+    let max = items
+        .iter()
+        .fold(Vec2::splat(f32::MIN), |acc, item| acc.max(item.max));
+    let center = (min + max) / 2.0;
+
+    let distribute = |axis: Vec2| {
+        let mut order: Vec<usize> = (0..items.len()).collect();
+        order.sort_by(|a, b| {
+            let (a, b) = (items[*a].center().dot(axis), items[*b].center().dot(axis));
+            a.partial_cmp(&b).unwrap()
+        });
+        let first = items[order[0]].center().dot(axis);
+        let last = items[order[order.len() - 1]].center().dot(axis);
+        let step = (last - first) / (items.len() - 1) as f32;
+
+        let mut shifts = vec![Vec2::ZERO; items.len()];
+        for (k, idx) in order.iter().enumerate() {
+            let target = first + step * k as f32;
+            shifts[*idx] = axis * (target - items[*idx].center().dot(axis));
+        }
+        shifts
+    };
+
+    match alignment {
+        Alignment::Left => items
+            .iter()
+            .map(|item| Vec2::new(min.x - item.min.x, 0.0))
+            .collect(),
+        Alignment::Right => items
+            .iter()
+            .map(|item| Vec2::new(max.x - item.max.x, 0.0))
+            .collect(),
+        Alignment::Top => items
+            .iter()
+            .map(|item| Vec2::new(0.0, max.y - item.max.y))
+            .collect(),
+        Alignment::Bottom => items
+            .iter()
+            .map(|item| Vec2::new(0.0, min.y - item.min.y))
+            .collect(),
+        Alignment::HorizontalCenter => items
+            .iter()
+            .map(|item| Vec2::new(center.x - item.center().x, 0.0))
+            .collect(),
+        Alignment::VerticalCenter => items
+            .iter()
+            .map(|item| Vec2::new(0.0, center.y - item.center().y))
+            .collect(),
+        Alignment::DistributeHorizontally => distribute(Vec2::X),
+        Alignment::DistributeVertically => distribute(Vec2::Y),
+    }
+}
+
+// aligns or distributes the selected curves, groups, meshes and anchors, as a single entry
+// of the history
+pub fn align_selection(
+    mut commands: Commands,
+    mut action_event_reader: EventReader<Action>,
+    mut selection: ResMut<Selection>,
+    mut bezier_curves: ResMut<Assets<Bezier>>,
+    maps: ResMut<Maps>,
+    mut position_query: Query<(
+        &mut Transform,
+        Option<&Handle<FillMesh2dMaterial>>,
+        Option<&Handle<RoadMesh2dMaterial>>,
+    )>,
+    mut fill_materials: ResMut<Assets<FillMesh2dMaterial>>,
+    mut road_materials: ResMut<Assets<RoadMesh2dMaterial>>,
+    mut add_to_history_event_writer: EventWriter<HistoryAction>,
+) {
+    for action in action_event_reader.iter() {
+        let alignment = match action {
+            Action::Align(alignment) => *alignment,
+            _ => continue,
+        };
+
+        let items = aligned_items(&selection, &bezier_curves, &maps, &position_query);
+        let is_distribution = alignment == Alignment::DistributeHorizontally
+            || alignment == Alignment::DistributeVertically;
+        if items.len() < 2 || (is_distribution && items.len() < 3) {
+            info!("not enough selected items to {:?}", alignment);
+            continue;
+        }
+
+        let mut moves = Vec::new();
+        let mut mesh_moves = Vec::new();
+        for (item, shift) in items.iter().zip(alignment_shifts(&items, alignment)) {
+            if shift.length() < 0.0001 {
+                continue;
+            }
+            for (bezier_id, anchor) in item.anchors.iter() {
+                if let Some(bezier) = maps
+                    .bezier_map
+                    .get(bezier_id)
+                    .and_then(|handle_entity| bezier_curves.get(&handle_entity.handle))
+                {
+                    let previous_position = bezier.get_position(*anchor);
+                    moves.push(AnchorMove {
+                        bezier_id: (*bezier_id).into(),
+                        anchor: *anchor,
+                        previous_position,
+                        new_position: previous_position + shift,
+                    });
+                }
+            }
+            if let Some((mesh_id, _, translation)) = item.mesh {
+                mesh_moves.push(MeshMove {
+                    mesh_id,
+                    previous_position: translation,
+                    new_position: translation + shift,
+                });
+            }
+        }
+
+        if moves.is_empty() && mesh_moves.is_empty() {
+            continue;
+        }
+
+        move_anchors(&mut commands, &moves, false, &mut bezier_curves, &maps);
+        for mesh_move in mesh_moves.iter() {
+            if let Some(entity) = maps.mesh_map.get(&mesh_move.mesh_id) {
+                place_mesh(
+                    *entity,
+                    mesh_move.new_position,
+                    &mut position_query,
+                    &mut fill_materials,
+                    &mut road_materials,
+                );
+            }
+        }
+
+        // the selection box follows the meshes
+        for selected in selection.selected.iter_mut() {
+            if let SelectionChoice::Mesh(pen_mesh, translation) = selected {
+                if let Some(mesh_move) = mesh_moves
+                    .iter()
+                    .find(|mesh_move| mesh_move.mesh_id == pen_mesh.id)
+                {
+                    *translation = mesh_move.new_position;
+                }
+            }
+        }
+
+        add_to_history_event_writer.send(HistoryAction::MovedSelection { moves, mesh_moves });
+    }
+}
+
+// flips or rotates the selected curves around the center of their bounding box. The latched
+// curves that are not selected follow the moved anchors, so that the latches stay valid
+pub fn transform_selection(
+    mut commands: Commands,
+    mut action_event_reader: EventReader<Action>,
+    selection: Res<Selection>,
+    mut bezier_curves: ResMut<Assets<Bezier>>,
+    maps: ResMut<Maps>,
+    mut add_to_history_event_writer: EventWriter<HistoryAction>,
+) {
+    for action in action_event_reader.iter() {
+        if let Action::Transform(transformation) = action {
+            let ids = selection.curve_ids();
+            let moves = transformed_anchors(&ids, *transformation, &bezier_curves, &maps);
+            if moves.is_empty() {
+                continue;
+            }
+
+            move_anchors(&mut commands, &moves, false, &mut bezier_curves, &maps);
+            add_to_history_event_writer.send(HistoryAction::MovedSelection {
+                moves,
+                mesh_moves: Vec::new(),
+            });
+        }
+    }
+}
+
+// draws a square around each selected anchor and control point
+pub fn show_selected_anchors(
+    mut commands: Commands,
+    selection: Res<Selection>,
+    bezier_curves: Res<Assets<Bezier>>,
+    maps: Res<Maps>,
+    globals: Res<Globals>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+    mut overlay_query: Query<
+        (&Mesh2dHandle, &mut Transform, &mut Visibility),
+        With<SelectedAnchorsOverlay>,
+    >,
+    mut shown_overlay: Local<Option<(Vec<(Vec2, Vec2)>, f32)>>,
+) {
+    let mut segments = Vec::new();
+    for (bezier_id, anchor) in selection.anchors().iter() {
+        if let Some(bezier) = maps
+            .bezier_map
+            .get(bezier_id)
+            .and_then(|handle_entity| bezier_curves.get(&handle_entity.handle))
+        {
+            let center = bezier.get_position(*anchor);
+            let corners = [
+                center + Vec2::new(-4.0, -4.0),
+                center + Vec2::new(4.0, -4.0),
+                center + Vec2::new(4.0, 4.0),
+                center + Vec2::new(-4.0, 4.0),
+            ];
+            for k in 0..4 {
+                segments.push((corners[k], corners[(k + 1) % 4]));
+            }
+        }
+    }
+
+    // the mesh is only rebuilt when the selection, its anchors or the scale change
+    let wanted_overlay = (segments, globals.scale);
+    if shown_overlay.as_ref() == Some(&wanted_overlay) {
+        return;
+    }
+    let (segments, _) = shown_overlay.insert(wanted_overlay);
+
+    if segments.is_empty() {
+        for (_, _, mut visibility) in overlay_query.iter_mut() {
+            visibility.is_visible = false;
+        }
+        return;
+    }
+
+    let mesh = lines_mesh(segments, 0.5);
+    let scale = Vec3::new(globals.scale, globals.scale, 1.0);
+
+    if let Some((Mesh2dHandle(handle), mut transform, mut visibility)) =
+        overlay_query.iter_mut().next()
+    {
+        if let Some(overlay_mesh) = meshes.get_mut(handle) {
+            *overlay_mesh = mesh;
+        }
+        transform.scale = scale;
+        visibility.is_visible = true;
+    } else {
+        let mut transform = Transform::from_xyz(0.0, 0.0, globals.z_pos.selected_anchors);
+        transform.scale = scale;
+
+        commands
+            .spawn_bundle(MaterialMesh2dBundle {
+                mesh: Mesh2dHandle(meshes.add(mesh)),
+                material: color_materials.add(ColorMaterial::from(Color::rgb(0.3, 0.85, 1.0))),
+                transform,
+                ..default()
+            })
+            .insert(SelectedAnchorsOverlay);
+    }
+}
+
 pub fn unselect(
     mut selection: ResMut<Selection>,
     mut visible_selection_query: Query<
@@ -516,6 +1101,7 @@ pub fn latchy(
                     latched_to_id: id,
                     self_edge: anchor_edge,
                     partners_edge: mover_edge,
+                    corner: false,
                 };
 
                 partner_bezier.potential_latch = Some(partner_latch_data);
@@ -550,6 +1136,7 @@ pub fn latchy(
                 latched_to_id: partner_id,
                 self_edge: mover_anchor,
                 partners_edge: pa_edge,
+                corner: false,
             };
 
             bezier.potential_latch = Some(mover_latch_data.clone());
@@ -706,6 +1293,7 @@ pub fn unlatchy(
                         partner_id: latch.latched_to_id.into(),
                         self_anchor: latch.self_edge,
                         partner_anchor: latch.partners_edge,
+                        corner: latch.corner,
                     });
 
                     if globals.sound_on {
@@ -826,6 +1414,7 @@ pub fn officiate_latch_partnership(
             latched_to_id: bezier_1_id.into(),
             self_edge: latch.partners_edge,
             partners_edge: latch.self_edge,
+            corner: latch.corner,
         };
 
         bezier_2.latches.insert(latch.partners_edge, latch_2);
@@ -922,9 +1511,57 @@ pub fn compute_group_lut(
     mut group_lut_event_reader: EventReader<ComputeGroupLut>,
     mut group_asset_event: EventReader<AssetEvent<Group>>,
     mut bezier_asset_event: EventReader<AssetEvent<Bezier>>,
+    mut action_event_reader: EventReader<Action>,
     maps: Res<Maps>,
     globals: Res<Globals>,
+    selection: Res<Selection>,
 ) {
+    // Recompute every look-up table on demand (Shift+T or the Lut button), optionally
+    // cycling through the sampling modes of the groups of the selected curves (Ctrl+T),
+    // and report how evenly the points are spaced
+    for action in action_event_reader.iter() {
+        let toggle = match action {
+            Action::ComputeLut => false,
+            Action::ToggleLutSampling => true,
+            _ => continue,
+        };
+        let bezier_assets = bezier_curves
+            .iter()
+            .collect::<HashMap<bevy::asset::HandleId, &Bezier>>();
+
+        let selected_groups: HashSet<GroupId> = selection
+            .curve_ids()
+            .iter()
+            .filter_map(|id| maps.bezier_map.get(id))
+            .filter_map(|handle_entity| bezier_curves.get(&handle_entity.handle))
+            .map(|bezier| bezier.group)
+            .collect();
+        if toggle && selected_groups.is_empty() {
+            info!("select curves of a group to change its look-up table sampling");
+        }
+
+        for (_, group) in groups.iter_mut() {
+            if toggle && selected_groups.contains(&group.id) {
+                group.lut_sampling = match group.lut_sampling {
+                    LutSampling::GroupT => LutSampling::ArcLength,
+                    LutSampling::ArcLength => LutSampling::Adaptive,
+                    LutSampling::Adaptive => LutSampling::GroupT,
+                };
+            }
+            group.find_connected_ends(&bezier_assets, maps.bezier_map.clone());
+            group.group_lut(&bezier_assets, maps.bezier_map.clone());
+            group.compute_standalone_lut(&bezier_assets, globals.group_lut_num_points);
+            info!(
+                "look-up table of group {:?} ({:?} sampling): {} points, length = {}, max spacing error = {}",
+                group.id,
+                group.lut_sampling,
+                group.standalone_lut.lut.len(),
+                group.standalone_lut.path_length,
+                group.lut_spacing_error
+            );
+        }
+    }
+
     // On demand look-up table computation using the ComputeGroupLut event
     for group_lut_event in group_lut_event_reader.iter() {
         if let Some(group_handle) = maps.group_map.get(&group_lut_event.0) {
@@ -1198,17 +1835,14 @@ pub fn delete(
                             groups.remove(group_handle);
                         }
                     }
-                    SelectionChoice::Mesh(
-                        PenMesh {
-                            id,
-                            bounding_box: _,
-                        },
-                        _pos,
-                    ) => {
-                        //
-                        let entity = maps.mesh_map.get(&id).unwrap();
-                        commands.entity(*entity).despawn();
-                        maps.mesh_map.remove(&id);
+                    SelectionChoice::Mesh(pen_mesh, _pos) => {
+                        // the mesh is only hidden, so that the deletion can be undone
+                        stash_mesh(&mut commands, &mut maps, &pen_mesh);
+                        if !*is_from_redo {
+                            add_to_history_event_writer.send(HistoryAction::DeletedMesh {
+                                mesh_id: pen_mesh.id,
+                            });
+                        }
                     }
                     _ => {}
                 }
@@ -1258,6 +1892,7 @@ pub fn delete(
                                 partner_id: latch_data.latched_to_id.into(),
                                 self_anchor: latch_data.self_edge,
                                 partner_anchor: latch_data.partners_edge,
+                                corner: latch_data.corner,
                             };
 
                             // info!("unlatched: {:?}", unlatched);
@@ -1293,6 +1928,302 @@ pub fn delete(
     }
 }
 
+// moves a mesh and the center of mass of its material, like move_mesh does
+pub fn place_mesh(
+    entity: Entity,
+    position: Vec2,
+    position_query: &mut Query<(
+        &mut Transform,
+        Option<&Handle<FillMesh2dMaterial>>,
+        Option<&Handle<RoadMesh2dMaterial>>,
+    )>,
+    fill_materials: &mut Assets<FillMesh2dMaterial>,
+    road_materials: &mut Assets<RoadMesh2dMaterial>,
+) {
+    if let Ok((mut transform, fill_handle, road_handle)) = position_query.get_mut(entity) {
+        transform.translation = position.extend(transform.translation.z);
+
+        if let Some(material) = fill_handle.and_then(|handle| fill_materials.get_mut(handle)) {
+            material.center_of_mass = position;
+        }
+        if let Some(material) = road_handle.and_then(|handle| road_materials.get_mut(handle)) {
+            material.center_of_mass = position;
+        }
+    }
+}
+
+// recolors a mesh and returns its previous color. Roads are tinted: the colors of their
+// bands, which come from the road profile, are multiplied by the new color
+pub fn recolor_mesh(
+    entity: Entity,
+    color: Color,
+    color_query: &mut Query<(
+        &Mesh2dHandle,
+        &Handle<FillMesh2dMaterial>,
+        Option<&mut MeshSource>,
+    )>,
+    road_query: &mut Query<(&mut RoadTint, &Children)>,
+    band_query: &Query<(&RoadBandColor, &Handle<RoadMesh2dMaterial>)>,
+    meshes: &mut Assets<Mesh>,
+    fill_materials: &mut Assets<FillMesh2dMaterial>,
+    road_materials: &mut Assets<RoadMesh2dMaterial>,
+) -> Option<Color> {
+    if let Ok((mut tint, children)) = road_query.get_mut(entity) {
+        let previous_tint = tint.0;
+        tint.0 = color;
+        for child in children.iter() {
+            if let Ok((RoadBandColor(band_color), material_handle)) = band_query.get(*child) {
+                if let Some(material) = road_materials.get_mut(material_handle) {
+                    material.color = road_band_color(*band_color, color);
+                }
+            }
+        }
+        return Some(previous_tint);
+    }
+
+    let (Mesh2dHandle(mesh_handle), material_handle, source) = color_query.get_mut(entity).ok()?;
+
+    let mesh = meshes.get_mut(mesh_handle)?;
+    let previous_color = match mesh.attribute(Mesh::ATTRIBUTE_COLOR) {
+        Some(VertexAttributeValues::Float32x4(colors)) if !colors.is_empty() => {
+            Color::rgba(colors[0][0], colors[0][1], colors[0][2], colors[0][3])
+        }
+        _ => return None,
+    };
+    set_mesh_color(mesh, color);
+
+    if let Some(material) = fill_materials.get_mut(material_handle) {
+        material.color = color.into();
+    }
+
+    // a regenerated mesh keeps its new color
+    if let Some(mut source) = source {
+        match &mut *source {
+            MeshSource::Fill {
+                color: source_color,
+                ..
+            }
+            | MeshSource::Stroke {
+                color: source_color,
+                ..
+            } => *source_color = color,
+            _ => {}
+        }
+    }
+
+    return Some(previous_color);
+}
+
+// copies the selected meshes next to the originals. The copies are baked: they do not
+// follow the curves of the originals
+pub fn duplicate_mesh(
+    mut commands: Commands,
+    mut action_event_reader: EventReader<Action>,
+    mut selection: ResMut<Selection>,
+    mut maps: ResMut<Maps>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut fill_materials: ResMut<Assets<FillMesh2dMaterial>>,
+    mut road_materials: ResMut<Assets<RoadMesh2dMaterial>>,
+    mesh_query: Query<(
+        &Transform,
+        &PenMesh,
+        Option<&Mesh2dHandle>,
+        Option<&Handle<FillMesh2dMaterial>>,
+        Option<&Children>,
+        Option<&RoadTint>,
+    )>,
+    band_query: Query<(
+        &Transform,
+        &Mesh2dHandle,
+        &Handle<RoadMesh2dMaterial>,
+        &RoadBandMesh,
+        &RoadBandColor,
+    )>,
+    mut add_to_history_event_writer: EventWriter<HistoryAction>,
+) {
+    if action_event_reader.iter().any(|x| x == &Action::Duplicate) {
+        let mut duplicates = Vec::new();
+
+        for selected in selection.selected.iter() {
+            let entity = match selected {
+                SelectionChoice::Mesh(PenMesh { id, .. }, _) => match maps.mesh_map.get(id) {
+                    Some(entity) => *entity,
+                    None => continue,
+                },
+                _ => continue,
+            };
+
+            if let Ok((transform, pen_mesh, mesh_handle, fill_handle, children, tint)) =
+                mesh_query.get(entity)
+            {
+                // the copy is offset so that it does not hide the original
+                let mut new_transform = *transform;
+                new_transform.translation += Vec3::new(20.0, -20.0, 0.0);
+                let new_position = new_transform.translation.truncate();
+
+                let new_entity = if let (Some(Mesh2dHandle(mesh_handle)), Some(fill_handle)) =
+                    (mesh_handle, fill_handle)
+                {
+                    // fill and stroke meshes
+                    let mesh = match meshes.get(mesh_handle) {
+                        Some(mesh) => mesh.clone(),
+                        None => continue,
+                    };
+                    let mut material = match fill_materials.get(fill_handle) {
+                        Some(material) => material.clone(),
+                        None => continue,
+                    };
+                    material.center_of_mass = new_position;
+
+                    commands
+                        .spawn_bundle(MaterialMesh2dBundle {
+                            mesh: Mesh2dHandle(meshes.add(mesh)),
+                            material: fill_materials.add(material),
+                            transform: new_transform,
+                            ..default()
+                        })
+                        .id()
+                } else if let Some(children) = children {
+                    // roads have a child mesh per band
+                    let mut band_entities = Vec::new();
+                    let mut road_material = None;
+
+                    for child in children.iter() {
+                        if let Ok((
+                            band_transform,
+                            Mesh2dHandle(band_mesh),
+                            band_material,
+                            band,
+                            band_color,
+                        )) = band_query.get(*child)
+                        {
+                            let (mesh, mut material) =
+                                match (meshes.get(band_mesh), road_materials.get(band_material)) {
+                                    (Some(mesh), Some(material)) => {
+                                        (mesh.clone(), material.clone())
+                                    }
+                                    _ => continue,
+                                };
+                            material.center_of_mass = new_position;
+                            let material_handle = road_materials.add(material);
+                            if band.0 == 0 {
+                                road_material = Some(material_handle.clone());
+                            }
+
+                            let band_entity = commands
+                                .spawn_bundle(MaterialMesh2dBundle {
+                                    mesh: Mesh2dHandle(meshes.add(mesh)),
+                                    material: material_handle,
+                                    transform: *band_transform,
+                                    ..default()
+                                })
+                                .insert(RoadBandMesh(band.0))
+                                .insert(*band_color)
+                                .id();
+                            band_entities.push(band_entity);
+                        }
+                    }
+
+                    let road_material = match road_material {
+                        Some(road_material) => road_material,
+                        None => continue,
+                    };
+
+                    commands
+                        .spawn_bundle(SpatialBundle {
+                            transform: new_transform,
+                            ..default()
+                        })
+                        // the material of the first band is used to highlight the road
+                        .insert(road_material)
+                        .insert(tint.copied().unwrap_or(RoadTint(Color::WHITE)))
+                        .push_children(&band_entities)
+                        .id()
+                } else {
+                    continue;
+                };
+
+                let mut rng = thread_rng();
+                let new_pen_mesh = PenMesh {
+                    id: rng.gen::<u64>(),
+                    bounding_box: pen_mesh.bounding_box,
+                };
+                commands.entity(new_entity).insert(new_pen_mesh.clone());
+                maps.mesh_map.insert(new_pen_mesh.id, new_entity);
+
+                add_to_history_event_writer.send(HistoryAction::SpawnedMesh {
+                    mesh_id: new_pen_mesh.id,
+                });
+                duplicates.push(SelectionChoice::Mesh(new_pen_mesh, new_position));
+            }
+        }
+
+        // the copies replace the original meshes in the selection. The curves are
+        // duplicated by duplicate_curves
+        if !duplicates.is_empty() {
+            selection
+                .selected
+                .retain(|selected| !matches!(selected, SelectionChoice::Mesh(..)));
+            selection.selected.extend(duplicates);
+        }
+    }
+}
+
+// gives the picked color to the selected meshes
+pub fn recolor_selected_meshes(
+    mut action_event_reader: EventReader<Action>,
+    selection: Res<Selection>,
+    maps: Res<Maps>,
+    globals: Res<Globals>,
+    mut color_query: Query<(
+        &Mesh2dHandle,
+        &Handle<FillMesh2dMaterial>,
+        Option<&mut MeshSource>,
+    )>,
+    mut road_query: Query<(&mut RoadTint, &Children)>,
+    band_query: Query<(&RoadBandColor, &Handle<RoadMesh2dMaterial>)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut fill_materials: ResMut<Assets<FillMesh2dMaterial>>,
+    mut road_materials: ResMut<Assets<RoadMesh2dMaterial>>,
+    mut add_to_history_event_writer: EventWriter<HistoryAction>,
+) {
+    if action_event_reader
+        .iter()
+        .any(|x| x == &Action::RecolorMesh)
+    {
+        let color = match globals.picked_color {
+            Some(color) => color,
+            None => {
+                info!("Pick a color to recolor the selected meshes");
+                return;
+            }
+        };
+
+        for selected in selection.selected.iter() {
+            if let SelectionChoice::Mesh(PenMesh { id, .. }, _) = selected {
+                if let Some(entity) = maps.mesh_map.get(id) {
+                    if let Some(previous_color) = recolor_mesh(
+                        *entity,
+                        color,
+                        &mut color_query,
+                        &mut road_query,
+                        &band_query,
+                        &mut meshes,
+                        &mut fill_materials,
+                        &mut road_materials,
+                    ) {
+                        add_to_history_event_writer.send(HistoryAction::RecoloredMesh {
+                            mesh_id: *id,
+                            previous_color,
+                            new_color: color,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
 pub fn hide_anchors(
     mut globals: ResMut<Globals>,
     mut query: Query<&mut Visibility, Or<(With<ControlPointQuad>, With<AchorEdgeQuad>)>>,
@@ -1325,3 +2256,292 @@ pub fn hide_control_points(
         }
     }
 }
+
+// group closest to a position, with the t-value of its point closest to that position
+fn closest_group_t(groups: &Assets<Group>, position: Vec2) -> Option<(bevy::asset::HandleId, f32)> {
+    let mut closest: Option<(bevy::asset::HandleId, f32, f32)> = None;
+    for (group_handle_id, group) in groups.iter() {
+        if let Some((t, distance)) = group.closest_t(position) {
+            if closest.is_none() || distance < closest.unwrap().2 {
+                closest = Some((group_handle_id, t, distance));
+            }
+        }
+    }
+    closest.map(|(group_handle_id, t, _distance)| (group_handle_id, t))
+}
+
+// path marker or width keyframe handle closest to a position, within the clicking distance
+fn closest_group_handle(
+    groups: &Assets<Group>,
+    bezier_assets: &BezierAssets,
+    kind: GroupHandleKind,
+    position: Vec2,
+    clicking_dist: f32,
+) -> Option<(bevy::asset::HandleId, usize)> {
+    let mut closest: Option<(bevy::asset::HandleId, usize, f32)> = None;
+    for (group_handle_id, group) in groups.iter() {
+        for idx in 0..group.handle_count(kind) {
+            if let Some(handle_position) = group.handle_position(bezier_assets, kind, idx) {
+                let distance = handle_position.distance(position);
+                if distance < clicking_dist && (closest.is_none() || distance < closest.unwrap().2)
+                {
+                    closest = Some((group_handle_id, idx, distance));
+                }
+            }
+        }
+    }
+    closest.map(|(group_handle_id, idx, _distance)| (group_handle_id, idx))
+}
+
+// handles are dragged with a plain left click
+fn no_drag_modifier(keyboard_input: &Input<KeyCode>) -> bool {
+    !keyboard_input.pressed(KeyCode::LShift)
+        && !keyboard_input.pressed(KeyCode::LControl)
+        && !keyboard_input.pressed(KeyCode::Space)
+}
+
+// adds a marker on the group closest to the cursor, removes the marker closest to the cursor,
+// and lets the user drag markers along their group. Each edit makes one history entry
+pub fn edit_path_markers(
+    mut commands: Commands,
+    cursor: Res<Cursor>,
+    mouse_button_input: Res<Input<MouseButton>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    globals: Res<Globals>,
+    bezier_curves: Res<Assets<Bezier>>,
+    mut groups: ResMut<Assets<Group>>,
+    mut action_event_reader: EventReader<Action>,
+    mut add_to_history_event_writer: EventWriter<HistoryAction>,
+    marker_query: Query<(Entity, &Handle<Group>, &PathMarkerQuad)>,
+    moving_marker_query: Query<(Entity, &Handle<Group>, &PathMarkerQuad, &MovingPathMarker)>,
+) {
+    let bezier_assets = bezier_curves
+        .iter()
+        .collect::<HashMap<bevy::asset::HandleId, &Bezier>>();
+    let kind = GroupHandleKind::PathMarker;
+
+    for action in action_event_reader.iter() {
+        match action {
+            Action::AddPathMarker => {
+                if let Some((group_handle_id, t)) = closest_group_t(&groups, cursor.position) {
+                    let group = groups.get_mut(&Handle::weak(group_handle_id)).unwrap();
+                    let name = format!("marker{}", group.markers.len());
+                    let marker_id = group.add_marker(&name, t);
+                    info!("added path marker {} at t = {}", name, t);
+
+                    add_to_history_event_writer.send(HistoryAction::AddedPathMarker {
+                        group_id: group.id,
+                        marker_id,
+                        name,
+                        t,
+                    });
+                }
+            }
+            Action::RemovePathMarker => {
+                if let Some((group_handle_id, idx)) = closest_group_handle(
+                    &groups,
+                    &bezier_assets,
+                    kind,
+                    cursor.position,
+                    globals.anchor_clicking_dist,
+                ) {
+                    let group = groups.get_mut(&Handle::weak(group_handle_id)).unwrap();
+                    let marker_id = group.marker_ids[idx];
+                    if let Some(marker) = group.remove_marker(marker_id) {
+                        add_to_history_event_writer.send(HistoryAction::RemovedPathMarker {
+                            group_id: group.id,
+                            marker_id,
+                            name: marker.name,
+                            t: marker.t,
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if mouse_button_input.just_pressed(MouseButton::Left) && no_drag_modifier(&keyboard_input) {
+        if let Some((group_handle_id, idx)) = closest_group_handle(
+            &groups,
+            &bezier_assets,
+            kind,
+            cursor.position,
+            globals.anchor_clicking_dist,
+        ) {
+            let group = groups.get(&Handle::weak(group_handle_id)).unwrap();
+            let marker_id = group.marker_ids[idx];
+            if let Some((entity, _, _)) = marker_query.iter().find(|(_, group_handle, quad)| {
+                group_handle.id == group_handle_id && quad.0 == marker_id
+            }) {
+                commands.entity(entity).insert(MovingPathMarker {
+                    previous_t: group.markers[idx].t,
+                });
+            }
+        }
+    }
+
+    for (entity, group_handle, PathMarkerQuad(marker_id), moving) in moving_marker_query.iter() {
+        if let Some(group) = groups.get_mut(group_handle) {
+            if mouse_button_input.pressed(MouseButton::Left) {
+                if let Some((t, _distance)) = group.closest_t(cursor.position) {
+                    group.set_marker_t(*marker_id, t);
+                }
+            } else {
+                // markers are kept sorted once they are released
+                group.sort_markers();
+                commands.entity(entity).remove::<MovingPathMarker>();
+
+                if let Some(marker) = group.marker(*marker_id) {
+                    if marker.t != moving.previous_t {
+                        add_to_history_event_writer.send(HistoryAction::MovedPathMarker {
+                            group_id: group.id,
+                            marker_id: *marker_id,
+                            previous_t: moving.previous_t,
+                            new_t: marker.t,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+// adds a width keyframe on the group closest to the cursor, removes the keyframe handle closest
+// to the cursor, and lets the user drag keyframe handles: along the group to change their t-value,
+// and away from the path to change the width
+pub fn edit_width_keyframes(
+    mut commands: Commands,
+    cursor: Res<Cursor>,
+    mouse_button_input: Res<Input<MouseButton>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    globals: Res<Globals>,
+    bezier_curves: Res<Assets<Bezier>>,
+    mut groups: ResMut<Assets<Group>>,
+    mut action_event_reader: EventReader<Action>,
+    keyframe_query: Query<(Entity, &Handle<Group>, &WidthKeyframeQuad)>,
+    moving_keyframe_query: Query<
+        (Entity, &Handle<Group>, &WidthKeyframeQuad),
+        With<MovingWidthKeyframe>,
+    >,
+) {
+    let bezier_assets = bezier_curves
+        .iter()
+        .collect::<HashMap<bevy::asset::HandleId, &Bezier>>();
+    let kind = GroupHandleKind::WidthKeyframe;
+
+    for action in action_event_reader.iter() {
+        match action {
+            Action::AddWidthKeyframe => {
+                if let Some((group_handle_id, t)) = closest_group_t(&groups, cursor.position) {
+                    let group = groups.get_mut(&Handle::weak(group_handle_id)).unwrap();
+                    // the new keyframe keeps the current width of the road
+                    let width = group
+                        .road_width_at(t)
+                        .unwrap_or(globals.road_profile.width());
+                    group.add_width_keyframe(t, width);
+                    info!("added width keyframe at t = {}, width = {}", t, width);
+                }
+            }
+            Action::RemoveWidthKeyframe => {
+                if let Some((group_handle_id, idx)) = closest_group_handle(
+                    &groups,
+                    &bezier_assets,
+                    kind,
+                    cursor.position,
+                    globals.anchor_clicking_dist,
+                ) {
+                    let group = groups.get_mut(&Handle::weak(group_handle_id)).unwrap();
+                    group.width_keyframes.remove(idx);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if mouse_button_input.just_pressed(MouseButton::Left) && no_drag_modifier(&keyboard_input) {
+        if let Some((group_handle_id, idx)) = closest_group_handle(
+            &groups,
+            &bezier_assets,
+            kind,
+            cursor.position,
+            globals.anchor_clicking_dist,
+        ) {
+            if let Some((entity, _, _)) = keyframe_query
+                .iter()
+                .find(|(_, group_handle, quad)| group_handle.id == group_handle_id && quad.0 == idx)
+            {
+                commands.entity(entity).insert(MovingWidthKeyframe);
+            }
+        }
+    }
+
+    for (entity, group_handle, WidthKeyframeQuad(idx)) in moving_keyframe_query.iter() {
+        if let Some(group) = groups.get_mut(group_handle) {
+            if mouse_button_input.pressed(MouseButton::Left) {
+                if let Some((t, distance)) = group.closest_t(cursor.position) {
+                    if let Some(keyframe) = group.width_keyframes.get_mut(*idx) {
+                        keyframe.t = t;
+                        keyframe.width = 2.0 * distance;
+                    }
+                }
+            } else {
+                // keyframes are kept sorted once they are released
+                group.sort_width_keyframes();
+                commands.entity(entity).remove::<MovingWidthKeyframe>();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(min: Vec2, max: Vec2) -> AlignedItem {
+        AlignedItem {
+            min,
+            max,
+            anchors: Vec::new(),
+            mesh: None,
+        }
+    }
+
+    #[test]
+    fn items_are_aligned_to_the_edges_of_the_selection() {
+        let items = vec![
+            item(Vec2::new(0.0, 0.0), Vec2::new(10.0, 10.0)),
+            item(Vec2::new(30.0, -20.0), Vec2::new(50.0, 5.0)),
+        ];
+
+        let shifts = alignment_shifts(&items, Alignment::Left);
+        assert_eq!(shifts, vec![Vec2::ZERO, Vec2::new(-30.0, 0.0)]);
+
+        let shifts = alignment_shifts(&items, Alignment::Top);
+        assert_eq!(shifts, vec![Vec2::ZERO, Vec2::new(0.0, 5.0)]);
+
+        // the center of the bounding box of the whole selection is at (25, -5)
+        let shifts = alignment_shifts(&items, Alignment::HorizontalCenter);
+        assert_eq!(shifts, vec![Vec2::new(20.0, 0.0), Vec2::new(-15.0, 0.0)]);
+    }
+
+    #[test]
+    fn distributed_items_are_evenly_spaced_between_the_outermost_ones() {
+        // centers at x = 40, 0 and 10, out of order
+        let items = vec![
+            item(Vec2::new(35.0, 0.0), Vec2::new(45.0, 10.0)),
+            item(Vec2::new(-5.0, 20.0), Vec2::new(5.0, 30.0)),
+            item(Vec2::new(5.0, -10.0), Vec2::new(15.0, 0.0)),
+        ];
+
+        let shifts = alignment_shifts(&items, Alignment::DistributeHorizontally);
+        assert_eq!(shifts, vec![Vec2::ZERO, Vec2::ZERO, Vec2::new(10.0, 0.0)]);
+
+        let centers: Vec<f32> = items
+            .iter()
+            .zip(shifts.iter())
+            .map(|(item, shift)| item.center().x + shift.x)
+            .collect();
+        assert_eq!(centers, vec![40.0, 0.0, 20.0]);
+    }
+}
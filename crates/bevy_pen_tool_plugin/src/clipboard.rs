@@ -0,0 +1,180 @@
+use bevy::prelude::*;
+use bevy_egui::EguiClipboard;
+use bevy_pen_tool_model::*;
+
+use std::collections::HashSet;
+use std::fs::{create_dir_all, read_to_string, File};
+use std::io::Write;
+use std::path::PathBuf;
+
+// copies are offset by this much from the original, and further with every paste
+const PASTE_OFFSET: Vec2 = Vec2::new(20.0, -20.0);
+
+// file shared by all the running editors, in case the system clipboard is not available
+fn clipboard_path() -> PathBuf {
+    let mut path = std::env::current_dir().unwrap();
+    path.push("saved");
+    path.push("clipboard");
+    path.push("clipboard.json");
+    path
+}
+
+// spawns the curves of a document and selects them in place of the selected curves
+fn paste_document(
+    document: &ClipboardDocument,
+    offset: Vec2,
+    selection: &mut Selection,
+    spawn_groups_event_writer: &mut EventWriter<SpawningGroups>,
+) {
+    let groups = document.to_groups(offset);
+    let new_ids: HashSet<BezierId> = groups
+        .iter()
+        .flatten()
+        .map(|bezier_hist| bezier_hist.id.into())
+        .collect();
+
+    spawn_groups_event_writer.send(SpawningGroups {
+        groups,
+        do_send_to_history: true,
+    });
+
+    selection
+        .selected
+        .retain(|selected| !matches!(selected, SelectionChoice::CurveSet(_)));
+    selection.selected.push(SelectionChoice::CurveSet(new_ids));
+}
+
+// copies the selected curves to the system clipboard and to the clipboard file
+pub fn copy_curves(
+    mut action_event_reader: EventReader<Action>,
+    mut clipboard: ResMut<Clipboard>,
+    mut egui_clipboard: ResMut<EguiClipboard>,
+    selection: Res<Selection>,
+    bezier_curves: Res<Assets<Bezier>>,
+    maps: Res<Maps>,
+) {
+    for action in action_event_reader.iter() {
+        if action != &Action::Copy && action != &Action::Cut {
+            continue;
+        }
+
+        let ids = selection.curve_ids();
+        let document = ClipboardDocument::from_curves(&ids, &bezier_curves, &maps);
+        if document.is_empty() {
+            info!("no curves to copy");
+            continue;
+        }
+
+        let json = document.to_json();
+        egui_clipboard.set_contents(&json);
+
+        let path = clipboard_path();
+        if let Some(folder) = path.parent() {
+            let _ = create_dir_all(folder);
+        }
+        match File::create(&path) {
+            Ok(mut output) => {
+                let _write_result = output.write(json.as_bytes());
+            }
+            Err(e) => info!("could not write the clipboard file: {}", e),
+        }
+
+        info!("copied {} curves", document.curves.len());
+        clipboard.document = Some(document);
+        clipboard.pastes = 0;
+
+        clipboard.delete_copied = action == &Action::Cut;
+    }
+}
+
+// the cut curves are deleted once they are copied, at the latest on the next frame
+pub fn delete_cut_curves(
+    mut clipboard: ResMut<Clipboard>,
+    mut selection: ResMut<Selection>,
+    mut action_event_writer: EventWriter<Action>,
+) {
+    if clipboard.delete_copied {
+        clipboard.delete_copied = false;
+        // only the curves were copied, so the selected meshes and anchors are not deleted
+        selection
+            .selected
+            .retain(|selected| matches!(selected, SelectionChoice::CurveSet(..)));
+        action_event_writer.send(Action::Delete(false));
+    }
+}
+
+// pastes the last copied curves, from this editor or from another one. The system clipboard
+// is read first, then the clipboard file, and the internal copy only if both fail
+pub fn paste_curves(
+    mut action_event_reader: EventReader<Action>,
+    mut clipboard: ResMut<Clipboard>,
+    egui_clipboard: Res<EguiClipboard>,
+    mut selection: ResMut<Selection>,
+    mut spawn_groups_event_writer: EventWriter<SpawningGroups>,
+) {
+    if action_event_reader.iter().any(|x| x == &Action::Paste) {
+        let document = egui_clipboard
+            .get_contents()
+            .and_then(|text| ClipboardDocument::from_json(&text))
+            .or_else(|| {
+                read_to_string(clipboard_path())
+                    .ok()
+                    .and_then(|text| ClipboardDocument::from_json(&text))
+            })
+            .or_else(|| clipboard.document.clone());
+
+        let document = match document {
+            Some(document) if !document.is_empty() => document,
+            _ => {
+                info!("nothing to paste");
+                return;
+            }
+        };
+
+        // a document copied elsewhere starts a new series of pastes
+        let is_same_document = clipboard
+            .document
+            .as_ref()
+            .map(|previous| previous.to_json() == document.to_json())
+            .unwrap_or(false);
+        if !is_same_document {
+            clipboard.pastes = 0;
+        }
+        clipboard.pastes += 1;
+
+        let offset = PASTE_OFFSET * clipboard.pastes as f32;
+        paste_document(
+            &document,
+            offset,
+            &mut selection,
+            &mut spawn_groups_event_writer,
+        );
+
+        info!("pasted {} curves", document.curves.len());
+        clipboard.document = Some(document);
+    }
+}
+
+// copies the selected curves in place, without going through the clipboard
+pub fn duplicate_curves(
+    mut action_event_reader: EventReader<Action>,
+    mut selection: ResMut<Selection>,
+    bezier_curves: Res<Assets<Bezier>>,
+    maps: Res<Maps>,
+    mut spawn_groups_event_writer: EventWriter<SpawningGroups>,
+) {
+    if action_event_reader.iter().any(|x| x == &Action::Duplicate) {
+        let ids = selection.curve_ids();
+        let document = ClipboardDocument::from_curves(&ids, &bezier_curves, &maps);
+        if document.is_empty() {
+            return;
+        }
+
+        paste_document(
+            &document,
+            PASTE_OFFSET,
+            &mut selection,
+            &mut spawn_groups_event_writer,
+        );
+    }
+}
@@ -8,7 +8,7 @@ use bevy::{
 
 use bevy_pen_tool_model::mesh::*;
 use bevy_pen_tool_model::model::*;
-use bevy_pen_tool_model::spawn_bezier;
+use bevy_pen_tool_model::{spawn_bezier, StandaloneLut};
 
 use serde::Deserialize;
 use serde::Serialize;
@@ -30,6 +30,13 @@ struct MeshMeta {
     color: Vec4,
 }
 
+// settings of the canvas that are not part of the curves, saved next to each .group file
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct CanvasMeta {
+    reference_image: ReferenceImage,
+}
+
 pub fn save(
     bezier_curves: Res<Assets<Bezier>>,
     // group_query: Query<&Handle<Group>, With<GroupParent>>,
@@ -42,6 +49,7 @@ pub fn save(
     globals: ResMut<Globals>,
     mut action_event_reader: EventReader<Action>,
     maps: Res<Maps>,
+    reference_image: Res<ReferenceImage>,
 ) {
     if action_event_reader.iter().any(|x| x == &Action::Save) {
         //
@@ -99,11 +107,14 @@ pub fn save(
                                 &bezier_assets,
                                 globals.group_lut_num_points,
                             );
-                            let lut_serialized =
-                                serde_json::to_string_pretty(&group.standalone_lut).unwrap();
+                            let mut standalone_lut = group.standalone_lut.clone();
+                            if globals.lut_tangents {
+                                standalone_lut.compute_tangents();
+                            }
+                            let lut_serialized = standalone_lut.encode(globals.lut_format);
                             // let lut_path = "assets/lut/my_group_lut.txt";
                             let mut lut_output = File::create(&lut_path).unwrap();
-                            let _lut_write_result = lut_output.write(lut_serialized.as_bytes());
+                            let _lut_write_result = lut_output.write(&lut_serialized);
                         }
 
                         ////////////// start. Save Group
@@ -115,8 +126,16 @@ pub fn save(
                             let serialized = serde_json::to_string_pretty(&group_vec).unwrap();
 
                             // let path = "curve_groups.txt";
-                            let mut output = File::create(group_path).unwrap();
+                            let mut output = File::create(&group_path).unwrap();
                             let _group_write_result = output.write(serialized.as_bytes());
+
+                            let canvas_meta = CanvasMeta {
+                                reference_image: reference_image.clone(),
+                            };
+                            let serialized = serde_json::to_string_pretty(&canvas_meta).unwrap();
+                            let mut output =
+                                File::create(group_path.with_extension("canvas")).unwrap();
+                            let _canvas_write_result = output.write(serialized.as_bytes());
                         }
                     }
                 }
@@ -251,6 +270,7 @@ pub fn load(
     mut ends_params: ResMut<Assets<BezierEndsMat>>,
     // mut mid_params: ResMut<Assets<BezierMidMat>>,
     mut add_to_history_event_writer: EventWriter<HistoryAction>,
+    mut reference_image: ResMut<ReferenceImage>,
 ) {
     if action_event_reader.iter().any(|x| x == &Action::Load) {
         let mut default_path = std::env::current_dir().unwrap();
@@ -284,13 +304,25 @@ pub fn load(
 
         globals.do_hide_anchors = false;
         globals.do_hide_bounding_boxes = true;
+        // the loaded curves are spawned like new ones, and would get mirrored
+        globals.symmetry.mode = SymmetryMode::Off;
 
-        let mut file = std::fs::File::open(path).unwrap();
+        let mut file = std::fs::File::open(&path).unwrap();
 
         let mut contents = String::new();
         file.read_to_string(&mut contents).unwrap();
 
-        let loaded_groups_vec: Vec<GroupSaveLoad> = serde_json::from_str(&contents).unwrap();
+        let mut loaded_groups_vec: Vec<GroupSaveLoad> = serde_json::from_str(&contents).unwrap();
+        for group_load_save in loaded_groups_vec.iter_mut() {
+            group_load_save.migrate_lut_layout();
+        }
+
+        // older documents do not have a .canvas file
+        if let Ok(canvas_contents) = std::fs::read_to_string(path.with_extension("canvas")) {
+            if let Ok(canvas_meta) = serde_json::from_str::<CanvasMeta>(&canvas_contents) {
+                *reference_image = canvas_meta.reference_image;
+            }
+        }
 
         let id: GroupId = GroupId::default();
 
@@ -299,10 +331,14 @@ pub fn load(
             bezier_handles: HashSet::new(),
             lut: Vec::new(),
             ends: None,
-            standalone_lut: StandaloneLut {
-                path_length: 0.0,
-                lut: Vec::new(),
-            },
+            standalone_lut: StandaloneLut::default(),
+            speed_profile: Vec::new(),
+            markers: Vec::new(),
+            marker_ids: Vec::new(),
+            width_keyframes: Vec::new(),
+            lut_sampling: LutSampling::default(),
+            lut_tolerance: default_lut_tolerance(),
+            lut_spacing_error: 0.0,
             id,
             entity: None,
         };
@@ -331,6 +367,11 @@ pub fn load(
                 group.group.insert((entity.clone(), handle.clone()));
                 group.bezier_handles.insert(handle.clone());
                 group.standalone_lut = group_load_save.standalone_lut.clone();
+                group.speed_profile = group_load_save.speed_profile.clone();
+                group.set_markers(group_load_save.markers.clone());
+                group.width_keyframes = group_load_save.width_keyframes.clone();
+                group.lut_sampling = group_load_save.lut_sampling;
+                group.lut_tolerance = group_load_save.lut_tolerance;
                 group.lut.push((handle.clone(), anchor, t_ends, local_lut));
 
                 curve_set.insert(handle.id.into());
@@ -344,6 +385,31 @@ pub fn load(
     }
 }
 
+// picks the image to trace over, among the images that bevy can load
+pub fn load_reference_image(
+    mut action_event_reader: EventReader<Action>,
+    mut reference_image: ResMut<ReferenceImage>,
+) {
+    if action_event_reader
+        .iter()
+        .any(|x| x == &Action::LoadReferenceImage)
+    {
+        let mut default_path = std::env::current_dir().unwrap();
+        default_path.push("assets");
+
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("image", &["png"])
+            .set_directory(&default_path)
+            .pick_file()
+        {
+            *reference_image = ReferenceImage {
+                path: Some(path.to_str().unwrap().to_string()),
+                ..Default::default()
+            };
+        }
+    }
+}
+
 use std::path::PathBuf;
 pub fn open_file_dialog(save_name: &str, folder: &str, extension: &str) -> Option<PathBuf> {
     let mut k = 0;
@@ -1,14 +1,22 @@
 pub mod actions;
+pub mod clipboard;
 pub mod io;
 pub mod moves;
 pub mod pen;
 pub mod plugin;
+pub mod properties;
+pub mod reference_image;
+pub mod ruler;
 pub mod undo;
 
 pub use actions::*;
 pub use bevy_pen_tool_model::*;
+pub use clipboard::*;
 pub use io::*;
 pub use moves::*;
 pub use pen::*;
 pub use plugin::*;
+pub use properties::*;
+pub use reference_image::*;
+pub use ruler::*;
 pub use undo::*;
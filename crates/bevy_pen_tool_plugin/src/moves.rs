@@ -4,10 +4,11 @@ use bevy_pen_tool_model::inputs::Cursor;
 use bevy_pen_tool_model::materials::{BezierMidMat, SelectionMat};
 use bevy_pen_tool_model::mesh::{FillMesh2dMaterial, RoadMesh2dMaterial, StartMovingMesh};
 use bevy_pen_tool_model::model::{
-    AchorEdgeQuad, AnchorEdge, Bezier, BezierParent, BoundingBoxQuad, ControlPointQuad,
-    FollowBezierAnimation, Globals, Group, GroupMiddleQuad, MainUi, MiddlePointQuad, MovingAnchor,
-    TurnRoundAnimation, UiAction, UiBoard,
+    AchorEdgeQuad, AnchorEdge, Bezier, BezierAssets, BezierParent, BoundingBoxQuad,
+    ControlPointQuad, Globals, Group, GroupHandleKind, GroupMiddleQuad, MainUi, MiddlePointQuad,
+    MovingAnchor, PathMarkerQuad, TurnRoundAnimation, UiAction, UiBoard, WidthKeyframeQuad,
 };
+use bevy_pen_tool_model::{FollowerEvent, PathFollower, PathMarkerReached};
 
 use std::collections::HashMap;
 
@@ -230,6 +231,60 @@ pub fn move_end_quads(
     }
 }
 
+pub fn move_path_marker_quads(
+    groups: Res<Assets<Group>>,
+    bezier_curves: Res<Assets<Bezier>>,
+    mut query: Query<(&mut Transform, &Handle<Group>, &PathMarkerQuad)>,
+) {
+    let bezier_assets = bezier_curves
+        .iter()
+        .collect::<HashMap<bevy::asset::HandleId, &Bezier>>();
+    for (mut transform, group_handle, PathMarkerQuad(marker_id)) in query.iter_mut() {
+        let group = groups.get(group_handle);
+        if let Some(idx) = group.and_then(|group| group.marker_index(*marker_id)) {
+            place_group_handle_quad(
+                &mut transform,
+                group,
+                &bezier_assets,
+                GroupHandleKind::PathMarker,
+                idx,
+            );
+        }
+    }
+}
+
+pub fn move_width_keyframe_quads(
+    groups: Res<Assets<Group>>,
+    bezier_curves: Res<Assets<Bezier>>,
+    mut query: Query<(&mut Transform, &Handle<Group>, &WidthKeyframeQuad)>,
+) {
+    let bezier_assets = bezier_curves
+        .iter()
+        .collect::<HashMap<bevy::asset::HandleId, &Bezier>>();
+    for (mut transform, group_handle, WidthKeyframeQuad(idx)) in query.iter_mut() {
+        place_group_handle_quad(
+            &mut transform,
+            groups.get(group_handle),
+            &bezier_assets,
+            GroupHandleKind::WidthKeyframe,
+            *idx,
+        );
+    }
+}
+
+fn place_group_handle_quad(
+    transform: &mut Transform,
+    group: Option<&Group>,
+    bezier_assets: &BezierAssets,
+    kind: GroupHandleKind,
+    idx: usize,
+) {
+    if let Some(pos) = group.and_then(|group| group.handle_position(bezier_assets, kind, idx)) {
+        transform.translation.x = pos.x;
+        transform.translation.y = pos.y;
+    }
+}
+
 pub fn move_control_quads(
     mut commands: Commands,
     mut bezier_curves: ResMut<Assets<Bezier>>,
@@ -344,11 +399,10 @@ pub fn turn_round_animation(mut query: Query<(&mut Transform, &TurnRoundAnimatio
 //
 // // moves the helicopter along the Group path
 pub fn follow_bezier_group(
-    mut query: Query<(&mut Transform, &FollowBezierAnimation)>,
-    mut visible_query: Query<
-        &mut Visibility,
-        Or<(With<FollowBezierAnimation>, With<TurnRoundAnimation>)>,
-    >,
+    mut query: Query<(Entity, &mut Transform, &mut PathFollower)>,
+    mut visible_query: Query<&mut Visibility, Or<(With<PathFollower>, With<TurnRoundAnimation>)>>,
+    mut follower_event_writer: EventWriter<FollowerEvent>,
+    mut marker_event_writer: EventWriter<PathMarkerReached>,
     groups: Res<Assets<Group>>,
     curves: Res<Assets<Bezier>>,
     time: Res<Time>,
@@ -363,61 +417,36 @@ pub fn follow_bezier_group(
             .iter()
             .collect::<HashMap<bevy::asset::HandleId, &Bezier>>();
 
-        for (mut transform, bezier_animation) in query.iter_mut() {
-            let path_length = group.1.standalone_lut.path_length as f64;
-
-            let multiplier: f64 = 1500.0 / path_length;
-            let t_time = (bezier_animation.animation_offset
-                + time.seconds_since_startup() * (0.1 * multiplier))
-                % 1.0;
-            let mut pos = group.1.compute_position_with_lut(t_time as f32);
-
-            let road_line_offset = 4.0;
-            let normal = group
-                .1
-                .compute_normal_with_bezier(&bezier_assets, t_time as f64)
-                .normalize();
-            pos += normal * road_line_offset;
-
-            transform.translation.x = pos.x * globals.scale;
-            transform.translation.y = pos.y * globals.scale;
-
-            // the car looks ahead (5% of the curve length) to orient itself
-            let further_pos = group
-                .1
-                .compute_position_with_lut(((t_time + 0.05 * multiplier) % 1.0) as f32);
-            let further_normal = group
-                .1
-                .compute_normal_with_bezier(
-                    &bezier_assets,
-                    ((t_time + 0.05 * multiplier) % 1.0) as f64,
-                )
-                .normalize();
-
-            let forward_direction =
-                (further_pos + further_normal * road_line_offset - pos).normalize();
-
-            // let initial_rot = Quat::from_rotation_x(std::f32::consts::FRAC_PI_2);
-            // let forward_direction = initial_rot.mul_vec3(forward_direction0.extend(0.0));
-
-            let mut current_looking_dir = transform
-                .rotation
-                .mul_vec3(bezier_animation.initial_direction);
-            current_looking_dir.z = 0.0;
-
-            let quat = Quat::from_rotation_arc(current_looking_dir, forward_direction.extend(0.0));
-
-            let (axis, mut angle) = quat.to_axis_angle();
-            // println!(
-            //     "current_looking_dir: {:?}, forward_direction: {:?}",
-            //     current_looking_dir, forward_direction
-            // );
-
-            // maximum rotating speed
-            angle = angle.clamp(0.0, 3.0 * std::f32::consts::PI / 180.0);
-            let clamped_quat = Quat::from_axis_angle(axis, angle);
-
-            transform.rotation = clamped_quat.mul_quat(transform.rotation);
+        let path_length = group.1.standalone_lut.path_length;
+        if path_length <= 0.0 {
+            return;
+        }
+
+        for (entity, mut transform, mut follower) in query.iter_mut() {
+            let speed_multiplier = group.1.speed_multiplier_at(follower.group_t());
+
+            let (follower_events, marker_events) = follower.step(
+                entity,
+                &group.1.standalone_lut,
+                speed_multiplier,
+                time.delta_seconds(),
+            );
+            follower_event_writer.send_batch(follower_events.into_iter());
+            marker_event_writer.send_batch(marker_events.into_iter());
+
+            // the normals of the Bezier curves are more precise than the ones of the look-up table
+            follower.place(
+                &mut transform,
+                &group.1.standalone_lut,
+                |t| {
+                    group
+                        .1
+                        .compute_normal_with_bezier(&bezier_assets, t as f64)
+                        .normalize_or_zero()
+                },
+                time.delta_seconds(),
+                globals.scale,
+            );
         }
     }
 }
@@ -6,7 +6,7 @@ use bevy::prelude::*;
 use bevy_pen_tool_model::*;
 
 use rand::prelude::*;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 pub(crate) enum PenCommand {
     Spawn {
@@ -30,6 +30,52 @@ pub(crate) enum PenCommand {
         id: BezierId,
     },
 
+    SpeedRange {
+        id: BezierId,
+        t_min: f32,
+        t_max: f32,
+        multiplier: f32,
+    },
+
+    PathMarker {
+        id: BezierId,
+        name: String,
+        t: f32,
+    },
+
+    PathMarkerAtDistance {
+        id: BezierId,
+        name: String,
+        distance: f32,
+    },
+
+    WidthKeyframe {
+        id: BezierId,
+        t: f32,
+        width: f32,
+    },
+
+    WidthKeyframeAtDistance {
+        id: BezierId,
+        distance: f32,
+        width: f32,
+    },
+
+    LutSampling {
+        id: BezierId,
+        sampling: LutSampling,
+    },
+
+    LutTolerance {
+        id: BezierId,
+        tolerance: f32,
+    },
+
+    Transform {
+        ids: Vec<BezierId>,
+        transformation: Transformation,
+    },
+
     Undo,
     Redo,
 }
@@ -90,6 +136,88 @@ impl PenCommandVec {
         self.0.push(PenCommand::Unlatch { l1, l2 });
     }
 
+    /// Multiply the speed of path followers between two t-values of the group containing the given curve.
+    /// The speed profile is saved along with the group.
+    pub fn speed_range(&mut self, id: BezierId, t_min: f32, t_max: f32, multiplier: f32) {
+        self.0.push(PenCommand::SpeedRange {
+            id,
+            t_min,
+            t_max,
+            multiplier,
+        });
+    }
+
+    /// Place a named marker at a given t-value on the group containing the given curve.
+    /// Markers are saved with the group and exported in its look-up table.
+    pub fn path_marker(&mut self, id: BezierId, name: &str, t: f32) {
+        self.0.push(PenCommand::PathMarker {
+            id,
+            name: name.to_string(),
+            t,
+        });
+    }
+
+    /// Place a named marker at a given distance from the start of the group containing the given curve.
+    pub fn path_marker_at_distance(&mut self, id: BezierId, name: &str, distance: f32) {
+        self.0.push(PenCommand::PathMarkerAtDistance {
+            id,
+            name: name.to_string(),
+            distance,
+        });
+    }
+
+    /// Set the total width of the roads made along the group containing the given curve, at a given t-value.
+    /// Widths are interpolated smoothly between keyframes, and keyframes are saved with the group.
+    pub fn width_keyframe(&mut self, id: BezierId, t: f32, width: f32) {
+        self.0.push(PenCommand::WidthKeyframe { id, t, width });
+    }
+
+    /// Set the total road width at a given distance from the start of the group containing the given curve.
+    pub fn width_keyframe_at_distance(&mut self, id: BezierId, distance: f32, width: f32) {
+        self.0.push(PenCommand::WidthKeyframeAtDistance {
+            id,
+            distance,
+            width,
+        });
+    }
+
+    /// Choose how the points of the look-up table of the group containing the given curve are placed.
+    /// `LutSampling::ArcLength` spaces them evenly along the path, for constant-speed followers.
+    pub fn lut_sampling(&mut self, id: BezierId, sampling: LutSampling) {
+        self.0.push(PenCommand::LutSampling { id, sampling });
+    }
+
+    /// Set the maximum distance between the path and the adaptive look-up table of the group
+    /// containing the given curve. Smaller tolerances give more points. The tolerance is saved with the group.
+    pub fn lut_tolerance(&mut self, id: BezierId, tolerance: f32) {
+        self.0.push(PenCommand::LutTolerance { id, tolerance });
+    }
+
+    /// Flip the given curves horizontally, around the center of their bounding box.
+    /// The curves latched to them follow the flipped anchors.
+    pub fn flip_horizontally(&mut self, ids: &[BezierId]) {
+        self.0.push(PenCommand::Transform {
+            ids: ids.to_vec(),
+            transformation: Transformation::FlipHorizontally,
+        });
+    }
+
+    /// Flip the given curves vertically, around the center of their bounding box.
+    pub fn flip_vertically(&mut self, ids: &[BezierId]) {
+        self.0.push(PenCommand::Transform {
+            ids: ids.to_vec(),
+            transformation: Transformation::FlipVertically,
+        });
+    }
+
+    /// Rotate the given curves counterclockwise by an angle in degrees, around the center of their bounding box.
+    pub fn rotate(&mut self, ids: &[BezierId], angle: f32) {
+        self.0.push(PenCommand::Transform {
+            ids: ids.to_vec(),
+            transformation: Transformation::Rotate(angle),
+        });
+    }
+
     /// Undo a command. Useful for internal tests, but not very useful for users of the API.
     pub fn undo(&mut self) {
         self.0.push(PenCommand::Undo);
@@ -131,6 +259,147 @@ pub(crate) fn move_anchor(
     }
 }
 
+// moves a set of anchors and control points to their new positions, or back to their previous
+// ones. The curves moved as a whole get all their positions at once, and only their latched
+// partners outside of the set follow them. For the other curves, the anchors go first, as they
+// carry their control points along
+pub(crate) fn move_anchors(
+    commands: &mut Commands,
+    moves: &Vec<AnchorMove>,
+    to_previous: bool,
+    bezier_curves: &mut ResMut<Assets<Bezier>>,
+    maps: &ResMut<Maps>,
+) {
+    let mut positions_per_curve: HashMap<BezierId, HashMap<Anchor, Vec2>> = HashMap::new();
+    for anchor_move in moves.iter() {
+        let position = if to_previous {
+            anchor_move.previous_position
+        } else {
+            anchor_move.new_position
+        };
+        positions_per_curve
+            .entry(anchor_move.bezier_id.into())
+            .or_default()
+            .insert(anchor_move.anchor, position);
+    }
+
+    let whole_curves: HashMap<BezierId, BezierPositions> = positions_per_curve
+        .iter()
+        .filter(|(id, _)| maps.bezier_map.contains_key(*id))
+        .filter_map(|(id, positions)| {
+            let positions = BezierPositions {
+                start: *positions.get(&Anchor::Start)?,
+                end: *positions.get(&Anchor::End)?,
+                control_start: *positions.get(&Anchor::ControlStart)?,
+                control_end: *positions.get(&Anchor::ControlEnd)?,
+            };
+            Some((*id, positions))
+        })
+        .collect();
+
+    for (id, positions) in whole_curves.iter() {
+        let bezier = bezier_curves.get_mut(&maps.bezier_map[id].handle).unwrap();
+        bezier.positions = *positions;
+
+        // updates the quads of the anchors and control points, and of the latched partners
+        bezier.move_anchor(commands, true, false, Anchor::Start, maps.as_ref());
+        bezier.move_anchor(commands, true, false, Anchor::End, maps.as_ref());
+    }
+
+    for id in whole_curves.keys() {
+        let bezier = bezier_curves
+            .get(&maps.bezier_map[id].handle)
+            .unwrap()
+            .clone();
+        for latch in bezier.latches.values() {
+            if !whole_curves.contains_key(&latch.latched_to_id) {
+                let latch_info = bezier.get_anchor_latch_info(latch.self_edge.to_anchor());
+                update_latched_partner_position(&maps.bezier_map, bezier_curves, latch_info);
+            }
+        }
+    }
+
+    let (edges, controls): (Vec<&AnchorMove>, Vec<&AnchorMove>) = moves
+        .iter()
+        .filter(|anchor_move| !whole_curves.contains_key(&BezierId::from(anchor_move.bezier_id)))
+        .partition(|anchor_move| anchor_move.anchor.is_edge());
+
+    for anchor_move in edges.into_iter().chain(controls.into_iter()) {
+        let id: BezierId = anchor_move.bezier_id.into();
+        // the curve may have been deleted since
+        if !maps.bezier_map.contains_key(&id) {
+            continue;
+        }
+
+        let move_command = MoveCommand {
+            id,
+            anchor: anchor_move.anchor,
+            new_position: if to_previous {
+                anchor_move.previous_position
+            } else {
+                anchor_move.new_position
+            },
+        };
+        move_anchor(commands, move_command, bezier_curves, maps);
+    }
+}
+
+// moves of all the anchors and control points of a set of curves, flipped or rotated around
+// the center of their common bounding box
+pub(crate) fn transformed_anchors(
+    ids: &HashSet<BezierId>,
+    transformation: Transformation,
+    bezier_curves: &Assets<Bezier>,
+    maps: &Maps,
+) -> Vec<AnchorMove> {
+    let beziers: Vec<&Bezier> = ids
+        .iter()
+        .filter_map(|id| maps.bezier_map.get(id))
+        .filter_map(|handle_entity| bezier_curves.get(&handle_entity.handle))
+        .collect();
+
+    let bounds = beziers
+        .iter()
+        .map(|bezier| bezier.bounding_box())
+        .reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)));
+    let center = match bounds {
+        Some((min, max)) => (min + max) / 2.0,
+        None => return Vec::new(),
+    };
+
+    let mut moves = Vec::new();
+    for bezier in beziers {
+        for anchor in [
+            Anchor::Start,
+            Anchor::End,
+            Anchor::ControlStart,
+            Anchor::ControlEnd,
+        ] {
+            let previous_position = bezier.get_position(anchor);
+            moves.push(AnchorMove {
+                bezier_id: bezier.id.into(),
+                anchor,
+                previous_position,
+                new_position: transformation.apply(center, previous_position),
+            });
+        }
+    }
+    moves
+}
+
+// finds the group containing a given curve
+fn group_of_curve<'a>(
+    id: BezierId,
+    bezier_curves: &ResMut<Assets<Bezier>>,
+    groups: &'a mut ResMut<Assets<Group>>,
+    maps: &ResMut<Maps>,
+) -> Option<&'a mut Group> {
+    let handle_entity = maps.bezier_map.get(&id)?;
+    let group_id = bezier_curves.get(&handle_entity.handle)?.group;
+    let group_handle = maps.group_map.get(&group_id)?;
+    groups.get_mut(group_handle)
+}
+
 pub(crate) struct PenApiPlugin;
 
 impl Plugin for PenApiPlugin {
@@ -143,6 +412,7 @@ impl Plugin for PenApiPlugin {
 fn direct_api_calls(
     mut commands: Commands,
     mut bezier_curves: ResMut<Assets<Bezier>>,
+    mut groups: ResMut<Assets<Group>>,
     // mut spawn_curve_event_reader: EventReader<SpawnCurve>,
     mut spawning_curve_event_writer: EventWriter<SpawningCurve>,
     mut pen_command_vec: ResMut<PenCommandVec>,
@@ -191,7 +461,11 @@ fn direct_api_calls(
                     info!("unlatch");
                     let handle_entity_1 = maps.bezier_map[&l1.id.into()].clone();
                     let bezier_1 = bezier_curves.get_mut(&handle_entity_1.handle).unwrap();
-                    bezier_1.latches.remove(&l1.anchor_edge);
+                    let corner = bezier_1
+                        .latches
+                        .remove(&l1.anchor_edge)
+                        .map(|latch| latch.corner)
+                        .unwrap_or(false);
 
                     let handle_entity_2 = maps.bezier_map[&l2.id.into()].clone();
                     let bezier_2 = bezier_curves.get_mut(&handle_entity_2.handle).unwrap();
@@ -202,6 +476,7 @@ fn direct_api_calls(
                         partner_id: l2.id.into(),
                         self_anchor: l1.anchor_edge,
                         partner_anchor: l2.anchor_edge,
+                        corner,
                     });
                 }
                 PenCommand::Delete { id } => {
@@ -218,6 +493,66 @@ fn direct_api_calls(
                         info!("COULD NOT DELETE CURVE FROM MAP: {:?}", id);
                     }
                 }
+                PenCommand::SpeedRange {
+                    id,
+                    t_min,
+                    t_max,
+                    multiplier,
+                } => {
+                    if let Some(group) = group_of_curve(*id, &bezier_curves, &mut groups, &maps) {
+                        group.add_speed_range(*t_min, *t_max, *multiplier);
+                    }
+                }
+                PenCommand::PathMarker { id, name, t } => {
+                    if let Some(group) = group_of_curve(*id, &bezier_curves, &mut groups, &maps) {
+                        group.add_marker(name, *t);
+                    }
+                }
+                PenCommand::PathMarkerAtDistance { id, name, distance } => {
+                    if let Some(group) = group_of_curve(*id, &bezier_curves, &mut groups, &maps) {
+                        group.add_marker_at_distance(name, *distance);
+                    }
+                }
+                PenCommand::WidthKeyframe { id, t, width } => {
+                    if let Some(group) = group_of_curve(*id, &bezier_curves, &mut groups, &maps) {
+                        group.add_width_keyframe(*t, *width);
+                    }
+                }
+                PenCommand::WidthKeyframeAtDistance {
+                    id,
+                    distance,
+                    width,
+                } => {
+                    if let Some(group) = group_of_curve(*id, &bezier_curves, &mut groups, &maps) {
+                        group.add_width_keyframe_at_distance(*distance, *width);
+                    }
+                }
+                PenCommand::LutSampling { id, sampling } => {
+                    if let Some(group) = group_of_curve(*id, &bezier_curves, &mut groups, &maps) {
+                        group.lut_sampling = *sampling;
+                        action_event_writer.send(Action::ComputeLut);
+                    }
+                }
+                PenCommand::LutTolerance { id, tolerance } => {
+                    if let Some(group) = group_of_curve(*id, &bezier_curves, &mut groups, &maps) {
+                        group.lut_tolerance = *tolerance;
+                        action_event_writer.send(Action::ComputeLut);
+                    }
+                }
+                PenCommand::Transform {
+                    ids,
+                    transformation,
+                } => {
+                    let ids = ids.iter().cloned().collect();
+                    let moves = transformed_anchors(&ids, *transformation, &bezier_curves, &maps);
+                    if !moves.is_empty() {
+                        move_anchors(&mut commands, &moves, false, &mut bezier_curves, &maps);
+                        add_to_history_event_writer.send(HistoryAction::MovedSelection {
+                            moves,
+                            mesh_moves: Vec::new(),
+                        });
+                    }
+                }
                 PenCommand::Undo => {
                     action_event_writer.send(Action::Undo);
                 }
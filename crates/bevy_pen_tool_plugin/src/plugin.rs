@@ -1,10 +1,15 @@
 use crate::actions::*;
-use crate::io::{load, load_mesh, save};
+use crate::clipboard::*;
+use crate::io::{load, load_mesh, load_reference_image, save};
 use crate::moves::*;
 use crate::pen::*;
+use crate::properties::*;
+use crate::reference_image::*;
+use crate::ruler::*;
 use crate::undo::*;
 
 use bevy::prelude::*;
+use bevy_egui::{EguiContext, EguiPlugin};
 use bevy_obj::*;
 use bevy_pen_tool_model::*;
 pub struct BevyPenToolPlugin;
@@ -37,6 +42,11 @@ pub struct BevyPenToolPlugin;
 
 impl Plugin for BevyPenToolPlugin {
     fn build(&self, app: &mut App) {
+        // the labels of the ruler are drawn with egui, which the app may already use
+        if !app.world.contains_resource::<EguiContext>() {
+            app.add_plugin(EguiPlugin);
+        }
+
         app.add_plugin(PenApiPlugin)
             .add_plugin(ObjPlugin)
             .add_plugin(SpawnerPlugin)
@@ -54,6 +64,11 @@ impl Plugin for BevyPenToolPlugin {
             .add_system(unlatchy)
             .add_system(compute_group_lut)
             .add_system(load_mesh)
+            .add_system(regenerate_linked_meshes)
+            .add_system(draw_ruler_labels)
+            .add_system(show_selected_anchors)
+            .add_system(properties_panel)
+            .add_system(reference_image_panel)
             //
             // Update model
             .add_system_set(
@@ -65,8 +80,28 @@ impl Plugin for BevyPenToolPlugin {
                     .with_system(officiate_latch_partnership)
                     .with_system(selection_box_init)
                     .with_system(selection_area_finalize)
+                    .with_system(toggle_selected_items)
+                    .with_system(edit_selection)
+                    .with_system(nudge_selection)
+                    .with_system(align_selection)
+                    .with_system(transform_selection)
                     .with_system(hide_anchors)
                     .with_system(delete)
+                    .with_system(edit_path_markers)
+                    .with_system(edit_width_keyframes)
+                    .with_system(bake_mesh)
+                    .with_system(duplicate_mesh)
+                    .with_system(duplicate_curves)
+                    .with_system(copy_curves)
+                    .with_system(delete_cut_curves)
+                    .with_system(paste_curves)
+                    .with_system(recolor_selected_meshes)
+                    .with_system(toggle_snapping)
+                    .with_system(toggle_ruler)
+                    .with_system(toggle_symmetry)
+                    .with_system(mirror_symmetric_curves)
+                    .with_system(toggle_shape_tool)
+                    .with_system(spawn_shape)
                     .with_system(hide_control_points)
                     .with_system(unselect)
                     .with_system(undo)
@@ -80,6 +115,7 @@ impl Plugin for BevyPenToolPlugin {
                 SystemSet::on_update("ModelViewController")
                     .with_system(load)
                     .with_system(save)
+                    .with_system(load_reference_image)
                     .after("model"),
             )
             //
@@ -90,6 +126,8 @@ impl Plugin for BevyPenToolPlugin {
                     .with_system(move_end_quads)
                     .with_system(move_middle_quads)
                     .with_system(move_group_middle_quads)
+                    .with_system(move_path_marker_quads)
+                    .with_system(move_width_keyframe_quads)
                     .with_system(move_control_quads)
                     .with_system(move_bb_quads)
                     .with_system(move_ui)
@@ -0,0 +1,312 @@
+use crate::pen::{move_anchor, MoveCommand};
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use bevy_pen_tool_model::*;
+
+const ANCHORS: [(&str, Anchor); 4] = [
+    ("start", Anchor::Start),
+    ("control start", Anchor::ControlStart),
+    ("control end", Anchor::ControlEnd),
+    ("end", Anchor::End),
+];
+
+// flips and rotations of the selected curves, around the center of their bounding box
+fn transform_buttons(
+    ui: &mut egui::Ui,
+    rotation_angle: &mut f32,
+    action_event_writer: &mut EventWriter<Action>,
+) {
+    ui.horizontal(|ui| {
+        if ui.button("flip horizontally").clicked() {
+            action_event_writer.send(Action::Transform(Transformation::FlipHorizontally));
+        }
+        if ui.button("flip vertically").clicked() {
+            action_event_writer.send(Action::Transform(Transformation::FlipVertically));
+        }
+    });
+    ui.horizontal(|ui| {
+        for angle in [90.0, -90.0, 180.0] {
+            if ui.button(format!("rotate {}°", angle)).clicked() {
+                action_event_writer.send(Action::Transform(Transformation::Rotate(angle)));
+            }
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::DragValue::new(&mut *rotation_angle)
+                .speed(1.0)
+                .suffix("°"),
+        );
+        if ui.button("rotate").clicked() {
+            action_event_writer.send(Action::Transform(Transformation::Rotate(*rotation_angle)));
+        }
+    });
+}
+
+// proportions of the shapes drawn with the shape tools
+fn shape_tool_settings(ui: &mut egui::Ui, shape_tool: &mut ShapeTool) {
+    egui::Grid::new("shape_tool").show(ui, |ui| {
+        ui.label("corner radius");
+        ui.add(
+            egui::DragValue::new(&mut shape_tool.corner_radius)
+                .speed(0.5)
+                .clamp_range(0.0..=f32::MAX),
+        );
+        ui.end_row();
+
+        ui.label("polygon sides");
+        ui.add(egui::DragValue::new(&mut shape_tool.polygon_sides).clamp_range(3..=64));
+        ui.end_row();
+
+        ui.label("star points");
+        ui.add(egui::DragValue::new(&mut shape_tool.star_points).clamp_range(3..=64));
+        ui.end_row();
+
+        ui.label("star inner ratio");
+        ui.add(
+            egui::DragValue::new(&mut shape_tool.star_inner_ratio)
+                .speed(0.01)
+                .clamp_range(0.0..=1.0),
+        );
+        ui.end_row();
+    });
+}
+
+// center of the symmetry, along with the axis angle or the number of radial copies
+fn symmetry_settings(ui: &mut egui::Ui, symmetry: &mut Symmetry) {
+    egui::Grid::new("symmetry").show(ui, |ui| {
+        ui.label("center");
+        ui.add(
+            egui::DragValue::new(&mut symmetry.center.x)
+                .speed(0.5)
+                .prefix("x "),
+        );
+        ui.add(
+            egui::DragValue::new(&mut symmetry.center.y)
+                .speed(0.5)
+                .prefix("y "),
+        );
+        ui.end_row();
+
+        match symmetry.mode {
+            SymmetryMode::Line => {
+                ui.label("axis angle");
+                ui.add(
+                    egui::DragValue::new(&mut symmetry.angle)
+                        .speed(1.0)
+                        .suffix("°"),
+                );
+                ui.end_row();
+            }
+            SymmetryMode::Radial => {
+                ui.label("radial copies");
+                ui.add(egui::DragValue::new(&mut symmetry.radial_copies).clamp_range(2..=64));
+                ui.end_row();
+            }
+            _ => {}
+        }
+    });
+}
+
+// curve shown in the panel: the only selected curve, or the curve of the selected anchors
+fn edited_curve(selection: &Selection) -> Option<BezierId> {
+    let mut curves = selection.curve_ids();
+    curves.extend(selection.anchors().iter().map(|(bezier_id, _)| *bezier_id));
+    if curves.len() == 1 {
+        curves.into_iter().next()
+    } else {
+        None
+    }
+}
+
+// exact positions of the selected curve, along with the length and look-up table of its group.
+// The positions are edited in the panel, and each edit goes into the history once the value
+// is no longer being dragged or typed. The settings of the shape tool and of the symmetry are
+// shown while they are on
+pub fn properties_panel(
+    mut commands: Commands,
+    mut egui_context: ResMut<EguiContext>,
+    selection: Res<Selection>,
+    mut bezier_curves: ResMut<Assets<Bezier>>,
+    groups: Res<Assets<Group>>,
+    maps: ResMut<Maps>,
+    mut globals: ResMut<Globals>,
+    mut add_to_history_event_writer: EventWriter<HistoryAction>,
+    mut action_event_writer: EventWriter<Action>,
+    // counterclockwise angle of the "rotate" button, in degrees
+    mut rotation_angle: Local<f32>,
+    // curve being edited, with its positions before the edit
+    mut editing: Local<Option<(BezierId, Vec<(Anchor, Vec2)>)>>,
+) {
+    let ctx = egui_context.ctx_mut();
+
+    //
+    // record the finished edit
+    if !ctx.is_using_pointer() && !ctx.wants_keyboard_input() {
+        if let Some((bezier_id, previous_positions)) = editing.take() {
+            if let Some(bezier) = maps
+                .bezier_map
+                .get(&bezier_id)
+                .and_then(|handle_entity| bezier_curves.get(&handle_entity.handle))
+            {
+                let moves: Vec<AnchorMove> = previous_positions
+                    .iter()
+                    .filter(|(anchor, previous_position)| {
+                        bezier.get_position(*anchor) != *previous_position
+                    })
+                    .map(|(anchor, previous_position)| AnchorMove {
+                        bezier_id: bezier_id.into(),
+                        anchor: *anchor,
+                        previous_position: *previous_position,
+                        new_position: bezier.get_position(*anchor),
+                    })
+                    .collect();
+
+                if !moves.is_empty() {
+                    add_to_history_event_writer.send(HistoryAction::MovedSelection {
+                        moves,
+                        mesh_moves: Vec::new(),
+                    });
+                }
+            }
+        }
+    }
+
+    let has_selection = !selection.curve_ids().is_empty() || !selection.anchors().is_empty();
+    let shape_tool_on = globals.shape_tool.kind.is_some();
+    let symmetry_on = globals.symmetry.mode != SymmetryMode::Off;
+    if !has_selection && !shape_tool_on && !symmetry_on {
+        return;
+    }
+
+    let bezier_id = edited_curve(&selection);
+    let bezier = bezier_id
+        .and_then(|bezier_id| maps.bezier_map.get(&bezier_id))
+        .and_then(|handle_entity| bezier_curves.get(&handle_entity.handle));
+
+    let mut new_positions: Vec<(Anchor, Vec2)> = Vec::new();
+
+    egui::Window::new("Properties")
+        .resizable(false)
+        .show(ctx, |ui| {
+            if let Some(kind) = globals.shape_tool.kind {
+                ui.label(format!("{:?} tool", kind));
+                shape_tool_settings(ui, &mut globals.shape_tool);
+                if symmetry_on || has_selection {
+                    ui.separator();
+                }
+            }
+
+            if symmetry_on {
+                ui.label(format!("{:?} symmetry", globals.symmetry.mode));
+                symmetry_settings(ui, &mut globals.symmetry);
+                if has_selection {
+                    ui.separator();
+                }
+            }
+
+            if !has_selection {
+                return;
+            }
+
+            let bezier = match bezier {
+                Some(bezier) => bezier,
+                None => {
+                    let num_curves = selection.curve_ids().len();
+                    ui.label(format!("{} curves selected", num_curves));
+                    if num_curves > 0 {
+                        ui.separator();
+                        transform_buttons(ui, &mut rotation_angle, &mut action_event_writer);
+                    }
+                    return;
+                }
+            };
+
+            egui::Grid::new("bezier_positions").show(ui, |ui| {
+                for (name, anchor) in ANCHORS {
+                    let mut position = bezier.get_position(anchor);
+                    ui.label(name);
+                    let changed_x = ui
+                        .add(
+                            egui::DragValue::new(&mut position.x)
+                                .speed(0.5)
+                                .prefix("x "),
+                        )
+                        .changed();
+                    let changed_y = ui
+                        .add(
+                            egui::DragValue::new(&mut position.y)
+                                .speed(0.5)
+                                .prefix("y "),
+                        )
+                        .changed();
+                    if changed_x || changed_y {
+                        new_positions.push((anchor, position));
+                    }
+                    ui.end_row();
+                }
+            });
+
+            ui.separator();
+            ui.label(format!("curve length: {:.2}", bezier.length()));
+
+            if let Some(group) = maps
+                .group_map
+                .get(&bezier.group)
+                .and_then(|handle| groups.get(handle))
+            {
+                // the look-up table of the group may not be computed yet
+                let group_length = if group.standalone_lut.path_length > 0.0 {
+                    group.standalone_lut.path_length
+                } else {
+                    group
+                        .bezier_handles
+                        .iter()
+                        .filter_map(|handle| bezier_curves.get(handle))
+                        .map(|bezier| bezier.length())
+                        .sum()
+                };
+                ui.label(format!("group path length: {:.2}", group_length));
+                ui.label(format!(
+                    "group look-up table points: {}",
+                    group.standalone_lut.lut.len()
+                ));
+            }
+
+            if !selection.curve_ids().is_empty() {
+                ui.separator();
+                transform_buttons(ui, &mut rotation_angle, &mut action_event_writer);
+            }
+        });
+
+    //
+    // apply the edit
+    let bezier_id = match bezier_id {
+        Some(bezier_id) if !new_positions.is_empty() => bezier_id,
+        _ => return,
+    };
+
+    if editing.is_none() {
+        if let Some(bezier) = maps
+            .bezier_map
+            .get(&bezier_id)
+            .and_then(|handle_entity| bezier_curves.get(&handle_entity.handle))
+        {
+            let previous_positions = ANCHORS
+                .iter()
+                .map(|(_, anchor)| (*anchor, bezier.get_position(*anchor)))
+                .collect();
+            *editing = Some((bezier_id, previous_positions));
+        }
+    }
+
+    for (anchor, new_position) in new_positions {
+        let move_command = MoveCommand {
+            id: bezier_id,
+            anchor,
+            new_position,
+        };
+        move_anchor(&mut commands, move_command, &mut bezier_curves, &maps);
+    }
+}
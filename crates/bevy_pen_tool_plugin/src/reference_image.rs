@@ -0,0 +1,87 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use bevy_pen_tool_model::*;
+
+// position, scale, rotation and opacity of the reference image. Locking the image keeps these
+// settings from being changed by accident while tracing
+pub fn reference_image_panel(
+    mut egui_context: ResMut<EguiContext>,
+    mut reference_image: ResMut<ReferenceImage>,
+    mut action_event_writer: EventWriter<Action>,
+) {
+    if reference_image.path.is_none() {
+        return;
+    }
+
+    let mut settings = reference_image.clone();
+    let mut do_remove = false;
+
+    egui::Window::new("Reference image")
+        .resizable(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.checkbox(&mut settings.visible, "visible");
+            ui.checkbox(&mut settings.locked, "locked");
+
+            ui.add_enabled_ui(!settings.locked, |ui| {
+                egui::Grid::new("reference_image_settings").show(ui, |ui| {
+                    ui.label("position");
+                    ui.add(
+                        egui::DragValue::new(&mut settings.position.x)
+                            .speed(1.0)
+                            .prefix("x "),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut settings.position.y)
+                            .speed(1.0)
+                            .prefix("y "),
+                    );
+                    ui.end_row();
+
+                    ui.label("scale");
+                    ui.add(
+                        egui::DragValue::new(&mut settings.scale)
+                            .speed(0.01)
+                            .clamp_range(0.01..=100.0),
+                    );
+                    ui.end_row();
+
+                    ui.label("rotation");
+                    ui.add(
+                        egui::DragValue::new(&mut settings.rotation)
+                            .speed(1.0)
+                            .suffix("°"),
+                    );
+                    ui.end_row();
+
+                    ui.label("opacity");
+                    ui.add(egui::Slider::new(&mut settings.opacity, 0.0..=1.0));
+                    ui.end_row();
+                });
+
+                ui.horizontal(|ui| {
+                    if ui.button("replace").clicked() {
+                        action_event_writer.send(Action::LoadReferenceImage);
+                    }
+                    if ui.button("remove").clicked() {
+                        do_remove = true;
+                    }
+                });
+            });
+        });
+
+    if do_remove {
+        *reference_image = ReferenceImage::default();
+        return;
+    }
+
+    // only actual edits mark the resource as changed
+    if settings.visible != reference_image.visible
+        || settings.locked != reference_image.locked
+        || settings.position != reference_image.position
+        || settings.scale != reference_image.scale
+        || settings.rotation != reference_image.rotation
+        || settings.opacity != reference_image.opacity
+    {
+        *reference_image = settings;
+    }
+}
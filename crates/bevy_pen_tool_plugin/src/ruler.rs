@@ -0,0 +1,47 @@
+use bevy::{prelude::*, render::camera::OrthographicProjection};
+use bevy_egui::{egui, EguiContext};
+use bevy_pen_tool_model::*;
+
+// writes the measurements of the ruler next to what they measure. The labels are positioned
+// on the canvas, so they follow the camera and globals.scale
+pub fn draw_ruler_labels(
+    mut egui_context: ResMut<EguiContext>,
+    ruler: Res<Ruler>,
+    globals: Res<Globals>,
+    windows: Res<Windows>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<OrthographicProjection>>,
+) {
+    if !ruler.active || ruler.labels.is_empty() {
+        return;
+    }
+
+    let (camera, camera_transform) = match camera_query.iter().next() {
+        Some(camera) => camera,
+        None => return,
+    };
+    let window_height = match windows.get_primary() {
+        Some(window) => window.height(),
+        None => return,
+    };
+
+    let ctx = egui_context.ctx_mut();
+    for (idx, (position, text)) in ruler.labels.iter().enumerate() {
+        let world_position = (*position * globals.scale).extend(0.0);
+        if let Some(screen_position) = camera.world_to_viewport(camera_transform, world_position) {
+            // egui measures y from the top of the window
+            egui::Area::new(format!("ruler_label_{}", idx))
+                .fixed_pos(egui::pos2(
+                    screen_position.x,
+                    window_height - screen_position.y,
+                ))
+                .interactable(false)
+                .show(ctx, |ui| {
+                    ui.label(
+                        egui::RichText::new(text)
+                            .color(egui::Color32::from_rgb(255, 217, 77))
+                            .background_color(egui::Color32::from_black_alpha(160)),
+                    );
+                });
+        }
+    }
+}
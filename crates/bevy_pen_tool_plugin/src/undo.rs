@@ -1,10 +1,16 @@
 use bevy_pen_tool_model::inputs::Action;
 
+use bevy_pen_tool_model::mesh::{
+    free_stashed_mesh, restore_mesh, stash_mesh, FillMesh2dMaterial, MeshId, MeshSource, PenMesh,
+    RoadBandColor, RoadMesh2dMaterial, RoadTint,
+};
 use bevy_pen_tool_model::model::*;
+use bevy_pen_tool_model::PathMarker;
 
+use crate::actions::{place_mesh, recolor_mesh};
 use crate::pen::*;
 
-use bevy::prelude::*;
+use bevy::{prelude::*, sprite::Mesh2dHandle};
 
 use bevy_inspector_egui::Inspectable;
 
@@ -74,6 +80,37 @@ pub enum HistoryActionInspector {
         self_id: BezierHistId,
         partner_bezier_id: BezierHistId,
     },
+    SpawnedMesh {
+        mesh_id: MeshId,
+    },
+    DeletedMesh {
+        mesh_id: MeshId,
+    },
+    MovedMesh {
+        mesh_id: MeshId,
+    },
+    RecoloredMesh {
+        mesh_id: MeshId,
+    },
+    SpawnedGroups {
+        bezier_ids: Vec<BezierHistId>,
+    },
+    MovedSelection {
+        bezier_ids: Vec<BezierHistId>,
+        mesh_ids: Vec<MeshId>,
+    },
+    AddedPathMarker {
+        group_id: GroupId,
+        marker_id: PathMarkerId,
+    },
+    RemovedPathMarker {
+        group_id: GroupId,
+        marker_id: PathMarkerId,
+    },
+    MovedPathMarker {
+        group_id: GroupId,
+        marker_id: PathMarkerId,
+    },
     None,
 }
 
@@ -105,6 +142,57 @@ impl From<HistoryAction> for HistoryActionInspector {
                 self_id,
                 partner_bezier_id,
             },
+            HistoryAction::SpawnedMesh { mesh_id } => {
+                HistoryActionInspector::SpawnedMesh { mesh_id }
+            }
+            HistoryAction::DeletedMesh { mesh_id } => {
+                HistoryActionInspector::DeletedMesh { mesh_id }
+            }
+            HistoryAction::MovedMesh { mesh_id, .. } => {
+                HistoryActionInspector::MovedMesh { mesh_id }
+            }
+            HistoryAction::RecoloredMesh { mesh_id, .. } => {
+                HistoryActionInspector::RecoloredMesh { mesh_id }
+            }
+            HistoryAction::SpawnedGroups { groups } => HistoryActionInspector::SpawnedGroups {
+                bezier_ids: groups.iter().flatten().map(|hist| hist.id).collect(),
+            },
+            HistoryAction::MovedSelection { moves, mesh_moves } => {
+                HistoryActionInspector::MovedSelection {
+                    bezier_ids: moves
+                        .iter()
+                        .map(|anchor_move| anchor_move.bezier_id)
+                        .collect(),
+                    mesh_ids: mesh_moves
+                        .iter()
+                        .map(|mesh_move| mesh_move.mesh_id)
+                        .collect(),
+                }
+            }
+            HistoryAction::AddedPathMarker {
+                group_id,
+                marker_id,
+                ..
+            } => HistoryActionInspector::AddedPathMarker {
+                group_id,
+                marker_id,
+            },
+            HistoryAction::RemovedPathMarker {
+                group_id,
+                marker_id,
+                ..
+            } => HistoryActionInspector::RemovedPathMarker {
+                group_id,
+                marker_id,
+            },
+            HistoryAction::MovedPathMarker {
+                group_id,
+                marker_id,
+                ..
+            } => HistoryActionInspector::MovedPathMarker {
+                group_id,
+                marker_id,
+            },
 
             HistoryAction::None => HistoryActionInspector::None,
         }
@@ -136,6 +224,7 @@ pub fn latch_curves(
         latched_to_id: bezier_id_2.into(),
         self_edge: anchor_1,
         partners_edge: anchor_2,
+        corner: false,
     };
 
     bezier_1.do_compute_lut = true;
@@ -165,6 +254,7 @@ pub fn latch_curves(
         latched_to_id: bezier_id_1.into(),
         self_edge: anchor_2,
         partners_edge: anchor_1,
+        corner: false,
     };
 
     bezier_2.do_compute_lut = true;
@@ -180,16 +270,111 @@ pub fn latch_curves(
     );
 }
 
+// hides a mesh, as deleting it does
+fn stash_mesh_by_id(
+    commands: &mut Commands,
+    maps: &mut Maps,
+    mesh_id: MeshId,
+    pen_mesh_query: &Query<&PenMesh>,
+) {
+    if let Some(entity) = maps.mesh_map.get(&mesh_id).cloned() {
+        if let Ok(pen_mesh) = pen_mesh_query.get(entity) {
+            stash_mesh(commands, maps, pen_mesh);
+        }
+    }
+}
+
+// despawns spawned groups of curves, along with their group entities
+fn despawn_groups(
+    commands: &mut Commands,
+    bezier_hist_groups: &Vec<Vec<BezierHist>>,
+    bezier_curves: &Assets<Bezier>,
+    groups: &Assets<Group>,
+    maps: &Maps,
+) {
+    let mut group_entities = HashSet::new();
+    for bezier_hist in bezier_hist_groups.iter().flatten() {
+        if let Some(handle_entity) = maps.bezier_map.get(&bezier_hist.id.into()) {
+            if let Some(bezier) = bezier_curves.get(&handle_entity.handle) {
+                if let Some(group_handle) = maps.group_map.get(&bezier.group) {
+                    if let Some(group_entity) = groups.get(group_handle).and_then(|g| g.entity) {
+                        group_entities.insert(group_entity);
+                    }
+                }
+            }
+            commands.entity(handle_entity.entity).despawn_recursive();
+        }
+    }
+    for group_entity in group_entities {
+        commands.entity(group_entity).despawn_recursive();
+    }
+}
+
+// group of a history entry, if it still exists
+fn history_group<'a>(
+    groups: &'a mut Assets<Group>,
+    maps: &Maps,
+    group_id: GroupId,
+) -> Option<&'a mut Group> {
+    let group_handle = maps.group_map.get(&group_id)?;
+    return groups.get_mut(group_handle);
+}
+
+// adds the copies made by the symmetry tool to the entry that spawned their original
+fn merge_symmetric_copies(
+    history: &mut History,
+    original_id: BezierHistId,
+    copy_groups: &Vec<Vec<BezierHist>>,
+) {
+    let head_len = (history.index + 1) as usize;
+    for action in history.actions[0..head_len].iter_mut().rev() {
+        match action {
+            HistoryAction::SpawnedCurve {
+                bezier_id, copies, ..
+            } if *bezier_id == original_id => {
+                copies.extend(copy_groups.iter().cloned());
+                return;
+            }
+            HistoryAction::SpawnedGroups { groups }
+                if groups.iter().flatten().any(|hist| hist.id == original_id) =>
+            {
+                groups.extend(copy_groups.iter().cloned());
+                return;
+            }
+            _ => {}
+        }
+    }
+}
+
 pub fn undo(
     mut commands: Commands,
     mut history: ResMut<History>,
     mut bezier_curves: ResMut<Assets<Bezier>>,
-    groups: Res<Assets<Group>>,
+    mut groups: ResMut<Assets<Group>>,
     mut action_event_reader: EventReader<Action>,
-    maps: ResMut<Maps>,
+    mut maps: ResMut<Maps>,
     mut spawn_curve_event_writer: EventWriter<SpawningCurve>,
     audio: Res<Audio>,
     globals: ResMut<Globals>,
+    pen_mesh_query: Query<&PenMesh>,
+    mut position_query: Query<(
+        &mut Transform,
+        Option<&Handle<FillMesh2dMaterial>>,
+        Option<&Handle<RoadMesh2dMaterial>>,
+    )>,
+    // queries of recolor_mesh, grouped to stay within the number of system parameters
+    (mut color_query, mut road_query, band_query): (
+        Query<(
+            &Mesh2dHandle,
+            &Handle<FillMesh2dMaterial>,
+            Option<&mut MeshSource>,
+        )>,
+        Query<(&mut RoadTint, &Children)>,
+        Query<(&RoadBandColor, &Handle<RoadMesh2dMaterial>)>,
+    ),
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut fill_materials: ResMut<Assets<FillMesh2dMaterial>>,
+    mut road_materials: ResMut<Assets<RoadMesh2dMaterial>>,
 ) {
     if action_event_reader.iter().any(|x| x == &Action::Undo) {
         if history.index == -1 {
@@ -216,10 +401,27 @@ pub fn undo(
                 };
                 move_anchor(&mut commands, move_command, &mut bezier_curves, &maps);
             }
+            HistoryAction::MovedSelection { moves, mesh_moves } => {
+                move_anchors(&mut commands, &moves, true, &mut bezier_curves, &maps);
+                for mesh_move in mesh_moves.iter() {
+                    if let Some(entity) = maps.mesh_map.get(&mesh_move.mesh_id) {
+                        place_mesh(
+                            *entity,
+                            mesh_move.previous_position,
+                            &mut position_query,
+                            &mut fill_materials,
+                            &mut road_materials,
+                        );
+                    }
+                }
+            }
             HistoryAction::SpawnedCurve {
                 bezier_id,
                 bezier_hist: _,
+                copies,
             } => {
+                despawn_groups(&mut commands, &copies, &bezier_curves, &groups, &maps);
+
                 if let Some(handle_entity) = maps.bezier_map.get(&bezier_id.into()) {
                     if let Some(bezier) = bezier_curves.get_mut(&handle_entity.handle) {
                         if let Some(group_handle) = maps.group_map.get(&bezier.group) {
@@ -268,6 +470,7 @@ pub fn undo(
                 partner_id: partner_bezier_id,
                 self_anchor,
                 partner_anchor,
+                corner,
             } => {
                 // info!("undoing unlatch");
                 let handle_entity_1 = maps.bezier_map[&self_id.into()].clone();
@@ -277,6 +480,7 @@ pub fn undo(
                     latched_to_id: partner_bezier_id.into(),
                     self_edge: self_anchor,
                     partners_edge: partner_anchor,
+                    corner,
                 };
 
                 bezier_1.latches.insert(self_anchor, latch_1);
@@ -288,6 +492,7 @@ pub fn undo(
                     latched_to_id: self_id.into(),
                     self_edge: partner_anchor,
                     partners_edge: self_anchor,
+                    corner,
                 };
 
                 bezier_2.latches.insert(partner_anchor, latch_2);
@@ -299,6 +504,87 @@ pub fn undo(
                 }
             }
 
+            HistoryAction::SpawnedMesh { mesh_id } => {
+                stash_mesh_by_id(&mut commands, &mut maps, mesh_id, &pen_mesh_query);
+            }
+            HistoryAction::DeletedMesh { mesh_id } => {
+                restore_mesh(&mut commands, &mut maps, mesh_id);
+            }
+            HistoryAction::MovedMesh {
+                mesh_id,
+                previous_position,
+                new_position: _,
+            } => {
+                if let Some(entity) = maps.mesh_map.get(&mesh_id) {
+                    place_mesh(
+                        *entity,
+                        previous_position,
+                        &mut position_query,
+                        &mut fill_materials,
+                        &mut road_materials,
+                    );
+                }
+            }
+            HistoryAction::RecoloredMesh {
+                mesh_id,
+                previous_color,
+                new_color: _,
+            } => {
+                if let Some(entity) = maps.mesh_map.get(&mesh_id) {
+                    recolor_mesh(
+                        *entity,
+                        previous_color,
+                        &mut color_query,
+                        &mut road_query,
+                        &band_query,
+                        &mut meshes,
+                        &mut fill_materials,
+                        &mut road_materials,
+                    );
+                }
+            }
+            HistoryAction::SpawnedGroups {
+                groups: bezier_hist_groups,
+            } => {
+                despawn_groups(
+                    &mut commands,
+                    &bezier_hist_groups,
+                    &bezier_curves,
+                    &groups,
+                    &maps,
+                );
+            }
+            HistoryAction::AddedPathMarker {
+                group_id,
+                marker_id,
+                ..
+            } => {
+                if let Some(group) = history_group(&mut groups, &maps, group_id) {
+                    group.remove_marker(marker_id);
+                }
+            }
+            HistoryAction::RemovedPathMarker {
+                group_id,
+                marker_id,
+                name,
+                t,
+            } => {
+                if let Some(group) = history_group(&mut groups, &maps, group_id) {
+                    group.insert_marker(marker_id, PathMarker { name, t });
+                }
+            }
+            HistoryAction::MovedPathMarker {
+                group_id,
+                marker_id,
+                previous_t,
+                new_t: _,
+            } => {
+                if let Some(group) = history_group(&mut groups, &maps, group_id) {
+                    group.set_marker_t(marker_id, previous_t);
+                    group.sort_markers();
+                }
+            }
+
             _ => (),
         };
         history.index -= 1;
@@ -306,16 +592,27 @@ pub fn undo(
 }
 
 pub fn add_to_history(
+    mut commands: Commands,
     mut history: ResMut<History>,
+    mut maps: ResMut<Maps>,
     mut add_to_history_event_reader: EventReader<HistoryAction>,
+    mut symmetric_copies_event_reader: EventReader<SpawnedSymmetricCopies>,
     // bezier_curves: ResMut<Assets<Bezier>>,
     // mut action_event_writer: EventWriter<Action>,
 ) {
-    for (k, hist_event) in add_to_history_event_reader.iter().enumerate() {
-        // if history has a tail branching away from the head, remove it, but
-        // only once
-        if k == 0 && history.index > -1 {
-            history.actions = history.actions[0..(history.index + 1) as usize].to_vec();
+    for hist_event in add_to_history_event_reader.iter() {
+        // if history has a tail branching away from the head, remove it
+        let head_len = (history.index + 1) as usize;
+        if history.actions.len() > head_len {
+            let tail = history.actions.split_off(head_len);
+
+            // the meshes spawned by the undone tail are hidden, and can no longer be
+            // redone
+            for action in tail {
+                if let HistoryAction::SpawnedMesh { mesh_id } = action {
+                    free_stashed_mesh(&mut commands, &mut maps, mesh_id);
+                }
+            }
         }
 
         history.actions.push(hist_event.clone());
@@ -323,6 +620,16 @@ pub fn add_to_history(
         // move history head forward
         history.index += 1;
     }
+
+    // the entries of the original curves are in the history by now, as the copies are made
+    // upon reading them
+    for SpawnedSymmetricCopies {
+        original_id,
+        groups,
+    } in symmetric_copies_event_reader.iter()
+    {
+        merge_symmetric_copies(&mut history, *original_id, groups);
+    }
 }
 
 pub fn redo_effects(
@@ -361,11 +668,36 @@ pub fn redo(
     // mut lut_event_writer: EventWriter<ComputeLut>,
     mut delete_curve_event_writer: EventWriter<RedoDelete>,
     mut spawn_curve_event_writer: EventWriter<SpawningCurve>,
+    mut spawn_groups_event_writer: EventWriter<SpawningGroups>,
+    mut groups: ResMut<Assets<Group>>,
     audio: Res<Audio>,
     globals: ResMut<Globals>,
     // mut move_anchor_event_writer: EventWriter<MoveAnchorEvent>,
     // mut selection: ResMut<Selection>,
-    maps: ResMut<Maps>,
+    mut maps: ResMut<Maps>,
+    pen_mesh_query: Query<&PenMesh>,
+    mut position_query: Query<(
+        &mut Transform,
+        Option<&Handle<FillMesh2dMaterial>>,
+        Option<&Handle<RoadMesh2dMaterial>>,
+    )>,
+    // queries of recolor_mesh, grouped to stay within the number of system parameters
+    (mut color_query, mut road_query, band_query): (
+        Query<(
+            &Mesh2dHandle,
+            &Handle<FillMesh2dMaterial>,
+            Option<&mut MeshSource>,
+        )>,
+        Query<(&mut RoadTint, &Children)>,
+        Query<(&RoadBandColor, &Handle<RoadMesh2dMaterial>)>,
+    ),
+    // assets of place_mesh and recolor_mesh, grouped to stay within the number of system
+    // parameters
+    (mut meshes, mut fill_materials, mut road_materials): (
+        ResMut<Assets<Mesh>>,
+        ResMut<Assets<FillMesh2dMaterial>>,
+        ResMut<Assets<RoadMesh2dMaterial>>,
+    ),
 ) {
     if action_event_reader.iter().any(|x| x == &Action::Redo) {
         //
@@ -397,9 +729,25 @@ pub fn redo(
                 move_anchor(&mut commands, move_command, &mut bezier_curves, &maps);
             }
 
+            HistoryAction::MovedSelection { moves, mesh_moves } => {
+                move_anchors(&mut commands, &moves, false, &mut bezier_curves, &maps);
+                for mesh_move in mesh_moves.iter() {
+                    if let Some(entity) = maps.mesh_map.get(&mesh_move.mesh_id) {
+                        place_mesh(
+                            *entity,
+                            mesh_move.new_position,
+                            &mut position_query,
+                            &mut fill_materials,
+                            &mut road_materials,
+                        );
+                    }
+                }
+            }
+
             HistoryAction::SpawnedCurve {
                 bezier_id,
                 bezier_hist,
+                copies,
             } => {
                 // *user_state = UserState::SpawningCurve {
                 //     bezier_hist: Some(bezier_hist),
@@ -410,6 +758,12 @@ pub fn redo(
                     maybe_bezier_id: Some(bezier_id.into()),
                     follow_mouse: false,
                 });
+                if !copies.is_empty() {
+                    spawn_groups_event_writer.send(SpawningGroups {
+                        groups: copies,
+                        do_send_to_history: false,
+                    });
+                }
             }
             HistoryAction::DeletedCurve {
                 bezier: _,
@@ -450,6 +804,7 @@ pub fn redo(
                 partner_id: partner_bezier_id,
                 self_anchor,
                 partner_anchor,
+                ..
             } => {
                 let handle_entity_1 = maps.bezier_map[&self_id.into()].clone();
                 let bezier_1 = bezier_curves.get_mut(&handle_entity_1.handle).unwrap();
@@ -465,6 +820,81 @@ pub fn redo(
                     }
                 }
             }
+            HistoryAction::SpawnedMesh { mesh_id } => {
+                restore_mesh(&mut commands, &mut maps, mesh_id);
+            }
+            HistoryAction::DeletedMesh { mesh_id } => {
+                stash_mesh_by_id(&mut commands, &mut maps, mesh_id, &pen_mesh_query);
+            }
+            HistoryAction::MovedMesh {
+                mesh_id,
+                previous_position: _,
+                new_position,
+            } => {
+                if let Some(entity) = maps.mesh_map.get(&mesh_id) {
+                    place_mesh(
+                        *entity,
+                        new_position,
+                        &mut position_query,
+                        &mut fill_materials,
+                        &mut road_materials,
+                    );
+                }
+            }
+            HistoryAction::RecoloredMesh {
+                mesh_id,
+                previous_color: _,
+                new_color,
+            } => {
+                if let Some(entity) = maps.mesh_map.get(&mesh_id) {
+                    recolor_mesh(
+                        *entity,
+                        new_color,
+                        &mut color_query,
+                        &mut road_query,
+                        &band_query,
+                        &mut meshes,
+                        &mut fill_materials,
+                        &mut road_materials,
+                    );
+                }
+            }
+            HistoryAction::SpawnedGroups { groups } => {
+                spawn_groups_event_writer.send(SpawningGroups {
+                    groups,
+                    do_send_to_history: false,
+                });
+            }
+            HistoryAction::AddedPathMarker {
+                group_id,
+                marker_id,
+                name,
+                t,
+            } => {
+                if let Some(group) = history_group(&mut groups, &maps, group_id) {
+                    group.insert_marker(marker_id, PathMarker { name, t });
+                }
+            }
+            HistoryAction::RemovedPathMarker {
+                group_id,
+                marker_id,
+                ..
+            } => {
+                if let Some(group) = history_group(&mut groups, &maps, group_id) {
+                    group.remove_marker(marker_id);
+                }
+            }
+            HistoryAction::MovedPathMarker {
+                group_id,
+                marker_id,
+                previous_t: _,
+                new_t,
+            } => {
+                if let Some(group) = history_group(&mut groups, &maps, group_id) {
+                    group.set_marker_t(marker_id, new_t);
+                    group.sort_markers();
+                }
+            }
             _ => {}
         }
         history.index += 1;
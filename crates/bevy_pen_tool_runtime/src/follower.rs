@@ -0,0 +1,680 @@
+use crate::lut::{PathMarker, StandaloneLut};
+
+use bevy::prelude::*;
+
+use serde::{Deserialize, Serialize};
+
+/// How a path follower behaves when it reaches the end of the group
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum PlaybackMode {
+    /// Travel from start to end once, then stop
+    Once,
+    /// Jump back to the start after reaching the end
+    #[default]
+    Loop,
+    /// Travel back and forth between the two ends of the group
+    PingPong,
+    /// Loop from the end of the group towards its start
+    Reverse,
+}
+
+/// Easing applied to the progress of a follower over one traversal of the group
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    /// Same convention as the css cubic-bezier(x1, y1, x2, y2) timing function
+    CubicBezier(f32, f32, f32, f32),
+}
+
+impl Easing {
+    /// Maps a progress value between 0 and 1 to an eased progress value between 0 and 1
+    pub fn apply(&self, x: f32) -> f32 {
+        let x = x.clamp(0.0, 1.0);
+        match *self {
+            Easing::Linear => x,
+            Easing::EaseIn => x * x * x,
+            Easing::EaseOut => 1.0 - (1.0 - x).powi(3),
+            Easing::EaseInOut => {
+                if x < 0.5 {
+                    4.0 * x * x * x
+                } else {
+                    1.0 - (-2.0 * x + 2.0).powi(3) / 2.0
+                }
+            }
+            Easing::CubicBezier(x1, y1, x2, y2) => {
+                let s = solve_cubic_bezier_x(x, x1, x2);
+                cubic_bezier_1d(s, y1, y2)
+            }
+        }
+    }
+}
+
+// one dimensional cubic bezier with fixed end points at 0 and 1
+fn cubic_bezier_1d(s: f32, p1: f32, p2: f32) -> f32 {
+    let u = 1.0 - s;
+    3.0 * u * u * s * p1 + 3.0 * u * s * s * p2 + s * s * s
+}
+
+fn cubic_bezier_1d_derivative(s: f32, p1: f32, p2: f32) -> f32 {
+    let u = 1.0 - s;
+    3.0 * u * u * p1 + 6.0 * u * s * (p2 - p1) + 3.0 * s * s * (1.0 - p2)
+}
+
+// finds the curve parameter s for which the x coordinate of the easing curve equals x.
+// Newton's method converges quickly for well-behaved curves, and bisection takes over otherwise
+fn solve_cubic_bezier_x(x: f32, x1: f32, x2: f32) -> f32 {
+    let mut s = x;
+    for _ in 0..8 {
+        let error = cubic_bezier_1d(s, x1, x2) - x;
+        if error.abs() < 1e-6 {
+            return s;
+        }
+        let derivative = cubic_bezier_1d_derivative(s, x1, x2);
+        if derivative.abs() < 1e-6 {
+            break;
+        }
+        s -= error / derivative;
+    }
+
+    let (mut low, mut high) = (0.0, 1.0);
+    s = x;
+    for _ in 0..32 {
+        let value = cubic_bezier_1d(s, x1, x2);
+        if (value - x).abs() < 1e-6 {
+            break;
+        }
+        if value < x {
+            low = s;
+        } else {
+            high = s;
+        }
+        s = (low + high) / 2.0;
+    }
+    s
+}
+
+/// Speed multiplier applied to followers while they are between t_min and t_max on a group.
+/// Overlapping ranges multiply each other.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SpeedRange {
+    pub t_min: f32,
+    pub t_max: f32,
+    pub multiplier: f32,
+}
+
+/// Computes the speed multiplier of a speed profile at a given group t-value
+pub fn speed_multiplier_at(speed_profile: &[SpeedRange], t: f32) -> f32 {
+    speed_profile
+        .iter()
+        .filter(|range| t >= range.t_min && t <= range.t_max)
+        .fold(1.0, |acc, range| acc * range.multiplier)
+}
+
+/// How a follower rotates to face its direction of travel
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum OrientationMode {
+    /// The rotation of the follower is left untouched
+    None,
+    /// Face the exact tangent of the path
+    #[default]
+    Tangent,
+    /// Face a point further along the path, distance given in world units
+    LookAhead { distance: f32 },
+    /// Turn towards the tangent of the path, with a maximum angular speed in radians per second
+    Smoothed { max_angular_speed: f32 },
+}
+
+/// Signed angle (in radians) from a to b, between -PI and PI
+pub fn signed_angle(a: Vec2, b: Vec2) -> f32 {
+    a.perp_dot(b).atan2(a.dot(b))
+}
+
+/// Moves an entity along a path. In the editor, the path is the first group of the canvas.
+/// With the [`PathFollowerPlugin`](crate::PathFollowerPlugin), the path is given by a `Handle<StandaloneLut>`
+/// on the same entity
+#[derive(Component)]
+pub struct PathFollower {
+    /// starting position on the group, as a t-value
+    pub animation_offset: f64,
+    pub initial_direction: Vec3,
+    pub orientation: OrientationMode,
+    /// lateral offset from the path, along its normal
+    pub lane_offset: f32,
+    /// banking strength: the lean angle is atan(speed^2 * curvature * bank_factor)
+    pub bank_factor: f32,
+    pub max_lean: f32,
+    /// lean angle (in radians) caused by the curvature of the path, positive when turning left.
+    /// Computed by the plugin, to be used for banking sprites or models
+    pub lean: f32,
+    pub playback: PlaybackMode,
+    pub easing: Easing,
+    /// speed in world units per second, before the speed profile of the group is applied
+    pub speed: f32,
+    /// position within the current traversal of the group, between 0 and 1
+    pub progress: f32,
+    /// 1.0 when travelling towards the end of the group, -1.0 otherwise
+    pub direction: f32,
+    pub started: bool,
+    pub finished: bool,
+    /// speed after the speed profile has been applied, updated at each step
+    pub current_speed: f32,
+}
+
+impl Default for PathFollower {
+    fn default() -> Self {
+        PathFollower {
+            animation_offset: 0.0,
+            initial_direction: Vec3::X,
+            orientation: OrientationMode::Tangent,
+            lane_offset: 0.0,
+            // inverse of the gravitational acceleration in world units
+            bank_factor: 1.0 / 981.0,
+            max_lean: std::f32::consts::FRAC_PI_4,
+            lean: 0.0,
+            playback: PlaybackMode::Loop,
+            easing: Easing::Linear,
+            speed: 150.0,
+            progress: 0.0,
+            direction: 1.0,
+            started: false,
+            finished: false,
+            current_speed: 0.0,
+        }
+    }
+}
+
+/// Sent by followers when they start moving, reach the end of a Once playback,
+/// or wrap around the group in the other playback modes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FollowerEvent {
+    Started { entity: Entity },
+    Finished { entity: Entity },
+    Wrapped { entity: Entity },
+}
+
+/// Sent when a follower passes over a [`PathMarker`] of the group it follows
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathMarkerReached {
+    pub marker: PathMarker,
+    pub entity: Entity,
+}
+
+/// Finds the markers passed by a follower travelling from t_previous to t_current.
+/// The direction is the one the follower had before the step, and wrapped tells whether
+/// the follower wrapped around (or bounced back at) an end of the group during the step
+pub fn markers_passed(
+    markers: &[PathMarker],
+    t_previous: f32,
+    t_current: f32,
+    direction: f32,
+    wrapped: bool,
+    playback: PlaybackMode,
+) -> Vec<&PathMarker> {
+    markers
+        .iter()
+        .filter(|marker| {
+            let t = marker.t;
+            match (wrapped, playback) {
+                // bounced at one end: every marker between the furthest point and that end is passed
+                (true, PlaybackMode::PingPong) => {
+                    if direction > 0.0 {
+                        t > t_previous.min(t_current)
+                    } else {
+                        t < t_previous.max(t_current)
+                    }
+                }
+                (true, PlaybackMode::Once) => t > t_previous,
+                (true, _) => {
+                    if direction > 0.0 {
+                        t > t_previous || t <= t_current
+                    } else {
+                        t < t_previous || t >= t_current
+                    }
+                }
+                (false, _) => {
+                    if direction > 0.0 {
+                        t > t_previous && t <= t_current
+                    } else {
+                        t < t_previous && t >= t_current
+                    }
+                }
+            }
+        })
+        .collect()
+}
+
+impl PathFollower {
+    /// Initializes the progress of the follower from its offset
+    pub fn start(&mut self) {
+        self.started = true;
+        self.finished = false;
+        self.progress = (self.animation_offset as f32).rem_euclid(1.0);
+        self.direction = match self.playback {
+            PlaybackMode::Reverse => -1.0,
+            _ => 1.0,
+        };
+
+        // a reverse follower starts from the end of the group, or it would wrap on its first step
+        if self.playback == PlaybackMode::Reverse && self.progress == 0.0 {
+            self.progress = 1.0;
+        }
+    }
+
+    /// Advances the progress of the follower by delta (a fraction of the group length).
+    /// Returns true if the follower wrapped around or finished during this step
+    pub fn advance(&mut self, delta: f32) -> bool {
+        if self.finished {
+            return false;
+        }
+
+        self.progress += delta * self.direction;
+
+        match self.playback {
+            PlaybackMode::Once => {
+                if self.progress >= 1.0 {
+                    self.progress = 1.0;
+                    self.finished = true;
+                    return true;
+                }
+            }
+            PlaybackMode::Loop | PlaybackMode::Reverse => {
+                if self.progress >= 1.0 || self.progress < 0.0 {
+                    self.progress = self.progress.rem_euclid(1.0);
+                    return true;
+                }
+            }
+            PlaybackMode::PingPong => {
+                if self.progress > 1.0 {
+                    self.progress = (2.0 - self.progress).max(0.0);
+                    self.direction = -1.0;
+                    return true;
+                } else if self.progress < 0.0 {
+                    self.progress = (-self.progress).min(1.0);
+                    self.direction = 1.0;
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// The eased t-value of the follower on the group.
+    /// Easing is applied in the direction of travel, so that an EaseIn always starts slowly
+    pub fn group_t(&self) -> f32 {
+        if self.direction > 0.0 {
+            self.easing.apply(self.progress)
+        } else {
+            1.0 - self.easing.apply(1.0 - self.progress)
+        }
+    }
+
+    /// Advances the follower along the path for one frame and collects the resulting events.
+    /// The speed multiplier usually comes from the speed profile at the current t-value
+    pub fn step(
+        &mut self,
+        entity: Entity,
+        lut: &StandaloneLut,
+        speed_multiplier: f32,
+        delta_seconds: f32,
+    ) -> (Vec<FollowerEvent>, Vec<PathMarkerReached>) {
+        let mut follower_events = Vec::new();
+        let mut marker_events = Vec::new();
+
+        if lut.path_length <= 0.0 {
+            return (follower_events, marker_events);
+        }
+
+        if !self.started {
+            self.start();
+            follower_events.push(FollowerEvent::Started { entity });
+        }
+
+        // the progress is advanced with the frame time, so the speed of the follower
+        // does not depend on the frame rate
+        let t_previous = self.group_t();
+        let direction = self.direction;
+        self.current_speed = self.speed * speed_multiplier;
+        let delta = delta_seconds * self.current_speed / lut.path_length;
+
+        let wrapped = self.advance(delta);
+        if wrapped {
+            if self.finished {
+                follower_events.push(FollowerEvent::Finished { entity });
+            } else {
+                follower_events.push(FollowerEvent::Wrapped { entity });
+            }
+        }
+
+        for marker in markers_passed(
+            &lut.markers,
+            t_previous,
+            self.group_t(),
+            direction,
+            wrapped,
+            self.playback,
+        ) {
+            marker_events.push(PathMarkerReached {
+                marker: marker.clone(),
+                entity,
+            });
+        }
+
+        (follower_events, marker_events)
+    }
+
+    /// Moves and orients the transform of the follower according to its current t-value.
+    /// normal_at gives the unit normal of the path at a t-value, and is used for the lane offset.
+    /// The translation is multiplied by scale
+    pub fn place(
+        &mut self,
+        transform: &mut Transform,
+        lut: &StandaloneLut,
+        normal_at: impl Fn(f32) -> Vec2,
+        delta_seconds: f32,
+        scale: f32,
+    ) {
+        if lut.path_length <= 0.0 {
+            return;
+        }
+        let t = self.group_t();
+
+        // position on the path, shifted laterally along the normal
+        let lane_offset = self.lane_offset;
+        let offset_position = |t: f32| {
+            let mut pos = lut.position_at(t);
+            if lane_offset != 0.0 {
+                pos += normal_at(t) * lane_offset;
+            }
+            pos
+        };
+
+        let pos = offset_position(t);
+
+        transform.translation.x = pos.x * scale;
+        transform.translation.y = pos.y * scale;
+
+        // direction of travel
+        let forward = lut.tangent_at(t) * self.direction;
+
+        // lean from the signed curvature of the path, measured over a short distance
+        let sample_distance = 5.0;
+        let further_tangent = lut
+            .tangent_at((t + sample_distance / lut.path_length * self.direction).clamp(0.0, 1.0))
+            * self.direction;
+        let curvature = signed_angle(forward, further_tangent) / sample_distance;
+        self.lean = (self.current_speed * self.current_speed * curvature * self.bank_factor)
+            .atan()
+            .clamp(-self.max_lean, self.max_lean);
+
+        let initial_angle = self.initial_direction.y.atan2(self.initial_direction.x);
+        let target_direction = match self.orientation {
+            OrientationMode::None => None,
+            OrientationMode::Tangent | OrientationMode::Smoothed { .. } => Some(forward),
+            OrientationMode::LookAhead { distance } => {
+                let look_ahead = distance / lut.path_length * self.direction;
+                let further_t = match self.playback {
+                    PlaybackMode::Loop | PlaybackMode::Reverse => (t + look_ahead).rem_euclid(1.0),
+                    _ => (t + look_ahead).clamp(0.0, 1.0),
+                };
+                Some((offset_position(further_t) - pos).normalize_or_zero())
+            }
+        };
+
+        if let Some(target_direction) = target_direction {
+            if target_direction != Vec2::ZERO {
+                let target_angle = target_direction.y.atan2(target_direction.x) - initial_angle;
+
+                let new_angle = match self.orientation {
+                    OrientationMode::Smoothed { max_angular_speed } => {
+                        let current_direction = transform
+                            .rotation
+                            .mul_vec3(self.initial_direction)
+                            .truncate();
+                        let current_angle =
+                            current_direction.y.atan2(current_direction.x) - initial_angle;
+                        let max_step = max_angular_speed * delta_seconds;
+                        let step = signed_angle(
+                            Vec2::new(current_angle.cos(), current_angle.sin()),
+                            Vec2::new(target_angle.cos(), target_angle.sin()),
+                        )
+                        .clamp(-max_step, max_step);
+                        current_angle + step
+                    }
+                    _ => target_angle,
+                };
+
+                transform.rotation = Quat::from_rotation_z(new_angle);
+            }
+        }
+    }
+}
+
+/// Moves every entity holding both a [`PathFollower`] and a `Handle<StandaloneLut>` along its path
+pub fn follow_path(
+    mut query: Query<(
+        Entity,
+        &mut Transform,
+        &mut PathFollower,
+        &Handle<StandaloneLut>,
+    )>,
+    luts: Res<Assets<StandaloneLut>>,
+    time: Res<Time>,
+    mut follower_event_writer: EventWriter<FollowerEvent>,
+    mut marker_event_writer: EventWriter<PathMarkerReached>,
+) {
+    for (entity, mut transform, mut follower, lut_handle) in query.iter_mut() {
+        if let Some(lut) = luts.get(lut_handle) {
+            let speed_multiplier = speed_multiplier_at(&lut.speed_profile, follower.group_t());
+            let (follower_events, marker_events) =
+                follower.step(entity, lut, speed_multiplier, time.delta_seconds());
+            follower_event_writer.send_batch(follower_events.into_iter());
+            marker_event_writer.send_batch(marker_events.into_iter());
+
+            follower.place(
+                &mut transform,
+                lut,
+                |t| lut.normal_at(t),
+                time.delta_seconds(),
+                1.0,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marker(name: &str, t: f32) -> PathMarker {
+        PathMarker {
+            name: name.to_string(),
+            t,
+        }
+    }
+
+    fn sample_lut() -> StandaloneLut {
+        let mut lut = StandaloneLut {
+            path_length: 20.0,
+            lut: vec![
+                Vec2::new(0.0, 0.0),
+                Vec2::new(10.0, 0.0),
+                Vec2::new(20.0, 0.0),
+            ],
+            markers: vec![marker("checkpoint", 0.25)],
+            distances: vec![0.0, 10.0, 20.0],
+            ..Default::default()
+        };
+        lut.compute_tangents();
+        lut
+    }
+
+    fn started_follower(playback: PlaybackMode) -> PathFollower {
+        let mut follower = PathFollower {
+            playback,
+            ..Default::default()
+        };
+        follower.start();
+        follower
+    }
+
+    #[test]
+    fn loop_wraps_around() {
+        let mut follower = started_follower(PlaybackMode::Loop);
+        follower.progress = 0.9;
+        assert!(follower.advance(0.2));
+        assert!((follower.progress - 0.1).abs() < 1e-5);
+        assert_eq!(follower.direction, 1.0);
+    }
+
+    #[test]
+    fn once_finishes_at_the_end() {
+        let mut follower = started_follower(PlaybackMode::Once);
+        assert!(!follower.advance(0.6));
+        assert!(follower.advance(0.6));
+        assert!(follower.finished);
+        assert_eq!(follower.progress, 1.0);
+        assert!(!follower.advance(0.6));
+    }
+
+    #[test]
+    fn ping_pong_bounces_at_both_ends() {
+        let mut follower = started_follower(PlaybackMode::PingPong);
+        follower.progress = 0.9;
+        assert!(follower.advance(0.2));
+        assert!((follower.progress - 0.9).abs() < 1e-5);
+        assert_eq!(follower.direction, -1.0);
+
+        follower.progress = 0.1;
+        assert!(follower.advance(0.2));
+        assert!((follower.progress - 0.1).abs() < 1e-5);
+        assert_eq!(follower.direction, 1.0);
+    }
+
+    #[test]
+    fn reverse_starts_at_the_end_without_wrapping() {
+        let mut follower = started_follower(PlaybackMode::Reverse);
+        assert_eq!(follower.progress, 1.0);
+        assert_eq!(follower.direction, -1.0);
+        assert!(!follower.advance(0.3));
+        assert!((follower.progress - 0.7).abs() < 1e-5);
+        assert!(follower.advance(0.8));
+        assert!((follower.progress - 0.9).abs() < 1e-5);
+    }
+
+    #[test]
+    fn markers_passed_without_wrapping() {
+        let markers = vec![marker("a", 0.2), marker("b", 0.5), marker("c", 0.8)];
+        let passed = markers_passed(&markers, 0.1, 0.5, 1.0, false, PlaybackMode::Loop);
+        let names: Vec<&str> = passed.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "b"]);
+
+        let passed = markers_passed(&markers, 0.9, 0.5, -1.0, false, PlaybackMode::Reverse);
+        let names: Vec<&str> = passed.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["b", "c"]);
+    }
+
+    #[test]
+    fn markers_passed_across_a_wrap() {
+        let markers = vec![marker("a", 0.05), marker("b", 0.5), marker("c", 0.95)];
+        let passed = markers_passed(&markers, 0.9, 0.1, 1.0, true, PlaybackMode::Loop);
+        let names: Vec<&str> = passed.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "c"]);
+
+        // bounced at the end: the markers between the furthest point and the end are passed
+        let passed = markers_passed(&markers, 0.9, 0.85, 1.0, true, PlaybackMode::PingPong);
+        let names: Vec<&str> = passed.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["c"]);
+    }
+
+    #[test]
+    fn step_sends_start_marker_and_wrap_events() {
+        let lut = sample_lut();
+        let entity = Entity::from_raw(0);
+        let mut follower = PathFollower {
+            speed: 10.0,
+            ..Default::default()
+        };
+
+        // half of the path per second
+        let (follower_events, marker_events) = follower.step(entity, &lut, 1.0, 1.0);
+        assert_eq!(follower_events, vec![FollowerEvent::Started { entity }]);
+        assert_eq!(marker_events.len(), 1);
+        assert_eq!(marker_events[0].marker.name, "checkpoint");
+        assert!((follower.progress - 0.5).abs() < 1e-5);
+
+        let (follower_events, marker_events) = follower.step(entity, &lut, 1.0, 1.0);
+        assert_eq!(follower_events, vec![FollowerEvent::Wrapped { entity }]);
+        assert!(marker_events.is_empty());
+    }
+
+    #[test]
+    fn step_applies_the_speed_multiplier() {
+        let lut = sample_lut();
+        let entity = Entity::from_raw(0);
+        let mut follower = PathFollower {
+            speed: 10.0,
+            playback: PlaybackMode::Once,
+            ..Default::default()
+        };
+
+        let (follower_events, _) = follower.step(entity, &lut, 2.0, 1.0);
+        assert_eq!(follower.current_speed, 20.0);
+        assert!(follower.finished);
+        assert_eq!(
+            follower_events,
+            vec![
+                FollowerEvent::Started { entity },
+                FollowerEvent::Finished { entity }
+            ]
+        );
+    }
+
+    #[test]
+    fn speed_multipliers_of_overlapping_ranges_multiply() {
+        let profile = vec![
+            SpeedRange {
+                t_min: 0.0,
+                t_max: 0.5,
+                multiplier: 2.0,
+            },
+            SpeedRange {
+                t_min: 0.4,
+                t_max: 1.0,
+                multiplier: 3.0,
+            },
+        ];
+        assert_eq!(speed_multiplier_at(&profile, 0.2), 2.0);
+        assert_eq!(speed_multiplier_at(&profile, 0.45), 6.0);
+        assert_eq!(speed_multiplier_at(&profile, 0.8), 3.0);
+        assert_eq!(speed_multiplier_at(&[], 0.8), 1.0);
+    }
+
+    #[test]
+    fn cubic_bezier_x_is_solved() {
+        for (x1, x2) in [(0.42, 0.58), (0.1, 0.9), (0.9, 0.1), (0.0, 1.0)] {
+            for k in 0..=10 {
+                let x = k as f32 / 10.0;
+                let s = solve_cubic_bezier_x(x, x1, x2);
+                assert!((cubic_bezier_1d(s, x1, x2) - x).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn easings_keep_the_end_points() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+            Easing::CubicBezier(0.25, 0.1, 0.25, 1.0),
+        ] {
+            assert!(easing.apply(0.0).abs() < 1e-4);
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-4);
+        }
+    }
+}
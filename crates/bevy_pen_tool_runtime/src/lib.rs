@@ -0,0 +1,22 @@
+//! Runtime for paths made with bevy_pen_tool. Loads the `.lut` files exported by the editor
+//! and moves [`PathFollower`] entities along them, without depending on the editor.
+
+mod follower;
+mod lut;
+
+pub use follower::*;
+pub use lut::*;
+
+use bevy::prelude::*;
+
+pub struct PathFollowerPlugin;
+
+impl Plugin for PathFollowerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<StandaloneLut>()
+            .init_asset_loader::<LutLoader>()
+            .add_event::<FollowerEvent>()
+            .add_event::<PathMarkerReached>()
+            .add_system(follow_path);
+    }
+}
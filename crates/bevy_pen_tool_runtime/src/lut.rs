@@ -0,0 +1,572 @@
+use crate::follower::SpeedRange;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+
+use serde::{Deserialize, Serialize};
+
+use std::io::Read;
+use std::path::Path;
+
+// map from t-values (between 0 and 1) to point on a path
+pub type LutPosition = Vec<Vec2>;
+
+/// Named position along a path (checkpoint, spawn point, stop sign...), given as a t-value
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PathMarker {
+    pub name: String,
+    pub t: f32,
+}
+
+/// Look-up table of a path, as exported by bevy_pen_tool in `.lut` files.
+/// t is proportional to the distance along the path. Without a distance column, the points
+/// are evenly spaced in t. Adaptive look-up tables store the distance of each point from the
+/// start of the path, and lookups binary-search that column
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default, TypeUuid)]
+#[uuid = "1394fd2b-0658-40b4-8921-f92baf633945"]
+pub struct StandaloneLut {
+    pub path_length: f32,
+    pub lut: LutPosition,
+    // older look-up tables do not have markers
+    #[serde(default)]
+    pub markers: Vec<PathMarker>,
+    // optional unit tangents, one per point of the look-up table
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tangents: Vec<Vec2>,
+    // optional distance from the start of the path, one per point of the look-up table
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub distances: Vec<f32>,
+    // speed multipliers of the path, applied to followers by the runtime
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub speed_profile: Vec<SpeedRange>,
+}
+
+/// Encoding of `.lut` files. Both are detected automatically when loading
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum LutFormat {
+    /// Pretty-printed JSON, for interchange with other tools
+    #[default]
+    Json,
+    /// Compact binary encoding, see [`StandaloneLut::to_binary`]
+    Binary,
+}
+
+/// First bytes of a binary `.lut` file
+pub const LUT_MAGIC: &[u8; 4] = b"BPTL";
+pub const LUT_BINARY_VERSION: u16 = 3;
+
+// flags of the binary header
+const LUT_FLAG_TANGENTS: u16 = 1;
+const LUT_FLAG_MARKERS: u16 = 1 << 1;
+const LUT_FLAG_DISTANCES: u16 = 1 << 2;
+const LUT_FLAG_SPEED_PROFILE: u16 = 1 << 3;
+
+#[derive(Debug)]
+pub enum LutError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Binary(String),
+}
+
+impl std::fmt::Display for LutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LutError::Io(e) => write!(f, "could not read look-up table: {}", e),
+            LutError::Json(e) => write!(f, "could not parse look-up table: {}", e),
+            LutError::Binary(e) => write!(f, "could not decode binary look-up table: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for LutError {}
+
+impl From<std::io::Error> for LutError {
+    fn from(e: std::io::Error) -> Self {
+        LutError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for LutError {
+    fn from(e: serde_json::Error) -> Self {
+        LutError::Json(e)
+    }
+}
+
+impl StandaloneLut {
+    /// Reads a look-up table from a `.lut` file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<StandaloneLut, LutError> {
+        let mut file = std::fs::File::open(path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        StandaloneLut::from_bytes(&bytes)
+    }
+
+    /// Decodes the content of a `.lut` file, in either the JSON or the binary format
+    pub fn from_bytes(bytes: &[u8]) -> Result<StandaloneLut, LutError> {
+        if bytes.starts_with(LUT_MAGIC) {
+            StandaloneLut::from_binary(bytes)
+        } else {
+            Ok(serde_json::from_slice(bytes)?)
+        }
+    }
+
+    /// Encodes the look-up table in the given format, ready to be written to a `.lut` file
+    pub fn encode(&self, format: LutFormat) -> Vec<u8> {
+        match format {
+            LutFormat::Json => self.to_json().into_bytes(),
+            LutFormat::Binary => self.to_binary(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Binary encoding, all numbers in little endian:
+    ///
+    /// - header: magic "BPTL", version (u16), flags (u16), point count (u32), path length (f32)
+    /// - points: count x (f32, f32)
+    /// - if flags & 1: tangents, count x (f32, f32)
+    /// - if flags & 4: distances, count x f32 (since version 2)
+    /// - if flags & 2: marker count (u32), then for each marker: t (f32), name length (u32), utf-8 name
+    /// - if flags & 8: speed range count (u32), then for each range: t_min, t_max, multiplier (f32) (since version 3)
+    pub fn to_binary(&self) -> Vec<u8> {
+        let has_tangents = !self.tangents.is_empty() && self.tangents.len() == self.lut.len();
+        let has_markers = !self.markers.is_empty();
+        let has_distances = self.has_distances();
+        let has_speed_profile = !self.speed_profile.is_empty();
+
+        let mut flags: u16 = 0;
+        if has_tangents {
+            flags |= LUT_FLAG_TANGENTS;
+        }
+        if has_markers {
+            flags |= LUT_FLAG_MARKERS;
+        }
+        if has_distances {
+            flags |= LUT_FLAG_DISTANCES;
+        }
+        if has_speed_profile {
+            flags |= LUT_FLAG_SPEED_PROFILE;
+        }
+
+        let mut bytes = Vec::with_capacity(16 + self.lut.len() * 16);
+        bytes.extend_from_slice(LUT_MAGIC);
+        bytes.extend_from_slice(&LUT_BINARY_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&flags.to_le_bytes());
+        bytes.extend_from_slice(&(self.lut.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.path_length.to_le_bytes());
+
+        for point in self.lut.iter() {
+            bytes.extend_from_slice(&point.x.to_le_bytes());
+            bytes.extend_from_slice(&point.y.to_le_bytes());
+        }
+
+        if has_tangents {
+            for tangent in self.tangents.iter() {
+                bytes.extend_from_slice(&tangent.x.to_le_bytes());
+                bytes.extend_from_slice(&tangent.y.to_le_bytes());
+            }
+        }
+
+        if has_distances {
+            for distance in self.distances.iter() {
+                bytes.extend_from_slice(&distance.to_le_bytes());
+            }
+        }
+
+        if has_markers {
+            bytes.extend_from_slice(&(self.markers.len() as u32).to_le_bytes());
+            for marker in self.markers.iter() {
+                bytes.extend_from_slice(&marker.t.to_le_bytes());
+                bytes.extend_from_slice(&(marker.name.len() as u32).to_le_bytes());
+                bytes.extend_from_slice(marker.name.as_bytes());
+            }
+        }
+
+        if has_speed_profile {
+            bytes.extend_from_slice(&(self.speed_profile.len() as u32).to_le_bytes());
+            for range in self.speed_profile.iter() {
+                bytes.extend_from_slice(&range.t_min.to_le_bytes());
+                bytes.extend_from_slice(&range.t_max.to_le_bytes());
+                bytes.extend_from_slice(&range.multiplier.to_le_bytes());
+            }
+        }
+
+        bytes
+    }
+
+    /// Decodes the binary encoding described in [`StandaloneLut::to_binary`]
+    pub fn from_binary(bytes: &[u8]) -> Result<StandaloneLut, LutError> {
+        let mut reader = BinaryReader { bytes, position: 0 };
+
+        if reader.take(4)? != LUT_MAGIC {
+            return Err(LutError::Binary("missing magic number".to_string()));
+        }
+        let version = reader.u16()?;
+        if version > LUT_BINARY_VERSION {
+            return Err(LutError::Binary(format!("unsupported version {}", version)));
+        }
+        let flags = reader.u16()?;
+        let count = reader.u32()? as usize;
+        let path_length = reader.f32()?;
+
+        // the count comes from the file, so it is checked against the size of the data
+        // before anything is allocated
+        let mut bytes_per_point = 8;
+        if flags & LUT_FLAG_TANGENTS != 0 {
+            bytes_per_point += 8;
+        }
+        if flags & LUT_FLAG_DISTANCES != 0 {
+            bytes_per_point += 4;
+        }
+        let needed = count.checked_mul(bytes_per_point);
+        if needed.is_none() || needed.unwrap() > reader.remaining() {
+            return Err(LutError::Binary(format!(
+                "point count {} does not fit in the file",
+                count
+            )));
+        }
+
+        let mut lut = Vec::with_capacity(count);
+        for _ in 0..count {
+            lut.push(reader.vec2()?);
+        }
+
+        let mut tangents = Vec::new();
+        if flags & LUT_FLAG_TANGENTS != 0 {
+            tangents.reserve(count);
+            for _ in 0..count {
+                tangents.push(reader.vec2()?);
+            }
+        }
+
+        let mut distances = Vec::new();
+        if flags & LUT_FLAG_DISTANCES != 0 {
+            distances.reserve(count);
+            for _ in 0..count {
+                distances.push(reader.f32()?);
+            }
+        }
+
+        let mut markers = Vec::new();
+        if flags & LUT_FLAG_MARKERS != 0 {
+            let num_markers = reader.u32()?;
+            for _ in 0..num_markers {
+                let t = reader.f32()?;
+                let name_length = reader.u32()? as usize;
+                let name = std::str::from_utf8(reader.take(name_length)?)
+                    .map_err(|e| LutError::Binary(e.to_string()))?
+                    .to_string();
+                markers.push(PathMarker { name, t });
+            }
+        }
+
+        let mut speed_profile = Vec::new();
+        if flags & LUT_FLAG_SPEED_PROFILE != 0 {
+            let num_ranges = reader.u32()?;
+            for _ in 0..num_ranges {
+                speed_profile.push(SpeedRange {
+                    t_min: reader.f32()?,
+                    t_max: reader.f32()?,
+                    multiplier: reader.f32()?,
+                });
+            }
+        }
+
+        Ok(StandaloneLut {
+            path_length,
+            lut,
+            markers,
+            tangents,
+            distances,
+            speed_profile,
+        })
+    }
+
+    /// Fills the tangents of the look-up table from its points
+    pub fn compute_tangents(&mut self) {
+        self.tangents = Vec::new();
+        let num_points = self.lut.len();
+        if num_points < 2 {
+            return;
+        }
+        for idx in 0..num_points {
+            let before = self.lut[idx.saturating_sub(1)];
+            let after = self.lut[(idx + 1).min(num_points - 1)];
+            self.tangents.push((after - before).normalize_or_zero());
+        }
+    }
+
+    fn has_distances(&self) -> bool {
+        self.lut.len() > 1 && self.distances.len() == self.lut.len()
+    }
+
+    // index of the segment of the look-up table containing a t-value,
+    // and the position of t inside that segment (between 0 and 1)
+    fn segment_at(&self, t: f32) -> (usize, f32) {
+        let num_points = self.lut.len();
+        let t = t.clamp(0.0, 1.0);
+
+        if self.has_distances() {
+            let distance = t * self.distances[num_points - 1];
+            // first point further than the distance, which ends the segment
+            let end = self.distances.partition_point(|d| *d <= distance);
+            let idx = end.saturating_sub(1).min(num_points - 2);
+            let segment_length = self.distances[idx + 1] - self.distances[idx];
+            let rem = if segment_length > 0.0 {
+                ((distance - self.distances[idx]) / segment_length).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            return (idx, rem);
+        }
+
+        let idx_f32 = t * (num_points - 1) as f32;
+        // t = 1 lands on the last point, which has no successor
+        let idx = (idx_f32 as usize).min(num_points - 2);
+        (idx, idx_f32 - idx as f32)
+    }
+
+    /// Position on the path at a given t-value (between 0 and 1)
+    pub fn position_at(&self, t: f32) -> Vec2 {
+        let lut = &self.lut;
+        if lut.len() > 1 {
+            let (idx, rem) = self.segment_at(t);
+            lut[idx] + (lut[idx + 1] - lut[idx]) * rem
+        } else if lut.len() == 1 {
+            lut[0]
+        } else {
+            Vec2::ZERO
+        }
+    }
+
+    /// Converts a distance from the start of the path into a t-value
+    pub fn t_at_distance(&self, distance: f32) -> f32 {
+        if self.path_length > 0.0 {
+            (distance / self.path_length).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// Position on the path at a given distance from its start
+    pub fn position_at_distance(&self, distance: f32) -> Vec2 {
+        self.position_at(self.t_at_distance(distance))
+    }
+
+    /// Unit tangent of the path at a given t-value, in the direction of increasing t
+    pub fn tangent_at(&self, t: f32) -> Vec2 {
+        let num_points = self.lut.len();
+        if num_points < 2 {
+            return Vec2::X;
+        }
+
+        // use the stored tangents when the look-up table has them
+        if self.tangents.len() == num_points {
+            let (idx, rem) = self.segment_at(t);
+            let tangent = self.tangents[idx].lerp(self.tangents[idx + 1], rem);
+            return tangent.normalize_or_zero();
+        }
+
+        // the points of an adaptive look-up table can be far apart on straight parts,
+        // so the direction of the segment is used instead of a central difference
+        if self.has_distances() {
+            let (idx, _rem) = self.segment_at(t);
+            return (self.lut[idx + 1] - self.lut[idx]).normalize_or_zero();
+        }
+
+        // central difference over one look-up table step
+        let h = 1.0 / (num_points - 1) as f32;
+        let t = t.clamp(0.0, 1.0);
+        let before = self.position_at((t - h).max(0.0));
+        let after = self.position_at((t + h).min(1.0));
+        (after - before).normalize_or_zero()
+    }
+
+    pub fn tangent_at_distance(&self, distance: f32) -> Vec2 {
+        self.tangent_at(self.t_at_distance(distance))
+    }
+
+    /// Unit normal of the path at a given t-value, pointing to the left of the direction of travel
+    pub fn normal_at(&self, t: f32) -> Vec2 {
+        self.tangent_at(t).perp()
+    }
+
+    pub fn marker(&self, name: &str) -> Option<&PathMarker> {
+        self.markers.iter().find(|marker| marker.name == name)
+    }
+
+    /// Finds the t-value of the point of the path closest to a position,
+    /// along with the distance between the two
+    pub fn closest_t(&self, position: Vec2) -> Option<(f32, f32)> {
+        let lut = &self.lut;
+        if lut.len() < 2 {
+            return None;
+        }
+        let num_segments = (lut.len() - 1) as f32;
+        let mut closest: Option<(f32, f32)> = None;
+        for (idx, segment) in lut.windows(2).enumerate() {
+            // project the position onto the segment
+            let direction = segment[1] - segment[0];
+            let length_squared = direction.length_squared();
+            let rem = if length_squared > 0.0 {
+                ((position - segment[0]).dot(direction) / length_squared).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let distance = position.distance(segment[0] + direction * rem);
+            if closest.is_none() || distance < closest.unwrap().1 {
+                let t = if self.has_distances() {
+                    let start = self.distances[idx];
+                    let end = self.distances[idx + 1];
+                    (start + (end - start) * rem) / self.distances[lut.len() - 1].max(f32::EPSILON)
+                } else {
+                    (idx as f32 + rem) / num_segments
+                };
+                closest = Some((t, distance));
+            }
+        }
+        closest
+    }
+}
+
+/// Loads `.lut` files through the AssetServer
+#[derive(Default)]
+pub struct LutLoader;
+
+impl AssetLoader for LutLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let lut = StandaloneLut::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(lut));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["lut"]
+    }
+}
+
+// reads little endian numbers from the binary encoding of a look-up table
+struct BinaryReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BinaryReader<'a> {
+    fn take(&mut self, num_bytes: usize) -> Result<&'a [u8], LutError> {
+        if num_bytes > self.remaining() {
+            return Err(LutError::Binary("unexpected end of file".to_string()));
+        }
+        let end = self.position + num_bytes;
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.position
+    }
+
+    fn u16(&mut self) -> Result<u16, LutError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, LutError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> Result<f32, LutError> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn vec2(&mut self) -> Result<Vec2, LutError> {
+        Ok(Vec2::new(self.f32()?, self.f32()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_lut() -> StandaloneLut {
+        let mut lut = StandaloneLut {
+            path_length: 20.0,
+            lut: vec![
+                Vec2::new(0.0, 0.0),
+                Vec2::new(10.0, 0.0),
+                Vec2::new(10.0, 10.0),
+            ],
+            markers: vec![PathMarker {
+                name: "checkpoint".to_string(),
+                t: 0.25,
+            }],
+            distances: vec![0.0, 10.0, 20.0],
+            speed_profile: vec![SpeedRange {
+                t_min: 0.5,
+                t_max: 1.0,
+                multiplier: 2.0,
+            }],
+            ..Default::default()
+        };
+        lut.compute_tangents();
+        lut
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let lut = sample_lut();
+        let bytes = lut.to_binary();
+        assert!(bytes.starts_with(LUT_MAGIC));
+        assert_eq!(StandaloneLut::from_bytes(&bytes).unwrap(), lut);
+    }
+
+    #[test]
+    fn binary_round_trip_points_only() {
+        let lut = StandaloneLut {
+            path_length: 1.0,
+            lut: vec![Vec2::ZERO, Vec2::X],
+            ..Default::default()
+        };
+        assert_eq!(StandaloneLut::from_binary(&lut.to_binary()).unwrap(), lut);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let lut = sample_lut();
+        let bytes = lut.encode(LutFormat::Json);
+        assert_eq!(StandaloneLut::from_bytes(&bytes).unwrap(), lut);
+    }
+
+    #[test]
+    fn truncated_binary_is_an_error() {
+        let bytes = sample_lut().to_binary();
+        for len in [4, 15, 20, bytes.len() - 1] {
+            assert!(matches!(
+                StandaloneLut::from_binary(&bytes[..len]),
+                Err(LutError::Binary(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn oversized_count_is_rejected_before_allocating() {
+        let mut bytes = sample_lut().to_binary();
+        // point count of the header
+        bytes[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            StandaloneLut::from_binary(&bytes),
+            Err(LutError::Binary(_))
+        ));
+    }
+}
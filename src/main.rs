@@ -1,6 +1,8 @@
 mod cam;
 
-use bevy_pen_tool_plugin::{Bezier, Globals, PenPlugin};
+use bevy_pen_tool_plugin::{
+    Bezier, Globals, LutFormat, PenPlugin, RoadBand, RoadEndCap, RoadProfile,
+};
 use cam::{Cam, CamPlugin};
 
 use bevy::{prelude::*, render::camera::OrthographicProjection};
@@ -53,6 +55,30 @@ fn camera_setup(mut commands: Commands, mut globals: ResMut<Globals>) {
     // make an animation smoother, but will take more space in memory
     globals.group_lut_num_points = 100;
     globals.road_width = 8.0;
+
+    // roads are made of bands (lanes, markings, curbs, sidewalks...), listed from left to right.
+    // RoadProfile::single_lane(globals.road_width) makes the textured single-lane road instead.
+    // Roads along open chains end with the given cap, roads along loops are closed
+    globals.road_profile = RoadProfile::new(vec![
+        RoadBand::sidewalk(3.0),
+        RoadBand::curb(0.5),
+        RoadBand::marking(0.3),
+        RoadBand::lane(6.0),
+        RoadBand::dashed_marking(0.3, 6.0, 6.0),
+        RoadBand::lane(6.0),
+        RoadBand::marking(0.3),
+        RoadBand::curb(0.5),
+        RoadBand::sidewalk(3.0),
+    ])
+    .with_end_cap(RoadEndCap::Rounded);
+
+    // fill meshes are tessellated from the curves themselves, and stay within this distance
+    // of them whatever the size of the look-up tables
+    globals.fill_tolerance = 0.05;
+
+    // look-up tables can be saved as JSON or in the binary format, which is much smaller
+    // and faster to load, and both formats are detected automatically when loading
+    globals.lut_format = LutFormat::Json;
 }
 
 fn test(keyboard_input: Res<Input<KeyCode>>, mut _bezier_curves: ResMut<Assets<Bezier>>) {